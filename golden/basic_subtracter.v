@@ -0,0 +1,12 @@
+// main generated by ACL
+
+module main #(parameter WIDTH = 32) (
+    input signed [WIDTH-1:0] number_1,
+    input signed [WIDTH-1:0] number_2,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n2;
+
+    assign n2 = number_1 - number_2;
+    assign out0 = n2;
+endmodule
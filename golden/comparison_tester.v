@@ -0,0 +1,190 @@
+// main generated by ACL
+
+module clamp #(parameter WIDTH = 32) (
+    input signed [WIDTH-1:0] value,
+    input signed [WIDTH-1:0] low,
+    input signed [WIDTH-1:0] high,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n3;
+    wire signed [WIDTH-1:0] n4;
+    wire signed [WIDTH-1:0] n5;
+    wire signed [WIDTH-1:0] n6;
+    wire signed [WIDTH-1:0] n7;
+    wire signed [WIDTH-1:0] n8;
+    wire signed [WIDTH-1:0] n9;
+    wire signed [WIDTH-1:0] n10;
+    wire signed [WIDTH-1:0] n11;
+    wire signed [WIDTH-1:0] n12;
+    wire signed [WIDTH-1:0] n13;
+    wire signed [WIDTH-1:0] n14;
+    wire signed [WIDTH-1:0] n15;
+    wire signed [WIDTH-1:0] n16;
+    wire signed [WIDTH-1:0] n17;
+    wire signed [WIDTH-1:0] n18;
+    wire signed [WIDTH-1:0] n19;
+    wire signed [WIDTH-1:0] n20;
+    wire signed [WIDTH-1:0] n21;
+    wire signed [WIDTH-1:0] n22;
+    wire signed [WIDTH-1:0] n23;
+    wire signed [WIDTH-1:0] n24;
+    wire signed [WIDTH-1:0] n25;
+    wire signed [WIDTH-1:0] n26;
+    wire signed [WIDTH-1:0] n27;
+    wire signed [WIDTH-1:0] n28;
+    wire signed [WIDTH-1:0] n29;
+    wire signed [WIDTH-1:0] n30;
+    wire signed [WIDTH-1:0] n31;
+    wire signed [WIDTH-1:0] n32;
+    wire signed [WIDTH-1:0] n33;
+    wire signed [WIDTH-1:0] n34;
+    wire signed [WIDTH-1:0] n35;
+
+    assign n3 = low > value ? 1 : 0;
+    assign n4 = 1;
+    assign n5 = 0;
+    assign n6 = n3 != 0 ? 0 : n4;
+    assign n7 = n3 != 0 ? n4 : 0;
+    assign n8 = n3 != 0 ? 0 : n5;
+    assign n9 = n3 != 0 ? n5 : 0;
+    assign n10 = n7 + n8;
+    assign n11 = high > value ? 1 : 0;
+    assign n12 = n11 != 0 ? 0 : 1;
+    assign n13 = 1;
+    assign n14 = 0;
+    assign n15 = n12 != 0 ? 0 : n13;
+    assign n16 = n12 != 0 ? n13 : 0;
+    assign n17 = n12 != 0 ? 0 : n14;
+    assign n18 = n12 != 0 ? n14 : 0;
+    assign n19 = n16 + n17;
+    assign n20 = 1;
+    assign n21 = n10 != 0 ? 0 : n20;
+    assign n22 = n10 != 0 ? n20 : 0;
+    assign n23 = n10 != 0 ? 0 : n19;
+    assign n24 = n10 != 0 ? n19 : 0;
+    assign n25 = n22 + n23;
+    assign n26 = n10 != 0 ? 0 : low;
+    assign n27 = n10 != 0 ? low : 0;
+    assign n28 = n10 != 0 ? 0 : high;
+    assign n29 = n10 != 0 ? high : 0;
+    assign n30 = n27 + n28;
+    assign n31 = n25 != 0 ? 0 : n30;
+    assign n32 = n25 != 0 ? n30 : 0;
+    assign n33 = n25 != 0 ? 0 : value;
+    assign n34 = n25 != 0 ? value : 0;
+    assign n35 = n32 + n33;
+    assign out0 = n35;
+endmodule
+
+module main #(parameter WIDTH = 32) (
+    input signed [WIDTH-1:0] a,
+    input signed [WIDTH-1:0] b,
+    input signed [WIDTH-1:0] c,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n3;
+    wire signed [WIDTH-1:0] n4;
+    wire signed [WIDTH-1:0] n5;
+    wire signed [WIDTH-1:0] n6;
+    wire signed [WIDTH-1:0] n7;
+    wire signed [WIDTH-1:0] n8;
+    wire signed [WIDTH-1:0] n9;
+    wire signed [WIDTH-1:0] n10;
+    wire signed [WIDTH-1:0] n11;
+    wire signed [WIDTH-1:0] n12;
+    wire signed [WIDTH-1:0] n13;
+    wire signed [WIDTH-1:0] n14;
+    wire signed [WIDTH-1:0] n15;
+    wire signed [WIDTH-1:0] n16;
+    wire signed [WIDTH-1:0] n17;
+    wire signed [WIDTH-1:0] n18;
+    wire signed [WIDTH-1:0] n19;
+    wire signed [WIDTH-1:0] n20;
+    wire signed [WIDTH-1:0] n21;
+    wire signed [WIDTH-1:0] n22;
+    wire signed [WIDTH-1:0] n23;
+    wire signed [WIDTH-1:0] n24;
+    wire signed [WIDTH-1:0] n25;
+    wire signed [WIDTH-1:0] n26;
+    wire signed [WIDTH-1:0] n27;
+    wire signed [WIDTH-1:0] n28;
+    wire signed [WIDTH-1:0] n29;
+    wire signed [WIDTH-1:0] n30;
+    wire signed [WIDTH-1:0] n31;
+    wire signed [WIDTH-1:0] n32;
+    wire signed [WIDTH-1:0] n33;
+    wire signed [WIDTH-1:0] n34;
+    wire signed [WIDTH-1:0] n35;
+    wire signed [WIDTH-1:0] n36;
+    wire signed [WIDTH-1:0] n37;
+    wire signed [WIDTH-1:0] n38;
+    wire signed [WIDTH-1:0] n39;
+    wire signed [WIDTH-1:0] n40;
+    wire signed [WIDTH-1:0] n41;
+    wire signed [WIDTH-1:0] n42;
+    wire signed [WIDTH-1:0] n43;
+    wire signed [WIDTH-1:0] n44;
+    wire signed [WIDTH-1:0] n45;
+    wire signed [WIDTH-1:0] n46;
+    wire signed [WIDTH-1:0] n47;
+    wire signed [WIDTH-1:0] n48;
+    wire signed [WIDTH-1:0] n49;
+    wire signed [WIDTH-1:0] n50;
+    wire signed [WIDTH-1:0] n51;
+    wire signed [WIDTH-1:0] n52;
+    wire signed [WIDTH-1:0] n53;
+
+    clamp #(.WIDTH(WIDTH)) clamp_0 (n49, b, c, n50);
+
+    assign n3 = 0;
+    assign n4 = 0;
+    assign n5 = a + n4;
+    assign n6 = n5 > b ? 1 : 0;
+    assign n7 = b > n5 ? 1 : 0;
+    assign n8 = n6 + n7;
+    assign n9 = n8 != 0 ? 0 : 1;
+    assign n10 = 10;
+    assign n11 = n3 + n10;
+    assign n12 = n9 != 0 ? 0 : n11;
+    assign n13 = n9 != 0 ? n11 : 0;
+    assign n14 = n9 != 0 ? 0 : n3;
+    assign n15 = n9 != 0 ? n3 : 0;
+    assign n16 = n13 + n14;
+    assign n17 = a > c ? 1 : 0;
+    assign n18 = c > a ? 1 : 0;
+    assign n19 = n17 + n18;
+    assign n20 = 1;
+    assign n21 = n16 - n20;
+    assign n22 = n19 != 0 ? 0 : n21;
+    assign n23 = n19 != 0 ? n21 : 0;
+    assign n24 = n19 != 0 ? 0 : n16;
+    assign n25 = n19 != 0 ? n16 : 0;
+    assign n26 = n23 + n24;
+    assign n27 = 1;
+    assign n28 = a + n27;
+    assign n29 = n28 > b ? 1 : 0;
+    assign n30 = b > n28 ? 1 : 0;
+    assign n31 = n29 + n30;
+    assign n32 = n31 != 0 ? 0 : 1;
+    assign n33 = 10;
+    assign n34 = n26 + n33;
+    assign n35 = n32 != 0 ? 0 : n34;
+    assign n36 = n32 != 0 ? n34 : 0;
+    assign n37 = n32 != 0 ? 0 : n26;
+    assign n38 = n32 != 0 ? n26 : 0;
+    assign n39 = n36 + n37;
+    assign n40 = a > c ? 1 : 0;
+    assign n41 = c > a ? 1 : 0;
+    assign n42 = n40 + n41;
+    assign n43 = 1;
+    assign n44 = n39 - n43;
+    assign n45 = n42 != 0 ? 0 : n44;
+    assign n46 = n42 != 0 ? n44 : 0;
+    assign n47 = n42 != 0 ? 0 : n39;
+    assign n48 = n42 != 0 ? n39 : 0;
+    assign n49 = n46 + n47;
+    assign n51 = a > c ? 1 : 0;
+    assign n52 = n51 != 0 ? 0 : 1;
+    assign n53 = n50 * n52;
+    assign out0 = n53;
+endmodule
@@ -0,0 +1 @@
+FractionalQuotient { module: "average", part: "Divider#0" }
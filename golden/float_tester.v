@@ -0,0 +1 @@
+FractionalConstant { module: "attenuate", part: "Constant#0", value: 0.25 }
@@ -0,0 +1,48 @@
+// main generated by ACL
+
+module main #(parameter WIDTH = 32) (
+    input signed [WIDTH-1:0] number_1,
+    input signed [WIDTH-1:0] number_2
+);
+    wire signed [WIDTH-1:0] n2;
+    wire signed [WIDTH-1:0] n3;
+    wire signed [WIDTH-1:0] n4;
+    wire signed [WIDTH-1:0] n5;
+    wire signed [WIDTH-1:0] n6;
+    wire signed [WIDTH-1:0] n7;
+    wire signed [WIDTH-1:0] n8;
+    wire signed [WIDTH-1:0] n9;
+    wire signed [WIDTH-1:0] n10;
+    wire signed [WIDTH-1:0] n11;
+    wire signed [WIDTH-1:0] n12;
+    wire signed [WIDTH-1:0] n13;
+    wire signed [WIDTH-1:0] n14;
+    wire signed [WIDTH-1:0] n15;
+    wire signed [WIDTH-1:0] n16;
+    wire signed [WIDTH-1:0] n17;
+    wire signed [WIDTH-1:0] n18;
+    wire signed [WIDTH-1:0] n19;
+    wire signed [WIDTH-1:0] n20;
+    wire signed [WIDTH-1:0] n21;
+
+    assign n2 = 0;
+    assign n3 = number_1 + n2;
+    assign n4 = 1;
+    assign n5 = n3 + n4;
+    assign n6 = 2;
+    assign n7 = n5 + n6;
+    assign n8 = 3;
+    assign n9 = n7 + n8;
+    assign n10 = 4;
+    assign n11 = n9 + n10;
+    assign n12 = 5;
+    assign n13 = n11 + n12;
+    assign n14 = 6;
+    assign n15 = n13 + n14;
+    assign n16 = 7;
+    assign n17 = n15 + n16;
+    assign n18 = 8;
+    assign n19 = n17 + n18;
+    assign n20 = 9;
+    assign n21 = n19 + n20;
+endmodule
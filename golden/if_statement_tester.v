@@ -0,0 +1,26 @@
+// main generated by ACL
+
+module main #(parameter WIDTH = 32) (
+    input signed [WIDTH-1:0] number_1,
+    input signed [WIDTH-1:0] number_2,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n2;
+    wire signed [WIDTH-1:0] n3;
+    wire signed [WIDTH-1:0] n4;
+    wire signed [WIDTH-1:0] n5;
+    wire signed [WIDTH-1:0] n6;
+    wire signed [WIDTH-1:0] n7;
+    wire signed [WIDTH-1:0] n8;
+    wire signed [WIDTH-1:0] n9;
+
+    assign n2 = number_1 > number_2 ? 1 : 0;
+    assign n3 = number_1 - number_2;
+    assign n4 = number_1 + number_2;
+    assign n5 = n2 != 0 ? 0 : n3;
+    assign n6 = n2 != 0 ? n3 : 0;
+    assign n7 = n2 != 0 ? 0 : n4;
+    assign n8 = n2 != 0 ? n4 : 0;
+    assign n9 = n6 + n7;
+    assign out0 = n9;
+endmodule
@@ -0,0 +1,88 @@
+// main generated by ACL
+
+module in_window #(parameter WIDTH = 32) (
+    input signed [WIDTH-1:0] value,
+    input signed [WIDTH-1:0] low,
+    input signed [WIDTH-1:0] high,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n3;
+    wire signed [WIDTH-1:0] n4;
+    wire signed [WIDTH-1:0] n5;
+
+    assign n3 = value > low ? 1 : 0;
+    assign n4 = high > value ? 1 : 0;
+    assign n5 = (n3 != 0 && n4 != 0) ? 1 : 0;
+    assign out0 = n5;
+endmodule
+
+module main #(parameter WIDTH = 32) (
+    input signed [WIDTH-1:0] a,
+    input signed [WIDTH-1:0] b,
+    input signed [WIDTH-1:0] enabled,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n3;
+    wire signed [WIDTH-1:0] n4;
+    wire signed [WIDTH-1:0] n5;
+    wire signed [WIDTH-1:0] n6;
+    wire signed [WIDTH-1:0] n7;
+    wire signed [WIDTH-1:0] n8;
+    wire signed [WIDTH-1:0] n9;
+    wire signed [WIDTH-1:0] n10;
+    wire signed [WIDTH-1:0] n11;
+    wire signed [WIDTH-1:0] n12;
+    wire signed [WIDTH-1:0] n13;
+    wire signed [WIDTH-1:0] n14;
+    wire signed [WIDTH-1:0] n15;
+    wire signed [WIDTH-1:0] n16;
+    wire signed [WIDTH-1:0] n17;
+    wire signed [WIDTH-1:0] n18;
+    wire signed [WIDTH-1:0] n19;
+    wire signed [WIDTH-1:0] n20;
+    wire signed [WIDTH-1:0] n21;
+    wire signed [WIDTH-1:0] n22;
+    wire signed [WIDTH-1:0] n23;
+    wire signed [WIDTH-1:0] n24;
+    wire signed [WIDTH-1:0] n25;
+    wire signed [WIDTH-1:0] n26;
+    wire signed [WIDTH-1:0] n27;
+    wire signed [WIDTH-1:0] n28;
+    wire signed [WIDTH-1:0] n29;
+    wire signed [WIDTH-1:0] n30;
+    wire signed [WIDTH-1:0] n31;
+    wire signed [WIDTH-1:0] n32;
+
+    in_window #(.WIDTH(WIDTH)) in_window_0 (a, n19, n20, n21);
+
+    assign n3 = 0;
+    assign n4 = 0;
+    assign n5 = a > n4 ? 1 : 0;
+    assign n6 = 10;
+    assign n7 = n6 > b ? 1 : 0;
+    assign n8 = (n5 != 0 && n7 != 0) ? 1 : 0;
+    assign n9 = enabled != 0 ? 0 : 1;
+    assign n10 = (n8 != 0 || n9 != 0) ? 1 : 0;
+    assign n11 = a - b;
+    assign n12 = n10 != 0 ? 0 : n11;
+    assign n13 = n10 != 0 ? n11 : 0;
+    assign n14 = n10 != 0 ? 0 : n3;
+    assign n15 = n10 != 0 ? n3 : 0;
+    assign n16 = n13 + n14;
+    assign n17 = 0;
+    assign n18 = 3;
+    assign n19 = n17 - n18;
+    assign n20 = 3;
+    assign n22 = 0;
+    assign n23 = n21 > n22 ? 1 : 0;
+    assign n24 = n22 > n21 ? 1 : 0;
+    assign n25 = n23 + n24;
+    assign n26 = 100;
+    assign n27 = n16 + n26;
+    assign n28 = n25 != 0 ? 0 : n27;
+    assign n29 = n25 != 0 ? n27 : 0;
+    assign n30 = n25 != 0 ? 0 : n16;
+    assign n31 = n25 != 0 ? n16 : 0;
+    assign n32 = n29 + n30;
+    assign out0 = n32;
+endmodule
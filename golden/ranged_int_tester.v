@@ -0,0 +1,27 @@
+// main generated by ACL
+
+module double #(parameter WIDTH = 8) (
+    input signed [WIDTH-1:0] x,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n1;
+    wire signed [WIDTH-1:0] n2;
+
+    assign n1 = 2;
+    assign n2 = x * n1;
+    assign out0 = n2;
+endmodule
+
+module main #(parameter WIDTH = 5) (
+    input signed [WIDTH-1:0] level,
+    input signed [WIDTH-1:0] trim,
+    output signed [WIDTH-1:0] out0
+);
+    wire signed [WIDTH-1:0] n2;
+    wire signed [WIDTH-1:0] n3;
+
+    double #(.WIDTH(WIDTH)) double_0 (level, n2);
+
+    assign n3 = n2 + trim;
+    assign out0 = n3;
+endmodule
@@ -0,0 +1 @@
+NoModel("buffer")
@@ -1,31 +1,81 @@
-use crate::Rule;
+use std::fmt;
 
-struct Program {
-    nodes: Vec<ASTNode>,
-}
+use serde::{Deserialize, Serialize};
+
+use crate::{range_analysis::Interval, Rule};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Type {
     Int,
+    /// ```Int<low..high>```, both ends included, the type checker makes sure values stay in it
+    RangedInt {
+        low: i32,
+        high: i32,
+    },
+    /// a continuous value like a voltage, compiled to exact ```f64``` constants
+    Float,
+    /// what comparisons and the logical operators give, a 0 or 1 level in the circuit
+    Bool,
     String, // this one doesn't actually work yet...
 }
 
 impl Type {
-    fn from_str(s: &str) -> Self {
-        match s {
+    fn from_pair(pair: pest::iterators::Pair<Rule>) -> Self {
+        if let Some(range) = pair.clone().into_inner().next() {
+            let mut bounds = range.into_inner();
+            let mut bound = || {
+                let bound = bounds.next().unwrap().as_str();
+                bound
+                    .parse()
+                    .expect("check_ints lets through only ints that fit")
+            };
+            return Type::RangedInt {
+                low: bound(),
+                high: bound(),
+            };
+        }
+        match pair.as_str() {
             "Int" => Type::Int,
+            "Float" => Type::Float,
+            "Bool" => Type::Bool,
             "String" => Type::String,
-            _ => panic!("Unknown type: {}", s),
+            s => panic!("Unknown type: {}", s),
+        }
+    }
+
+    /// the values the type allows, None for a plain ```Int``` or a ```String```
+    pub fn get_range(&self) -> Option<Interval> {
+        match *self {
+            Type::RangedInt { low, high } => Some(Interval {
+                low: low as f64,
+                high: high as f64,
+            }),
+            Type::Int | Type::Float | Type::Bool | Type::String => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "Int"),
+            Type::RangedInt { low, high } => write!(f, "Int<{}..{}>", low, high),
+            Type::Float => write!(f, "Float"),
+            Type::Bool => write!(f, "Bool"),
+            Type::String => write!(f, "String"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionDefinition {
     name: String,
     args: Vec<(String, Type)>,
     return_type: Option<Type>,
     body: Vec<ASTNode>,
+    /// the netlist written in a ```spice { ... }``` block, it replaces the generated one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spice: Option<String>,
 }
 
 impl FunctionDefinition {
@@ -40,6 +90,7 @@ impl FunctionDefinition {
             args,
             body,
             return_type,
+            spice: None,
         }
     }
 
@@ -74,9 +125,43 @@ impl FunctionDefinition {
     pub fn get_body(&self) -> &Vec<ASTNode> {
         &self.body
     }
+
+    pub fn get_spice(&self) -> Option<&str> {
+        self.spice.as_deref()
+    }
 }
 
-#[derive(Debug, Clone)]
+/// ```extern fn name(args) -> Type;```, a function that is a registered part instead of ACL code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternFunction {
+    name: String,
+    args: Vec<(String, Type)>,
+    return_type: Option<Type>,
+}
+
+impl ExternFunction {
+    pub fn new(name: String, args: Vec<(String, Type)>, return_type: Option<Type>) -> Self {
+        ExternFunction {
+            name,
+            args,
+            return_type,
+        }
+    }
+
+    pub fn get_return_type(&self) -> Option<Type> {
+        self.return_type
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_args(&self) -> &Vec<(String, Type)> {
+        &self.args
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionCall {
     name: String,
     args: Vec<ASTNode>,
@@ -96,19 +181,62 @@ impl FunctionCall {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ASTNode {
     Program(Vec<ASTNode>),
     FunctionDefinition(FunctionDefinition),
+    ExternFunction(ExternFunction),
     Return(Box<ASTNode>),
     Expression(Expression),
     IfStatement(IfStatement),
+    Assignment(Assignment),
+    /// ```name = value``` on a variable that already exists
+    Reassignment(Assignment),
+    ForLoop(ForLoop),
+    Test(TestBlock),
+    Assert(Assertion),
 }
 
-#[derive(Debug, Clone)]
+/// ```test name { ... }```, the body runs like a function body with ```assert```s in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestBlock {
+    name: String,
+    body: Vec<ASTNode>,
+}
+
+impl TestBlock {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_body(&self) -> &Vec<ASTNode> {
+        &self.body
+    }
+}
+
+/// ```assert condition;```, the source is kept so a failure can say which one it was
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assertion {
+    condition: Expression,
+    source: String,
+}
+
+impl Assertion {
+    pub fn get_condition(&self) -> &Expression {
+        &self.condition
+    }
+
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IfStatement {
     condition: Box<Expression>,
     body: Vec<ASTNode>,
+    #[serde(default)]
+    else_body: Vec<ASTNode>,
 }
 
 impl IfStatement {
@@ -119,18 +247,112 @@ impl IfStatement {
     pub fn get_body(&self) -> &Vec<ASTNode> {
         &self.body
     }
+
+    /// empty if there is no else
+    pub fn get_else_body(&self) -> &Vec<ASTNode> {
+        &self.else_body
+    }
+}
+
+/// ```let name = value```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    name: String,
+    value: Expression,
+}
+
+impl Assignment {
+    pub fn new(name: String, value: Expression) -> Self {
+        Assignment { name, value }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_value(&self) -> &Expression {
+        &self.value
+    }
 }
 
-#[derive(Debug, Clone)]
+/// every iteration of a loop becomes its own copy of the body in the circuit, so longer ranges are refused
+pub const MAX_LOOP_ITERATIONS: usize = 1024;
+
+/// ```for variable in start..end { body }```, the end is exclusive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForLoop {
+    variable: String,
+    start: i32,
+    end: i32,
+    body: Vec<ASTNode>,
+}
+
+impl ForLoop {
+    pub fn get_variable(&self) -> &str {
+        &self.variable
+    }
+
+    pub fn get_range(&self) -> std::ops::Range<i32> {
+        self.start..self.end
+    }
+
+    pub fn get_body(&self) -> &Vec<ASTNode> {
+        &self.body
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expression {
     Dyadic(Dyadic),
     Value(Value),
     ParenExpression(Box<Expression>),
     Identifier(String),
     FunctionCall(FunctionCall),
+    IfExpression(IfExpression),
+    /// ```!value```
+    Not(Box<Expression>),
+}
+
+/// ```if condition { ... value } else { ... value }```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IfExpression {
+    condition: Box<Expression>,
+    body: ValueBlock,
+    else_body: ValueBlock,
+}
+
+impl IfExpression {
+    pub fn get_condition(&self) -> &Expression {
+        &self.condition
+    }
+
+    pub fn get_body(&self) -> &ValueBlock {
+        &self.body
+    }
+
+    pub fn get_else_body(&self) -> &ValueBlock {
+        &self.else_body
+    }
+}
+
+/// a block of statements that ends with the expression it evaluates to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueBlock {
+    statements: Vec<ASTNode>,
+    value: Box<Expression>,
+}
+
+impl ValueBlock {
+    pub fn get_statements(&self) -> &Vec<ASTNode> {
+        &self.statements
+    }
+
+    pub fn get_value(&self) -> &Expression {
+        &self.value
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dyadic {
     pub left: Box<Expression>,
     pub operator: Operator,
@@ -151,7 +373,7 @@ impl Dyadic {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Operator {
     Plus,
     Minus,
@@ -163,14 +385,89 @@ pub enum Operator {
     LessThanOrEqual,
     GreaterThan,
     GreaterThanOrEqual,
+    And,
+    Or,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value {
     Int(i32),
+    Float(f64),
+    Bool(bool),
     String(String),
 }
 
+fn parse_operator(op_str: &str) -> Operator {
+    match op_str {
+        "+" => Operator::Plus,
+        "-" => Operator::Minus,
+        "*" => Operator::Multiply,
+        "/" => Operator::Divide,
+        "==" => Operator::Equal,
+        "!=" => Operator::NotEqual,
+        "<" => Operator::LessThan,
+        "<=" => Operator::LessThanOrEqual,
+        ">" => Operator::GreaterThan,
+        ">=" => Operator::GreaterThanOrEqual,
+        "&&" => Operator::And,
+        "||" => Operator::Or,
+        _ => panic!("Unknown operator {:?}", op_str),
+    }
+}
+
+/// higher binds tighter, like in rust
+fn precedence(operator: &Operator) -> u8 {
+    match operator {
+        Operator::Or => 0,
+        Operator::And => 1,
+        Operator::Equal
+        | Operator::NotEqual
+        | Operator::LessThan
+        | Operator::LessThanOrEqual
+        | Operator::GreaterThan
+        | Operator::GreaterThanOrEqual => 2,
+        Operator::Plus | Operator::Minus => 3,
+        Operator::Multiply | Operator::Divide => 4,
+    }
+}
+
+/// splits at the loosest operator, the last one of those so ```a - b - c``` is ```(a - b) - c```
+fn by_precedence(mut operands: Vec<Expression>, mut operators: Vec<Operator>) -> Expression {
+    let Some(split) =
+        (0..operators.len()).min_by_key(|&i| (precedence(&operators[i]), usize::MAX - i))
+    else {
+        return operands.pop().expect("a dyadic without operands");
+    };
+    let right_operands = operands.split_off(split + 1);
+    let right_operators = operators.split_off(split + 1);
+    let operator = operators.pop().unwrap();
+    Expression::Dyadic(Dyadic {
+        left: Box::new(by_precedence(operands, operators)),
+        operator,
+        right: Box::new(by_precedence(right_operands, right_operators)),
+    })
+}
+
+/// every int in the source is an ```i32``` once it is lowered, one that doesn't fit is an error at where it is
+/// instead of a panic in ```build_ast```
+pub fn check_ints(
+    pairs: &pest::iterators::Pairs<Rule>,
+) -> Result<(), Box<pest::error::Error<Rule>>> {
+    let too_big = pairs.clone().flatten().find(|pair| {
+        matches!(pair.as_rule(), Rule::int | Rule::signed_int)
+            && pair.as_str().parse::<i32>().is_err()
+    });
+    match too_big {
+        Some(pair) => Err(Box::new(pest::error::Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: format!("{} doesn't fit in an Int", pair.as_str()),
+            },
+            pair.as_span(),
+        ))),
+        None => Ok(()),
+    }
+}
+
 pub fn build_ast(pair: pest::iterators::Pair<Rule>) -> Option<ASTNode> {
     match pair.as_rule() {
         Rule::program => {
@@ -180,74 +477,86 @@ pub fn build_ast(pair: pest::iterators::Pair<Rule>) -> Option<ASTNode> {
             }
             Some(ASTNode::Program(nodes))
         }
-        Rule::function_def => {
+        // an extern function is a function definition without the body
+        Rule::function_def | Rule::extern_function => {
+            let is_extern = pair.as_rule() == Rule::extern_function;
             let mut inner_pairs = pair.into_inner();
             let name = inner_pairs.next().unwrap().as_str().to_string();
             let mut args = vec![];
             let mut body = vec![];
             let mut return_type = None;
+            let mut spice = None;
             // keep consuming pairs until we reach the function body
-            while let Some(inner_pair) = inner_pairs.next() {
+            for inner_pair in inner_pairs {
                 match inner_pair.as_rule() {
                     Rule::params => {
                         let inner_pairs = inner_pair.into_inner();
                         for inner_pair in inner_pairs {
-                            let param;
-                            match inner_pair.as_rule() {
-                                Rule::param => param = inner_pair,
-                                _ => continue,
+                            if inner_pair.as_rule() != Rule::param {
+                                continue;
                             }
-                            let mut inner_pairs = param.into_inner();
+                            let mut inner_pairs = inner_pair.into_inner();
                             let name = inner_pairs.next().unwrap().as_str().to_string();
-                            let type_str = inner_pairs.next().unwrap().as_str();
-                            let type_enum = Type::from_str(type_str);
+                            let type_enum = Type::from_pair(inner_pairs.next().unwrap());
                             args.push((name, type_enum));
                         }
                     }
                     Rule::return_type => {
                         let inner = inner_pair.into_inner();
                         for inner_pair in inner {
-                            match inner_pair.as_rule() {
-                                Rule::value_type => {
-                                    return_type = Some(Type::from_str(inner_pair.as_str()))
-                                }
-                                _ => {}
-                            };
+                            if inner_pair.as_rule() == Rule::value_type {
+                                return_type = Some(Type::from_pair(inner_pair));
+                            }
                         }
                     }
                     Rule::function_body => {
-                        let mut inner_pairs = inner_pair.into_inner();
                         // all inner statements
-                        while let Some(inner_pair) = inner_pairs.next() {
-                            let inner_clone = inner_pair.clone(); // clone for debug TODO: remove
+                        for inner_pair in inner_pair.into_inner() {
+                            // the text borrows from the source, not the pair, so it outlives it
+                            let text = inner_pair.as_str();
                             let ast = build_ast(inner_pair);
                             if let Some(ast) = ast {
                                 body.push(ast);
                             } else {
-                                println!("Error parsing AST for function body: {:?}", inner_clone);
+                                log::warn!("Error parsing AST for function body: {:?}", text);
                             }
                         }
                     }
+                    Rule::spice_body => {
+                        let text = inner_pair.into_inner().next()?.as_str();
+                        spice = Some(dedent(text));
+                    }
                     _ => (),
                 }
             }
 
-            Some(ASTNode::FunctionDefinition(FunctionDefinition::new(
+            if is_extern {
+                return Some(ASTNode::ExternFunction(ExternFunction::new(
+                    name,
+                    args,
+                    return_type,
+                )));
+            }
+
+            Some(ASTNode::FunctionDefinition(FunctionDefinition {
                 name,
                 args,
-                body,
                 return_type,
-            )))
+                body,
+                spice,
+            }))
         }
         Rule::function_call => {
             let mut inner_pairs = pair.into_inner();
             let name = inner_pairs.next().unwrap().as_str().to_string();
-            let args = inner_pairs
-                .next()
-                .unwrap()
-                .into_inner()
-                .map(|inner_pair| build_ast(inner_pair))
-                .collect::<Option<Vec<ASTNode>>>()?;
+            // the param list is missing entirely when there are no arguments
+            let args = match inner_pairs.next() {
+                Some(param_list) => param_list
+                    .into_inner()
+                    .map(|inner_pair| build_ast(inner_pair))
+                    .collect::<Option<Vec<ASTNode>>>()?,
+                None => vec![],
+            };
             Some(ASTNode::Expression(Expression::FunctionCall(
                 FunctionCall { name, args },
             )))
@@ -258,55 +567,35 @@ pub fn build_ast(pair: pest::iterators::Pair<Rule>) -> Option<ASTNode> {
         }
         Rule::expression => {
             let inner_pair = pair.into_inner().next()?;
-            let inner_ast = build_ast(inner_pair);
-            inner_ast
+            build_ast(inner_pair)
         }
         Rule::primary_expression => {
             let inner_pair = pair.into_inner().next()?;
-            let inner_ast = build_ast(inner_pair);
-            inner_ast
+            build_ast(inner_pair)
         }
         Rule::dyadic => {
-            let mut inner_pairs = pair.into_inner();
-
-            let left_ast =
-                build_ast(inner_pairs.next().expect("failed to get left dyadic")).unwrap();
-            let left: Expression = match left_ast {
-                ASTNode::Expression(p) => p,
-                _ => panic!("Expected expression"),
-            };
-
-            let op_str = inner_pairs
-                .next()
-                .expect("failed to get operator of dyadic")
-                .as_str();
-
-            let operator = match op_str {
-                "+" => Operator::Plus,
-                "-" => Operator::Minus,
-                "*" => Operator::Multiply,
-                "/" => Operator::Divide,
-                "==" => Operator::Equal,
-                "!=" => Operator::NotEqual,
-                "<" => Operator::LessThan,
-                "<=" => Operator::LessThanOrEqual,
-                ">" => Operator::GreaterThan,
-                ">=" => Operator::GreaterThanOrEqual,
-                _ => panic!("Unknown operator {:?}", op_str),
-            };
-
-            let right_ast =
-                build_ast(inner_pairs.next().expect("failed to get right of dyadic")).unwrap();
-            let right: Expression = match right_ast {
-                ASTNode::Expression(p) => p,
-                _ => panic!("Expected expression got {:?}", right_ast),
-            };
-
-            Some(ASTNode::Expression(Expression::Dyadic(Dyadic {
-                left: Box::new(left),
-                operator,
-                right: Box::new(right),
-            })))
+            let mut operands = vec![];
+            let mut operators = vec![];
+            for inner_pair in pair.into_inner() {
+                if inner_pair.as_rule() == Rule::operator {
+                    operators.push(parse_operator(inner_pair.as_str()));
+                    continue;
+                }
+                match build_ast(inner_pair) {
+                    Some(ASTNode::Expression(expression)) => operands.push(expression),
+                    other => panic!("Expected expression got {:?}", other),
+                }
+            }
+            Some(ASTNode::Expression(by_precedence(operands, operators)))
+        }
+        Rule::not_expression => {
+            let inner_pair = pair.into_inner().next()?;
+            match build_ast(inner_pair)? {
+                ASTNode::Expression(expression) => {
+                    Some(ASTNode::Expression(Expression::Not(Box::new(expression))))
+                }
+                other => panic!("Expected expression got {:?}", other),
+            }
         }
         Rule::primary_identifier => {
             let name = pair.as_str().to_string();
@@ -316,6 +605,8 @@ pub fn build_ast(pair: pest::iterators::Pair<Rule>) -> Option<ASTNode> {
             let inner_pair = pair.into_inner().next().unwrap();
             let value = match inner_pair.as_rule() {
                 Rule::int => Value::Int(inner_pair.as_str().parse().unwrap()),
+                Rule::float => Value::Float(inner_pair.as_str().parse().unwrap()),
+                Rule::boolean => Value::Bool(inner_pair.as_str() == "true"),
                 Rule::string => Value::String(inner_pair.as_str().to_string()),
                 _ => panic!("Unknown value type {:?}", inner_pair.as_rule()),
             };
@@ -325,13 +616,20 @@ pub fn build_ast(pair: pest::iterators::Pair<Rule>) -> Option<ASTNode> {
             let mut inner_pairs = pair.into_inner();
             let condition = build_ast(inner_pairs.next().unwrap()).unwrap();
             let mut body = vec![];
-            while let Some(inner_pair) = inner_pairs.next() {
-                let inner_clone = inner_pair.clone(); // clone for debug TODO: remove
-                let ast = build_ast(inner_pair);
-                if let Some(ast) = ast {
-                    body.push(ast);
-                } else {
-                    println!("Error parsing AST for if statement: {:?}", inner_clone);
+            let mut else_body = vec![];
+            for inner_pair in inner_pairs {
+                let target = match inner_pair.as_rule() {
+                    Rule::else_body => &mut else_body,
+                    _ => &mut body,
+                };
+                for statement in inner_pair.into_inner() {
+                    let text = statement.as_str();
+                    let ast = build_ast(statement);
+                    if let Some(ast) = ast {
+                        target.push(ast);
+                    } else {
+                        log::warn!("Error parsing AST for if statement: {:?}", text);
+                    }
                 }
             }
             Some(ASTNode::IfStatement(IfStatement {
@@ -340,12 +638,114 @@ pub fn build_ast(pair: pest::iterators::Pair<Rule>) -> Option<ASTNode> {
                     _ => panic!("Expected expression"),
                 }),
                 body,
+                else_body,
+            }))
+        }
+        Rule::assignment | Rule::if_assignment | Rule::reassignment => {
+            let rule = pair.as_rule();
+            let mut inner_pairs = pair.into_inner();
+            let name = inner_pairs.next().unwrap().as_str().to_string();
+            let value = build_expression(inner_pairs.next()?);
+            let assignment = Assignment { name, value };
+            if rule == Rule::reassignment {
+                Some(ASTNode::Reassignment(assignment))
+            } else {
+                Some(ASTNode::Assignment(assignment))
+            }
+        }
+        Rule::if_expression => {
+            let mut inner_pairs = pair.into_inner();
+            let condition = build_expression(inner_pairs.next().unwrap());
+            let body = build_value_block(inner_pairs.next().unwrap());
+            let else_body = build_value_block(inner_pairs.next().unwrap());
+            Some(ASTNode::Expression(Expression::IfExpression(
+                IfExpression {
+                    condition: Box::new(condition),
+                    body,
+                    else_body,
+                },
+            )))
+        }
+        Rule::for_statement => {
+            let mut inner_pairs = pair.into_inner();
+            let variable = inner_pairs.next().unwrap().as_str().to_string();
+            let start = inner_pairs.next().unwrap().as_str().parse().unwrap();
+            let end = inner_pairs.next().unwrap().as_str().parse().unwrap();
+            let body = inner_pairs
+                .map(|inner_pair| build_ast(inner_pair).expect("failed to build for loop body"))
+                .collect();
+            Some(ASTNode::ForLoop(ForLoop {
+                variable,
+                start,
+                end,
+                body,
+            }))
+        }
+        Rule::test_block => {
+            let mut inner_pairs = pair.into_inner();
+            let name = inner_pairs.next().unwrap().as_str().to_string();
+            let body = inner_pairs
+                .map(|inner_pair| build_ast(inner_pair).expect("failed to build test body"))
+                .collect();
+            Some(ASTNode::Test(TestBlock { name, body }))
+        }
+        Rule::assert_statement => {
+            let inner_pair = pair.into_inner().next()?;
+            let source = inner_pair.as_str().trim().to_string();
+            Some(ASTNode::Assert(Assertion {
+                condition: build_expression(inner_pair),
+                source,
             }))
         }
         Rule::EOI => None,
         _ => {
-            println!("Unknown rule: {:?}", pair.as_rule());
+            log::debug!("Unknown rule: {:?}", pair.as_rule());
             None
         }
     }
 }
+
+/// drops the blank lines around a spice block and the indentation it shares with the ACL around it
+fn dedent(text: &str) -> String {
+    let lines = text
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect::<Vec<_>>();
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |last| last + 1);
+    let lines = &lines[..end];
+
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| format!("{}\n", line.get(indent..).unwrap_or("").trim_end()))
+        .collect()
+}
+
+pub(crate) fn build_expression(pair: pest::iterators::Pair<Rule>) -> Expression {
+    match build_ast(pair) {
+        Some(ASTNode::Expression(p)) => p,
+        other => panic!("Expected expression got {:?}", other),
+    }
+}
+
+fn build_value_block(pair: pest::iterators::Pair<Rule>) -> ValueBlock {
+    let mut inner_pairs = pair.into_inner().collect::<Vec<_>>();
+    // the last pair is the value, everything before it is a statement
+    let value = build_expression(inner_pairs.pop().expect("value block without a value"));
+    let statements = inner_pairs
+        .into_iter()
+        .map(|inner_pair| build_ast(inner_pair).expect("failed to build value block"))
+        .collect();
+    ValueBlock {
+        statements,
+        value: Box::new(value),
+    }
+}
@@ -0,0 +1,191 @@
+//! the whole pipeline in one place so it can be used without going through the command line
+//!
+//! source text -> ```parse``` -> parse tree -> ```lower``` -> ```ASTNode``` -> ```translate``` -> ```Circuit``` -> ```emit``` -> text
+
+use std::fmt;
+
+use pest::{iterators::Pairs, Parser};
+
+use crate::{
+    build_ast, check_ints, dot_translator,
+    kicad_translator::{self, KicadMapping},
+    serialization, spice_translator,
+    translator::{Circuit, TranslationError, Translator},
+    type_checker::{self, TypeError},
+    verilog_a_translator::{self, VerilogAError},
+    verilog_translator::{self, VerilogError},
+    ASTNode, HLHDLParser, PartRegistry, Rule,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitFormat {
+    Spice,
+    /// graphviz
+    Dot,
+    /// the versioned circuit json from ```serialization```
+    Json,
+    /// behavioural models for mixed signal simulators
+    VerilogA,
+    /// fixed width integer logic
+    Verilog,
+    /// netlist for pcbnew, the circuit is always flattened
+    Kicad,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompilerOptions {
+    /// the function that becomes the top level circuit
+    pub entry_point: String,
+    /// the parts operators are built from and ```extern fn``` declarations are bound to
+    pub parts: PartRegistry,
+    /// inline every function call so the circuit has no nested circuits, see ```Circuit::flatten```
+    pub flatten: bool,
+    /// how many bits an ```Int``` is in the verilog output
+    pub int_width: u32,
+    /// the symbols and footprints parts become in the KiCad netlist
+    pub kicad_mapping: KicadMapping,
+}
+
+impl Default for CompilerOptions {
+    fn default() -> Self {
+        CompilerOptions {
+            entry_point: "main".to_string(),
+            parts: PartRegistry::builtin(),
+            flatten: false,
+            int_width: verilog_translator::DEFAULT_WIDTH,
+            kicad_mapping: KicadMapping::builtin(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CompileError {
+    /// boxed because pest errors are huge
+    Parse(Box<pest::error::Error<Rule>>),
+    /// an ```Int<low..high>``` that doesn't hold, see ```type_checker```
+    Type(TypeError),
+    Translation(TranslationError),
+    /// the circuit has something verilog can't say, only ```emit``` gives this
+    Verilog(VerilogError),
+    /// a part with no Verilog-A model, only ```emit``` gives this
+    VerilogA(VerilogAError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Parse(error) => write!(f, "parsing error:\n{}", error),
+            CompileError::Type(error) => write!(f, "type error: {}", error),
+            CompileError::Translation(error) => write!(f, "translation error: {}", error),
+            CompileError::Verilog(error) => write!(f, "verilog error: {:?}", error),
+            CompileError::VerilogA(error) => write!(f, "verilog-a error: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<pest::error::Error<Rule>> for CompileError {
+    fn from(error: pest::error::Error<Rule>) -> Self {
+        CompileError::Parse(Box::new(error))
+    }
+}
+
+impl From<TypeError> for CompileError {
+    fn from(error: TypeError) -> Self {
+        CompileError::Type(error)
+    }
+}
+
+impl From<TranslationError> for CompileError {
+    fn from(error: TranslationError) -> Self {
+        CompileError::Translation(error)
+    }
+}
+
+impl From<VerilogError> for CompileError {
+    fn from(error: VerilogError) -> Self {
+        CompileError::Verilog(error)
+    }
+}
+
+impl From<VerilogAError> for CompileError {
+    fn from(error: VerilogAError) -> Self {
+        CompileError::VerilogA(error)
+    }
+}
+
+pub struct Compiler {
+    options: CompilerOptions,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler::with_options(CompilerOptions::default())
+    }
+
+    pub fn with_options(options: CompilerOptions) -> Self {
+        Compiler { options }
+    }
+
+    pub fn get_options(&self) -> &CompilerOptions {
+        &self.options
+    }
+
+    /// runs every stage up to the circuit
+    pub fn compile(&self, source: &str) -> Result<Circuit, CompileError> {
+        let program = self.lower(self.parse(source)?);
+        self.translate(&program)
+    }
+
+    /// source text to the pest parse tree
+    pub fn parse<'a>(&self, source: &'a str) -> Result<Pairs<'a, Rule>, CompileError> {
+        let pairs = HLHDLParser::parse(Rule::program, source)?;
+        check_ints(&pairs).map_err(CompileError::Parse)?;
+        Ok(pairs)
+    }
+
+    /// parse tree to an ```ASTNode::Program```
+    pub fn lower(&self, pairs: Pairs<'_, Rule>) -> ASTNode {
+        // none is returned for like EOI
+        ASTNode::Program(pairs.filter_map(build_ast).collect())
+    }
+
+    /// ```parse``` and ```lower``` in one go
+    pub fn parse_program(&self, source: &str) -> Result<ASTNode, CompileError> {
+        Ok(self.lower(self.parse(source)?))
+    }
+
+    /// type checks the program before it is turned into a circuit
+    pub fn translate(&self, program: &ASTNode) -> Result<Circuit, CompileError> {
+        type_checker::check(program, self.options.int_width)?;
+        let mut translator = Translator::with_entry_point(self.options.entry_point.clone());
+        translator.set_part_registry(self.options.parts.clone());
+        let circuit = translator.translate_ast(program.clone())?;
+        if self.options.flatten {
+            return Ok(circuit.flatten());
+        }
+        Ok(circuit)
+    }
+
+    pub fn emit(&self, circuit: &Circuit, format: EmitFormat) -> Result<String, CompileError> {
+        Ok(match format {
+            EmitFormat::Spice => spice_translator::circuit_to_spice(circuit),
+            EmitFormat::Dot => dot_translator::circuit_to_dot(circuit),
+            EmitFormat::Json => serialization::circuit_to_json(circuit) + "\n",
+            EmitFormat::VerilogA => verilog_a_translator::circuit_to_verilog_a(circuit)?,
+            EmitFormat::Verilog => {
+                verilog_translator::circuit_to_verilog(circuit, self.options.int_width)?
+            }
+            EmitFormat::Kicad => {
+                kicad_translator::circuit_to_kicad(circuit, &self.options.kicad_mapping)
+            }
+        })
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
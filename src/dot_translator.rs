@@ -0,0 +1,72 @@
+//! translates a ```Circuit``` to a graphviz dot graph so it can be looked at
+
+use crate::translator::Circuit;
+
+pub fn circuit_to_dot(circuit: &Circuit) -> String {
+    let name = circuit.get_circuit_name().unwrap_or("circuit");
+    let mut dot = format!("digraph \"{}\" {{\n    rankdir=LR;\n", escape(name));
+
+    // every producing index points at the node that produces it, same for consuming indices
+    let mut producers = vec![String::new(); circuit.get_next_output_index()];
+    let mut consumers = vec![String::new(); circuit.get_next_input_index()];
+
+    for (i, input) in circuit.get_program_inputs().iter().enumerate() {
+        let node = format!("input{}", i);
+        let label = input.name.clone().unwrap_or_else(|| node.clone());
+        dot.push_str(&format!(
+            "    {} [shape=ellipse, label=\"{}\"];\n",
+            node,
+            escape(&label)
+        ));
+        producers[input.index] = format!("{}:e", node);
+    }
+
+    let part_offsets = circuit.get_part_offsets();
+    for (i, (part, offsets)) in circuit.get_parts().iter().zip(part_offsets).enumerate() {
+        let node = format!("part{}", i);
+        let shape = if part.as_circuit().is_some() {
+            "box3d"
+        } else {
+            "box"
+        };
+        // one line for the name and one for each parameter
+        let mut label = escape(&part.get_name());
+        for (name, value) in part.get_parameters() {
+            label.push_str(&format!("\\n{} = {}", escape(&name), value));
+        }
+        dot.push_str(&format!(
+            "    {} [shape={}, label=\"{}\"];\n",
+            node, shape, label
+        ));
+
+        for input in 0..part.get_input_size() {
+            consumers[offsets.input_offset + input] = format!("{}:w", node);
+        }
+        for output in 0..part.get_output_size() {
+            producers[offsets.output_offset + output] = format!("{}:e", node);
+        }
+    }
+
+    for (i, &index) in circuit.get_program_outputs().iter().enumerate() {
+        let node = format!("output{}", i);
+        dot.push_str(&format!(
+            "    {} [shape=ellipse, label=\"out{}\"];\n",
+            node, i
+        ));
+        consumers[index] = format!("{}:w", node);
+    }
+
+    for &(from, to) in circuit.get_connections() {
+        dot.push_str(&format!(
+            "    {} -> {} [label=\"{} -> {}\"];\n",
+            producers[from], consumers[to], from, to
+        ));
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,529 @@
+//! runs an ```ASTNode::Program``` directly, this is the reference for what a program is *supposed* to compute
+
+use std::collections::HashMap;
+
+use crate::{
+    translator::PartInternal, ASTNode, Assertion, Expression, FunctionDefinition, Operator,
+    PartRegistry, TestBlock, Type, Value, MAX_LOOP_ITERATIONS,
+};
+
+/// deep enough for real programs, shallow enough to not blow the actual stack
+const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum InterpreterError {
+    NotAProgram,
+    UndefinedFunction(String),
+    UndefinedVariable(String),
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        got: usize,
+    },
+    /// the function finished without hitting a return but its value was needed
+    NoReturnValue(String),
+    /// a String was used in arithmetic, or something other than a Bool as a condition
+    TypeMismatch(String),
+    DivisionByZero,
+    Overflow,
+    CallDepthExceeded,
+    /// a loop runs for more than ```MAX_LOOP_ITERATIONS```
+    TooManyIterations(usize),
+    /// the value block of an if expression can't return from the function
+    ReturnInsideExpression,
+    /// an ```extern fn``` was called but there is no part to run for it
+    UnboundExtern(String),
+    /// the source of the ```assert``` and what its sides were if it was a comparison
+    AssertionFailed(String),
+    UndefinedTest(String),
+}
+
+/// what running a statement did to the control flow
+enum Flow {
+    Continue,
+    Return(Value),
+}
+
+pub struct Interpreter {
+    functions: HashMap<String, FunctionDefinition>,
+    // None when the part wasn't given to the interpreter
+    externs: HashMap<String, Option<Box<dyn PartInternal>>>,
+    /// what the extern functions were declared to return, a part has no idea if it gives a Float
+    extern_types: HashMap<String, Option<Type>>,
+    tests: Vec<TestBlock>,
+}
+
+impl Interpreter {
+    pub fn new(program: &ASTNode) -> Result<Self, InterpreterError> {
+        let nodes = match program {
+            ASTNode::Program(nodes) => nodes,
+            _ => return Err(InterpreterError::NotAProgram),
+        };
+
+        let mut functions = HashMap::new();
+        let mut externs = HashMap::new();
+        let mut extern_types = HashMap::new();
+        let mut tests = vec![];
+        for node in nodes {
+            match node {
+                ASTNode::FunctionDefinition(func_def) => {
+                    functions.insert(func_def.get_name().to_string(), func_def.clone());
+                }
+                ASTNode::ExternFunction(extern_function) => {
+                    externs.insert(extern_function.get_name().to_string(), None);
+                    extern_types.insert(
+                        extern_function.get_name().to_string(),
+                        extern_function.get_return_type(),
+                    );
+                }
+                ASTNode::Test(test) => tests.push(test.clone()),
+                _ => (),
+            }
+        }
+
+        Ok(Interpreter {
+            functions,
+            externs,
+            extern_types,
+            tests,
+        })
+    }
+
+    /// the extern functions run the ideal behaviour of the registered parts
+    pub fn with_parts(program: &ASTNode, parts: &PartRegistry) -> Result<Self, InterpreterError> {
+        let mut interpreter = Interpreter::new(program)?;
+        for (name, part) in interpreter.externs.iter_mut() {
+            *part = parts.instance(name);
+        }
+        Ok(interpreter)
+    }
+
+    /// calls to ```name``` run the part instead, even if it is a function of the program
+    /// that is how ```acl test``` swaps a function for its simulated circuit
+    pub fn bind(&mut self, name: &str, part: Box<dyn PartInternal>) {
+        self.externs.insert(name.to_string(), Some(part));
+    }
+
+    /// the ```test``` blocks in the order they are in the file
+    pub fn get_tests(&self) -> &Vec<TestBlock> {
+        &self.tests
+    }
+
+    /// runs the body of a ```test``` block, a failed ```assert``` is an ```AssertionFailed``` error
+    pub fn run_test(&self, name: &str) -> Result<(), InterpreterError> {
+        let test = self
+            .tests
+            .iter()
+            .find(|test| test.get_name() == name)
+            .ok_or_else(|| InterpreterError::UndefinedTest(name.to_string()))?;
+        self.execute_block(test.get_body(), &mut Scopes::new(), 0)?;
+        Ok(())
+    }
+
+    /// runs ```main``` with the given arguments
+    pub fn run_main(&self, args: Vec<Value>) -> Result<Option<Value>, InterpreterError> {
+        self.call("main", args)
+    }
+
+    /// runs a function, returns None if it finished without returning anything
+    pub fn call(&self, name: &str, args: Vec<Value>) -> Result<Option<Value>, InterpreterError> {
+        self.call_internal(name, args, 0)
+    }
+
+    fn call_internal(
+        &self,
+        name: &str,
+        args: Vec<Value>,
+        depth: usize,
+    ) -> Result<Option<Value>, InterpreterError> {
+        if depth >= MAX_CALL_DEPTH {
+            return Err(InterpreterError::CallDepthExceeded);
+        }
+
+        if let Some(part) = self.externs.get(name) {
+            let part = part
+                .as_ref()
+                .ok_or_else(|| InterpreterError::UnboundExtern(name.to_string()))?;
+            // a bound function keeps the type it was declared with
+            let return_type = match self.functions.get(name) {
+                Some(function) => function.get_return_type(),
+                None => self.extern_types.get(name).copied().flatten(),
+            };
+            return call_part(name, &**part, args, return_type);
+        }
+
+        let func_def = self
+            .functions
+            .get(name)
+            .ok_or_else(|| InterpreterError::UndefinedFunction(name.to_string()))?;
+        if func_def.get_args().len() != args.len() {
+            return Err(InterpreterError::WrongArgumentCount {
+                function: name.to_string(),
+                expected: func_def.get_args().len(),
+                got: args.len(),
+            });
+        }
+
+        let mut scopes = Scopes::new();
+        for ((arg_name, arg_type), value) in func_def.get_args().iter().zip(args) {
+            scopes.define(arg_name.clone(), promote(value, *arg_type));
+        }
+
+        match self.execute_block(func_def.get_body(), &mut scopes, depth)? {
+            Flow::Return(value) => Ok(Some(match func_def.get_return_type() {
+                Some(return_type) => promote(value, return_type),
+                None => value,
+            })),
+            Flow::Continue => Ok(None),
+        }
+    }
+
+    /// runs the statements in their own scope
+    fn execute_scoped(
+        &self,
+        body: &[ASTNode],
+        scopes: &mut Scopes,
+        depth: usize,
+    ) -> Result<Flow, InterpreterError> {
+        scopes.enter();
+        let flow = self.execute_block(body, scopes, depth);
+        scopes.exit();
+        flow
+    }
+
+    fn execute_block(
+        &self,
+        body: &[ASTNode],
+        scopes: &mut Scopes,
+        depth: usize,
+    ) -> Result<Flow, InterpreterError> {
+        for node in body {
+            match node {
+                ASTNode::Return(inner) => {
+                    let value = match &**inner {
+                        ASTNode::Expression(expr) => self.evaluate(expr, scopes, depth)?,
+                        other => panic!("Expected expression got {:?}", other),
+                    };
+                    return Ok(Flow::Return(value));
+                }
+                ASTNode::Assignment(assignment) => {
+                    let value = self.evaluate(assignment.get_value(), scopes, depth)?;
+                    scopes.define(assignment.get_name().to_string(), value);
+                }
+                ASTNode::Reassignment(assignment) => {
+                    let value = self.evaluate(assignment.get_value(), scopes, depth)?;
+                    scopes.assign(assignment.get_name(), value)?;
+                }
+                ASTNode::IfStatement(statement) => {
+                    let condition = self.evaluate(statement.get_condition(), scopes, depth)?;
+                    let branch = if as_bool(&condition)? {
+                        statement.get_body()
+                    } else {
+                        statement.get_else_body()
+                    };
+
+                    if let Flow::Return(value) = self.execute_scoped(branch, scopes, depth)? {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+                ASTNode::ForLoop(for_loop) => {
+                    // the circuit couldn't unroll it, so it doesn't run here either
+                    if for_loop.get_range().len() > MAX_LOOP_ITERATIONS {
+                        return Err(InterpreterError::TooManyIterations(
+                            for_loop.get_range().len(),
+                        ));
+                    }
+                    for i in for_loop.get_range() {
+                        scopes.enter();
+                        scopes.define(for_loop.get_variable().to_string(), Value::Int(i));
+                        let flow = self.execute_block(for_loop.get_body(), scopes, depth);
+                        scopes.exit();
+                        if let Flow::Return(value) = flow? {
+                            return Ok(Flow::Return(value));
+                        }
+                    }
+                }
+                ASTNode::Expression(expr) => {
+                    // function calls on their own, the value is thrown away
+                    if let Expression::FunctionCall(call) = expr {
+                        let args = self.evaluate_args(call.get_args(), scopes, depth)?;
+                        self.call_internal(call.get_name(), args, depth + 1)?;
+                    } else {
+                        self.evaluate(expr, scopes, depth)?;
+                    }
+                }
+                ASTNode::Assert(assertion) => self.check(assertion, scopes, depth)?,
+                other => panic!("{:?} can't be inside a function body", other),
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    fn check(
+        &self,
+        assertion: &Assertion,
+        scopes: &mut Scopes,
+        depth: usize,
+    ) -> Result<(), InterpreterError> {
+        // the sides of a comparison are worth knowing when it fails
+        let (holds, detail) = match assertion.get_condition() {
+            Expression::Dyadic(dyadic) => {
+                let left = self.evaluate(dyadic.get_left(), scopes, depth)?;
+                let right = self.evaluate(dyadic.get_right(), scopes, depth)?;
+                let holds = as_bool(&apply(dyadic.get_operator(), &left, &right)?)?;
+                (
+                    holds,
+                    format!(
+                        " (left is {}, right is {})",
+                        as_number(&left)?,
+                        as_number(&right)?
+                    ),
+                )
+            }
+            condition => (
+                as_bool(&self.evaluate(condition, scopes, depth)?)?,
+                String::new(),
+            ),
+        };
+        if holds {
+            return Ok(());
+        }
+        Err(InterpreterError::AssertionFailed(format!(
+            "{}{}",
+            assertion.get_source(),
+            detail
+        )))
+    }
+
+    fn evaluate_args(
+        &self,
+        args: &[ASTNode],
+        scopes: &mut Scopes,
+        depth: usize,
+    ) -> Result<Vec<Value>, InterpreterError> {
+        args.iter()
+            .map(|arg| match arg {
+                ASTNode::Expression(expr) => self.evaluate(expr, scopes, depth),
+                other => panic!("Expected expression got {:?}", other),
+            })
+            .collect()
+    }
+
+    fn evaluate(
+        &self,
+        expr: &Expression,
+        scopes: &mut Scopes,
+        depth: usize,
+    ) -> Result<Value, InterpreterError> {
+        match expr {
+            Expression::Value(value) => Ok(value.clone()),
+            Expression::ParenExpression(inner) => self.evaluate(inner, scopes, depth),
+            Expression::Identifier(name) => scopes.get(name),
+            Expression::FunctionCall(call) => {
+                let args = self.evaluate_args(call.get_args(), scopes, depth)?;
+                self.call_internal(call.get_name(), args, depth + 1)?
+                    .ok_or_else(|| InterpreterError::NoReturnValue(call.get_name().to_string()))
+            }
+            Expression::Dyadic(dyadic) => {
+                let left = self.evaluate(dyadic.get_left(), scopes, depth)?;
+                let right = self.evaluate(dyadic.get_right(), scopes, depth)?;
+                apply(dyadic.get_operator(), &left, &right)
+            }
+            Expression::Not(inner) => Ok(Value::Bool(!as_bool(
+                &self.evaluate(inner, scopes, depth)?,
+            )?)),
+            Expression::IfExpression(if_expression) => {
+                let condition = self.evaluate(if_expression.get_condition(), scopes, depth)?;
+                let block = if as_bool(&condition)? {
+                    if_expression.get_body()
+                } else {
+                    if_expression.get_else_body()
+                };
+
+                scopes.enter();
+                let value = match self.execute_block(block.get_statements(), scopes, depth) {
+                    Ok(Flow::Continue) => self.evaluate(block.get_value(), scopes, depth),
+                    Ok(Flow::Return(_)) => Err(InterpreterError::ReturnInsideExpression),
+                    Err(error) => Err(error),
+                };
+                scopes.exit();
+                value
+            }
+        }
+    }
+}
+
+/// the variables that can be seen from the current point, one map per block we are in
+struct Scopes {
+    scopes: Vec<HashMap<String, Value>>,
+}
+
+impl Scopes {
+    fn new() -> Self {
+        Scopes {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    fn enter(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// ```let```, always goes in the innermost scope
+    fn define(&mut self, name: String, value: Value) {
+        self.scopes
+            .last_mut()
+            .expect("no scope to define in")
+            .insert(name, value);
+    }
+
+    /// plain ```=```, changes the variable wherever it was defined
+    fn assign(&mut self, name: &str, value: Value) -> Result<(), InterpreterError> {
+        let variable = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .ok_or_else(|| InterpreterError::UndefinedVariable(name.to_string()))?;
+        *variable = value;
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<Value, InterpreterError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .ok_or_else(|| InterpreterError::UndefinedVariable(name.to_string()))
+    }
+}
+
+/// the part works on f64 so its first output has to come back as a whole number, unless the function
+/// returns a ```Float```. a ```Bool``` is whether the output is high
+fn call_part(
+    name: &str,
+    part: &dyn PartInternal,
+    args: Vec<Value>,
+    return_type: Option<Type>,
+) -> Result<Option<Value>, InterpreterError> {
+    if part.get_input_size() != args.len() {
+        return Err(InterpreterError::WrongArgumentCount {
+            function: name.to_string(),
+            expected: part.get_input_size(),
+            got: args.len(),
+        });
+    }
+    let inputs = args.iter().map(as_number).collect::<Result<Vec<_>, _>>()?;
+
+    let Some(&output) = part.evaluate(&inputs).first() else {
+        return Ok(None);
+    };
+    match return_type {
+        Some(Type::Float) => return Ok(Some(Value::Float(output))),
+        Some(Type::Bool) => return Ok(Some(Value::Bool(output > 0.5))),
+        _ => {}
+    }
+    if output.fract() != 0.0 || output < i32::MIN as f64 || output > i32::MAX as f64 {
+        return Err(InterpreterError::TypeMismatch(format!(
+            "{} returned {} which isn't an Int",
+            name, output
+        )));
+    }
+    Ok(Some(Value::Int(output as i32)))
+}
+
+/// an Int passed or returned where a Float is declared becomes one, and a Bool where a number is
+/// declared becomes 0 or 1
+fn promote(value: Value, declared: Type) -> Value {
+    match (value, declared) {
+        (Value::Int(value), Type::Float) => Value::Float(value as f64),
+        (Value::Bool(value), Type::Float) => Value::Float(value as i32 as f64),
+        (Value::Bool(value), Type::Int | Type::RangedInt { .. }) => Value::Int(value as i32),
+        (value, _) => value,
+    }
+}
+
+fn as_number(value: &Value) -> Result<f64, InterpreterError> {
+    match value {
+        Value::Float(value) => Ok(*value),
+        other => as_int(other).map(|value| value as f64),
+    }
+}
+
+/// a Bool counts as 0 or 1, the level it is in the circuit
+fn as_int(value: &Value) -> Result<i32, InterpreterError> {
+    match value {
+        Value::Int(value) => Ok(*value),
+        Value::Bool(value) => Ok(*value as i32),
+        other => Err(InterpreterError::TypeMismatch(format!(
+            "expected a number, got {:?}",
+            other
+        ))),
+    }
+}
+
+fn as_bool(value: &Value) -> Result<bool, InterpreterError> {
+    match value {
+        Value::Bool(value) => Ok(*value),
+        other => Err(InterpreterError::TypeMismatch(format!(
+            "expected a Bool, got {:?}",
+            other
+        ))),
+    }
+}
+
+/// comparisons and the logical operators give a Bool, arithmetic on Ints stays an Int and with a Float
+/// on either side it is done in f64 like the circuit does. division is exact like the divider so it is
+/// always a Float
+fn apply(operator: &Operator, left: &Value, right: &Value) -> Result<Value, InterpreterError> {
+    let compare = |holds: fn(&f64, &f64) -> bool| {
+        Ok(Value::Bool(holds(&as_number(left)?, &as_number(right)?)))
+    };
+    match operator {
+        Operator::And => Ok(Value::Bool(as_bool(left)? && as_bool(right)?)),
+        Operator::Or => Ok(Value::Bool(as_bool(left)? || as_bool(right)?)),
+        // every i32 is exact as an f64
+        Operator::Equal => compare(f64::eq),
+        Operator::NotEqual => compare(f64::ne),
+        Operator::LessThan => compare(f64::lt),
+        Operator::LessThanOrEqual => compare(f64::le),
+        Operator::GreaterThan => compare(f64::gt),
+        Operator::GreaterThanOrEqual => compare(f64::ge),
+        Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide => {
+            let float = matches!(left, Value::Float(_)) || matches!(right, Value::Float(_));
+            if float || matches!(operator, Operator::Divide) {
+                apply_float(operator, as_number(left)?, as_number(right)?).map(Value::Float)
+            } else {
+                apply_operator(operator, as_int(left)?, as_int(right)?).map(Value::Int)
+            }
+        }
+    }
+}
+
+fn apply_float(operator: &Operator, left: f64, right: f64) -> Result<f64, InterpreterError> {
+    match operator {
+        Operator::Plus => Ok(left + right),
+        Operator::Minus => Ok(left - right),
+        Operator::Multiply => Ok(left * right),
+        Operator::Divide if right == 0.0 => Err(InterpreterError::DivisionByZero),
+        Operator::Divide => Ok(left / right),
+        other => panic!("{:?} isn't arithmetic", other),
+    }
+}
+
+/// the arithmetic on Ints, anything that doesn't fit in an i32 is an error
+fn apply_operator(operator: &Operator, left: i32, right: i32) -> Result<i32, InterpreterError> {
+    let result = match operator {
+        Operator::Plus => left.checked_add(right).ok_or(InterpreterError::Overflow)?,
+        Operator::Minus => left.checked_sub(right).ok_or(InterpreterError::Overflow)?,
+        Operator::Multiply => left.checked_mul(right).ok_or(InterpreterError::Overflow)?,
+        other => panic!("{:?} isn't arithmetic", other),
+    };
+    Ok(result)
+}
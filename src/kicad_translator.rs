@@ -0,0 +1,287 @@
+//! translates a ```Circuit``` to a KiCad netlist so it can be read straight into pcbnew
+//!
+//! the circuit is flattened first, every primitive becomes a component with the symbol and footprint from a
+//! ```KicadMapping```, parts that aren't in the mapping get a generic ```acl:Name``` symbol without a footprint
+//! there is no schematic output yet, the netlist is enough to lay out a board
+//!
+//! ```toml
+//! [parts.Resistor]
+//! symbol = "Device:R"
+//! footprint = "Resistor_SMD:R_0603_1608Metric"
+//! reference = "R"
+//! # the pin number of every input then every output, missing ones are numbered by position
+//! pins = ["1", "2"]
+//! # the parameter shown as the value, the part name is used without one
+//! value = "resistance"
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::translator::{Circuit, PartInternal};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KicadMapping {
+    #[serde(default)]
+    parts: BTreeMap<String, SymbolMapping>,
+}
+
+/// what a single part turns into on the board
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolMapping {
+    /// ```library:name```
+    pub symbol: String,
+    pub footprint: Option<String>,
+    /// the letter the reference designators start with
+    #[serde(default = "default_reference")]
+    pub reference: String,
+    #[serde(default)]
+    pub pins: Vec<String>,
+    /// pins that are tied to ground, like the minus side of a source
+    #[serde(default)]
+    pub ground_pins: Vec<String>,
+    pub value: Option<String>,
+}
+
+fn default_reference() -> String {
+    "U".to_string()
+}
+
+impl KicadMapping {
+    pub fn empty() -> Self {
+        KicadMapping::default()
+    }
+
+    /// the parts KiCad has a symbol for out of the box
+    pub fn builtin() -> Self {
+        let mut mapping = KicadMapping::empty();
+        mapping.insert(
+            "Resistor",
+            SymbolMapping {
+                symbol: "Device:R".to_string(),
+                footprint: Some("Resistor_SMD:R_0603_1608Metric".to_string()),
+                reference: "R".to_string(),
+                pins: vec!["1".to_string(), "2".to_string()],
+                ground_pins: vec![],
+                value: Some("resistance".to_string()),
+            },
+        );
+        mapping.insert(
+            "Load",
+            SymbolMapping {
+                symbol: "Device:R".to_string(),
+                footprint: Some("Resistor_SMD:R_0603_1608Metric".to_string()),
+                reference: "R".to_string(),
+                pins: vec!["1".to_string()],
+                ground_pins: vec!["2".to_string()],
+                value: Some("resistance".to_string()),
+            },
+        );
+        mapping.insert(
+            "Capacitor",
+            SymbolMapping {
+                symbol: "Device:C".to_string(),
+                footprint: Some("Capacitor_SMD:C_0603_1608Metric".to_string()),
+                reference: "C".to_string(),
+                pins: vec!["1".to_string()],
+                ground_pins: vec!["2".to_string()],
+                value: Some("capacitance".to_string()),
+            },
+        );
+        mapping.insert(
+            "Constant",
+            SymbolMapping {
+                symbol: "Simulation_SPICE:VDC".to_string(),
+                footprint: None,
+                reference: "V".to_string(),
+                pins: vec!["1".to_string()],
+                ground_pins: vec!["2".to_string()],
+                value: Some("value".to_string()),
+            },
+        );
+        mapping
+    }
+
+    /// reads a mapping file, the entries replace the builtin ones with the same name
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: KicadMapping = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut mapping = KicadMapping::builtin();
+        mapping.parts.extend(file.parts);
+        Ok(mapping)
+    }
+
+    pub fn insert(&mut self, part: impl Into<String>, symbol: SymbolMapping) {
+        self.parts.insert(part.into(), symbol);
+    }
+
+    pub fn get(&self, part: &str) -> Option<&SymbolMapping> {
+        self.parts.get(part)
+    }
+}
+
+/// a component of the netlist, only what the writer needs
+struct Component {
+    reference: String,
+    value: String,
+    symbol: String,
+    footprint: String,
+    instance: String,
+    // the pin number of every input then every output
+    pins: Vec<String>,
+    ground_pins: Vec<String>,
+}
+
+pub fn circuit_to_kicad(circuit: &Circuit, mapping: &KicadMapping) -> String {
+    let flat = circuit.flatten();
+    let top_name = flat.get_circuit_name().unwrap_or("circuit").to_string();
+
+    // the nets are named after their producer, the outputs take over the net that drives them
+    let mut net_names = (0..flat.get_next_output_index())
+        .map(|index| format!("n{}", index))
+        .collect::<Vec<_>>();
+    for (i, input) in flat.get_program_inputs().iter().enumerate() {
+        net_names[input.index] = input
+            .get_name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("in{}", i));
+    }
+    let mut drivers = vec![None; flat.get_next_input_index()];
+    for &(from, to) in flat.get_connections() {
+        drivers[to] = Some(from);
+    }
+    let program_input_nets = flat
+        .get_program_inputs()
+        .iter()
+        .map(|input| input.index)
+        .collect::<Vec<_>>();
+    for (i, &index) in flat.get_program_outputs().iter().enumerate() {
+        if let Some(driver) = drivers[index] {
+            if !program_input_nets.contains(&driver) {
+                net_names[driver] = format!("out{}", i);
+            }
+        }
+    }
+
+    let mut counts = BTreeMap::new();
+    let mut components = vec![];
+    let mut nodes = vec![vec![]; flat.get_next_output_index()];
+    let mut ground_nodes = vec![];
+    for ((part, instance), offsets) in flat
+        .get_parts()
+        .iter()
+        .zip(flat.get_instance_names())
+        .zip(flat.get_part_offsets())
+    {
+        let component = component(&**part, instance, mapping, &mut counts);
+        for (i, pin) in component.pins.iter().enumerate() {
+            let net = if i < part.get_input_size() {
+                drivers[offsets.input_offset + i]
+            } else {
+                Some(offsets.output_offset + i - part.get_input_size())
+            };
+            if let Some(net) = net {
+                nodes[net].push((component.reference.clone(), pin.clone()));
+            }
+        }
+        for pin in &component.ground_pins {
+            ground_nodes.push((component.reference.clone(), pin.clone()));
+        }
+        components.push(component);
+    }
+
+    let mut netlist = format!(
+        "(export (version \"E\")\n  (design\n    (source \"{}\")\n    (tool \"ACL\"))\n  (components",
+        escape(&top_name)
+    );
+    for component in &components {
+        let (library, part) = component
+            .symbol
+            .split_once(':')
+            .unwrap_or(("acl", &component.symbol));
+        netlist.push_str(&format!(
+            "\n    (comp (ref \"{}\")\n      (value \"{}\")\n      (footprint \"{}\")\n      (libsource (lib \"{}\") (part \"{}\") (description \"\"))\n      (property (name \"acl_instance\") (value \"{}\")))",
+            escape(&component.reference),
+            escape(&component.value),
+            escape(&component.footprint),
+            escape(library),
+            escape(part),
+            escape(&component.instance)
+        ));
+    }
+    netlist.push_str(")\n  (nets");
+
+    let mut nets = vec![];
+    if !ground_nodes.is_empty() {
+        nets.push(("GND".to_string(), ground_nodes));
+    }
+    for (index, net_nodes) in nodes.into_iter().enumerate() {
+        if !net_nodes.is_empty() {
+            nets.push((net_names[index].clone(), net_nodes));
+        }
+    }
+    for (code, (name, net_nodes)) in nets.iter().enumerate() {
+        netlist.push_str(&format!(
+            "\n    (net (code \"{}\") (name \"{}\")",
+            code + 1,
+            escape(name)
+        ));
+        for (reference, pin) in net_nodes {
+            netlist.push_str(&format!(
+                "\n      (node (ref \"{}\") (pin \"{}\"))",
+                escape(reference),
+                escape(pin)
+            ));
+        }
+        netlist.push(')');
+    }
+    netlist.push_str("))\n");
+    netlist
+}
+
+/// looks the part up in the mapping and gives it the next free reference designator
+fn component(
+    part: &dyn PartInternal,
+    instance: &str,
+    mapping: &KicadMapping,
+    counts: &mut BTreeMap<String, usize>,
+) -> Component {
+    let name = part.get_name();
+    let pin_count = part.get_input_size() + part.get_output_size();
+    let symbol = mapping.get(&name);
+
+    let prefix = symbol.map_or_else(default_reference, |symbol| symbol.reference.clone());
+    let count = counts.entry(prefix.clone()).or_insert(0);
+    *count += 1;
+
+    let parameters = part.get_parameters();
+    let value = symbol
+        .and_then(|symbol| symbol.value.as_ref())
+        .and_then(|parameter| parameters.get(parameter))
+        .map_or_else(|| name.clone(), |value| value.to_string());
+    let pins = (0..pin_count)
+        .map(|i| {
+            symbol
+                .and_then(|symbol| symbol.pins.get(i).cloned())
+                .unwrap_or_else(|| (i + 1).to_string())
+        })
+        .collect();
+
+    Component {
+        reference: format!("{}{}", prefix, count),
+        value,
+        symbol: symbol.map_or_else(|| format!("acl:{}", name), |symbol| symbol.symbol.clone()),
+        footprint: symbol
+            .and_then(|symbol| symbol.footprint.clone())
+            .unwrap_or_default(),
+        instance: instance.to_string(),
+        pins,
+        ground_pins: symbol.map_or_else(Vec::new, |symbol| symbol.ground_pins.clone()),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,39 @@
+//! ACL compiles a small rust like language into circuits
+//!
+//! ```Compiler``` runs the whole pipeline, the modules are public for anything that needs a single stage
+
+#[macro_use]
+extern crate pest_derive;
+
+pub mod ast;
+pub mod compiler;
+pub mod dot_translator;
+pub mod interpreter;
+pub mod kicad_translator;
+pub mod mna;
+pub mod monte_carlo;
+pub mod ngspice;
+pub mod part_library;
+pub mod part_registry;
+pub mod range_analysis;
+pub mod serialization;
+pub mod simulator;
+pub mod spice_translator;
+pub mod sub_circuits;
+pub mod testing;
+#[cfg(test)]
+mod tests;
+pub mod transient;
+pub mod translator;
+pub mod type_checker;
+pub mod verilog_a_translator;
+pub mod verilog_translator;
+
+pub use ast::*;
+pub use compiler::{CompileError, Compiler, CompilerOptions, EmitFormat};
+pub use part_registry::PartRegistry;
+pub use translator::{Circuit, Part, Pin, PinType};
+
+#[derive(Parser)]
+#[grammar = "ACL.pest"] // Specifies the grammar file
+pub(crate) struct HLHDLParser;
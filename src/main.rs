@@ -1,43 +1,651 @@
-#[macro_use]
-extern crate pest_derive;
-
-use pest::Parser;
-mod ast;
-use ast::*;
-mod spice_translator;
-mod sub_circuits;
-#[cfg(test)]
-mod tests;
-mod translator;
-
-#[derive(Parser)]
-#[grammar = "ACL.pest"] // Specifies the grammar file
-struct HLHDLParser;
-
-const FILE: &str = "test_scripts/adder_with_assignment.acl";
-
-fn main() {
-    let unparsed_file = std::fs::read_to_string(FILE).expect("cannot read file");
-
-    let parse_result = HLHDLParser::parse(Rule::program, &unparsed_file);
-
-    match parse_result {
-        Ok(parsed) => {
-            let mut all_nodes = vec![];
-            for pair in parsed {
-                let ast = build_ast(pair.clone());
-                if let Some(ast) = ast {
-                    all_nodes.push(ast);
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use acl::{
+    interpreter::Interpreter,
+    kicad_translator::KicadMapping,
+    mna, monte_carlo, ngspice, part_library,
+    range_analysis::{self, Interval},
+    simulator, testing, transient,
+    translator::CircuitInput,
+    ASTNode, Circuit, CompileError, Compiler, CompilerOptions, PartRegistry, Type, Value,
+};
+use clap::{Subcommand, ValueEnum};
+
+/// compiles ACL programs into circuits
+#[derive(clap::Parser)]
+#[command(name = "acl", version)]
+struct Cli {
+    /// only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// print what the compiler is doing, repeat for more detail
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// a directory of part files to load on top of the builtin parts, can be given more than once
+    #[arg(long = "parts", value_name = "DIR", global = true)]
+    part_directories: Vec<PathBuf>,
+    /// inline every function call, for tools that can't handle nested subcircuits
+    #[arg(long, global = true)]
+    flatten: bool,
+    /// how many bits an Int is in the verilog output
+    #[arg(long, value_name = "BITS", global = true, default_value_t = acl::verilog_translator::DEFAULT_WIDTH)]
+    width: u32,
+    /// a toml file mapping parts to KiCad symbols and footprints
+    #[arg(long, value_name = "FILE", global = true)]
+    kicad_mapping: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// parse and translate the program without writing anything
+    Check { input: PathBuf },
+    /// compile the program to a spice netlist
+    Build {
+        input: PathBuf,
+        /// defaults to the input file with a .cir extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// write out one of the forms the program goes through
+    Emit {
+        input: PathBuf,
+        #[arg(short, long, value_enum)]
+        format: EmitFormat,
+        /// defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// run the compiled circuit through a simulator
+    Sim {
+        input: PathBuf,
+        /// one value for every input of main
+        #[arg(allow_negative_numbers = true)]
+        values: Vec<f64>,
+        #[arg(long, value_enum, default_value_t = Engine::Behavioral)]
+        engine: Engine,
+    },
+    /// simulate the circuit many times with the part parameters drawn from their tolerances
+    MonteCarlo {
+        input: PathBuf,
+        /// one value for every input of main
+        #[arg(allow_negative_numbers = true)]
+        values: Vec<f64>,
+        /// how a parameter varies, like Resistor.resistance=1% or OpAmp.offset=gauss:2m
+        #[arg(long = "vary", value_name = "PART.PARAMETER=SPREAD")]
+        variations: Vec<String>,
+        #[arg(long, default_value_t = 1000)]
+        runs: usize,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// only behavioral and mna, ngspice would be far too slow
+        #[arg(long, value_enum, default_value_t = Engine::Mna)]
+        engine: Engine,
+    },
+    /// run the test blocks of the program through the interpreter and the simulated circuit
+    Test { input: PathBuf },
+    /// simulate the circuit over time and write out the waveforms
+    Tran {
+        input: PathBuf,
+        /// what an input of main does, like a=1, a=pulse(0 1 10n 1n 1n 50n) or a=pwl(0 0 1u 5)
+        #[arg(short, long = "stimulus", value_name = "NAME=SHAPE")]
+        stimuli: Vec<String>,
+        /// the time step, spice suffixes work
+        #[arg(long, value_parser = parse_time)]
+        step: f64,
+        #[arg(long, value_parser = parse_time)]
+        stop: f64,
+        #[arg(long, value_enum, default_value_t = Method::BackwardEuler)]
+        method: Method,
+        /// how long the behavioral parts take to follow their inputs, 0 switches instantly
+        #[arg(long, value_parser = parse_time, default_value = "0")]
+        time_constant: f64,
+        /// record every net and not just the inputs and outputs
+        #[arg(long)]
+        all_nets: bool,
+        #[arg(short, long, value_enum, default_value_t = WaveformFormat::Csv)]
+        format: WaveformFormat,
+        /// defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// work out the range of every net from the ranges of the inputs and check it fits in the supply
+    Range {
+        input: PathBuf,
+        /// the values an input of main can take, like a=0..5 or b=-1m..1m
+        /// inputs declared as Int<low..high> default to that range
+        #[arg(short = 'i', long = "input", value_name = "NAME=LOW..HIGH")]
+        ranges: Vec<String>,
+        /// the rails, LOW..HIGH or a single value for plus and minus that
+        #[arg(long, value_parser = parse_supply, default_value = "12", allow_hyphen_values = true)]
+        supply: Interval,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Method {
+    BackwardEuler,
+    Trapezoidal,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum WaveformFormat {
+    Csv,
+    /// value change dump for waveform viewers like GTKWave
+    Vcd,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Engine {
+    /// the ideal values of every part, no external tools needed
+    Behavioral,
+    /// the DC operating point from the built in nodal solver, it sees loading the behavioral one doesn't
+    Mna,
+    /// the spice netlist through a local ngspice, checked against the interpreter
+    Ngspice,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum EmitFormat {
+    /// spice netlist
+    Spice,
+    /// graphviz graph of the circuit
+    Dot,
+    /// the circuit in the json interchange format
+    Json,
+    /// verilog-a modules of every function
+    VerilogA,
+    /// synthesizable verilog of every function
+    Verilog,
+    /// KiCad netlist of the flattened circuit
+    Kicad,
+    /// dump of the syntax tree
+    Ast,
+}
+
+/// everything goes to stderr so stdout only has what was asked for
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{}: {}",
+                record.level().to_string().to_lowercase(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+// spice solves to a tolerance so the outputs are only ever close to the exact value
+const SPICE_TOLERANCE: f64 = 1e-6;
+
+fn main() -> ExitCode {
+    let cli = <Cli as clap::Parser>::parse();
+
+    let level = if cli.quiet {
+        log::LevelFilter::Error
+    } else {
+        match cli.verbose {
+            0 => log::LevelFilter::Warn,
+            1 => log::LevelFilter::Info,
+            2 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        }
+    };
+    log::set_logger(&LOGGER).expect("logger already set");
+    log::set_max_level(level);
+
+    let result = load_parts(&cli.part_directories).and_then(|parts| {
+        let kicad_mapping = load_kicad_mapping(cli.kicad_mapping.as_deref())?;
+        let compiler = Compiler::with_options(CompilerOptions {
+            parts,
+            flatten: cli.flatten,
+            int_width: cli.width,
+            kicad_mapping,
+            ..CompilerOptions::default()
+        });
+        run(&compiler, cli.command)
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            log::error!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn load_parts(directories: &[PathBuf]) -> Result<PartRegistry, String> {
+    let mut parts = PartRegistry::builtin();
+    for directory in directories {
+        part_library::load_library(directory, &mut parts)
+            .map_err(|e| format!("cannot load parts from {}: {:?}", directory.display(), e))?;
+    }
+    Ok(parts)
+}
+
+fn load_kicad_mapping(path: Option<&Path>) -> Result<KicadMapping, String> {
+    let Some(path) = path else {
+        return Ok(KicadMapping::builtin());
+    };
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    KicadMapping::parse(&text).map_err(|e| format!("cannot load {}: {}", path.display(), e))
+}
+
+fn run(compiler: &Compiler, command: Command) -> Result<(), String> {
+    match command {
+        Command::Check { input } => {
+            compile(compiler, &input)?;
+            log::info!("{} is ok", input.display());
+        }
+        Command::Build { input, output } => {
+            let (_, circuit) = compile(compiler, &input)?;
+            let output = output.unwrap_or_else(|| input.with_extension("cir"));
+            let netlist = compiler
+                .emit(&circuit, acl::EmitFormat::Spice)
+                .map_err(|e| e.to_string())?;
+            write_output(Some(&output), &netlist)?;
+        }
+        Command::Emit {
+            input,
+            format,
+            output,
+        } => {
+            let (node, circuit) = compile(compiler, &input)?;
+            let format = match format {
+                EmitFormat::Spice => acl::EmitFormat::Spice,
+                EmitFormat::Dot => acl::EmitFormat::Dot,
+                EmitFormat::Json => acl::EmitFormat::Json,
+                EmitFormat::VerilogA => acl::EmitFormat::VerilogA,
+                EmitFormat::Verilog => acl::EmitFormat::Verilog,
+                EmitFormat::Kicad => acl::EmitFormat::Kicad,
+                EmitFormat::Ast => {
+                    return write_output(output.as_deref(), &format!("{:#?}\n", node));
+                }
+            };
+            let text = compiler.emit(&circuit, format).map_err(|e| e.to_string())?;
+            write_output(output.as_deref(), &text)?;
+        }
+        Command::Sim {
+            input,
+            values,
+            engine,
+        } => {
+            let (node, circuit) = compile(compiler, &input)?;
+            let outputs = match engine {
+                Engine::Behavioral => simulator::simulate(&circuit, &values)
+                    .map_err(|e| format!("simulation failed: {:?}", e))?,
+                Engine::Mna => mna::solve(&circuit, &values)
+                    .map_err(|e| format!("simulation failed: {:?}", e))?
+                    .get_outputs()
+                    .clone(),
+                Engine::Ngspice => {
+                    let outputs = ngspice::simulate(&circuit, &values).map_err(|e| match e {
+                        ngspice::NgspiceError::NotFound => format!(
+                            "ngspice was not found, install it or set {}",
+                            ngspice::NGSPICE_VARIABLE
+                        ),
+                        other => format!("ngspice failed: {:?}", other),
+                    })?;
+                    check_against_interpreter(compiler, &node, &circuit, &values, &outputs)?;
+                    outputs
+                }
+            };
+            for (i, value) in outputs.iter().enumerate() {
+                println!("out{} = {}", i, value);
+            }
+        }
+        Command::MonteCarlo {
+            input,
+            values,
+            variations,
+            runs,
+            seed,
+            engine,
+        } => {
+            let (_, circuit) = compile(compiler, &input)?;
+            let variations = variations
+                .iter()
+                .map(|text| monte_carlo::Variation::parse(text))
+                .collect::<Result<Vec<_>, _>>()?;
+            let engine = match engine {
+                Engine::Behavioral => monte_carlo::Engine::Behavioral,
+                Engine::Mna => monte_carlo::Engine::Mna,
+                Engine::Ngspice => {
+                    return Err("monte carlo runs on the behavioral or mna engine".to_string())
+                }
+            };
+            let options = monte_carlo::MonteCarloOptions {
+                runs,
+                seed,
+                variations,
+                engine,
+            };
+            let outputs = monte_carlo::run(&circuit, &values, &options)
+                .map_err(|e| format!("monte carlo failed: {:?}", e))?;
+            for (i, output) in outputs.iter().enumerate() {
+                println!(
+                    "out{}: nominal {}, mean {}, std dev {}, min {}, max {}, worst error {}, {:.1}% round to nominal",
+                    i,
+                    output.get_nominal(),
+                    output.mean(),
+                    output.standard_deviation(),
+                    output.min(),
+                    output.max(),
+                    output.worst_error(),
+                    output.yield_within(0.5) * 100.0
+                );
+            }
+        }
+        Command::Test { input } => {
+            let (node, _) = compile(compiler, &input)?;
+            let results = testing::run_tests(&node, compiler.get_options())
+                .map_err(|e| format!("cannot run the tests: {:?}", e))?;
+            let failed = results.iter().filter(|result| !result.passed()).count();
+            for result in &results {
+                let backend = match result.get_backend() {
+                    testing::Backend::Interpreter => "interpreter",
+                    testing::Backend::Circuit => "circuit",
+                };
+                match result.get_result() {
+                    Ok(()) => println!("test {} ({}) ... ok", result.get_name(), backend),
+                    Err(failure) => println!(
+                        "test {} ({}) ... FAILED: {:?}",
+                        result.get_name(),
+                        backend,
+                        failure
+                    ),
+                }
+            }
+            println!("{} passed, {} failed", results.len() - failed, failed);
+            if failed > 0 {
+                return Err(format!("{} of {} tests failed", failed, results.len()));
+            }
+        }
+        Command::Tran {
+            input,
+            stimuli,
+            step,
+            stop,
+            method,
+            time_constant,
+            all_nets,
+            format,
+            output,
+        } => {
+            let (_, circuit) = compile(compiler, &input)?;
+            let stimuli = match_inputs(
+                &circuit,
+                &stimuli,
+                "shape",
+                transient::Stimulus::parse,
+                |_| None,
+            )?;
+            let options = transient::TransientOptions {
+                step,
+                stop,
+                integration: match method {
+                    Method::BackwardEuler => transient::Integration::BackwardEuler,
+                    Method::Trapezoidal => transient::Integration::Trapezoidal,
+                },
+                time_constant,
+                probe_nets: all_nets,
+            };
+            let waveforms = transient::simulate(&circuit, &stimuli, &options)
+                .map_err(|e| format!("simulation failed: {:?}", e))?;
+            let text = match format {
+                WaveformFormat::Csv => waveforms.to_csv(),
+                WaveformFormat::Vcd => {
+                    waveforms.to_vcd(circuit.get_circuit_name().unwrap_or("circuit"))
                 }
-                // none is returned for like EOI
+            };
+            write_output(output.as_deref(), &text)?;
+        }
+        Command::Range {
+            input,
+            ranges,
+            supply,
+        } => {
+            let (_, circuit) = compile(compiler, &input)?;
+            let ranges = match_inputs(
+                &circuit,
+                &ranges,
+                "range",
+                Interval::parse,
+                CircuitInput::get_range,
+            )?;
+            let report = range_analysis::analyze(&circuit, &ranges, supply)
+                .map_err(|e| format!("range analysis failed: {:?}", e))?;
+            for (net, range) in report.get_nets() {
+                println!("{} {}", net, range);
+            }
+            for finding in report.get_findings() {
+                match finding {
+                    range_analysis::Finding::ExceedsSupply { net, range } => {
+                        println!("{} can reach {}, past the supply {}", net, range, supply)
+                    }
+                    range_analysis::Finding::Unbounded { part, inputs } => println!(
+                        "{} has no bound with its inputs in {}",
+                        part,
+                        inputs
+                            .iter()
+                            .map(Interval::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            }
+            match report.scale_to_fit(supply) {
+                Some(scale) if scale < 1.0 => println!(
+                    "scaling the values by {} would fit them in the supply",
+                    scale
+                ),
+                Some(_) => {}
+                None => println!("no scaling fits the values in the supply {}", supply),
+            }
+            if !report.get_findings().is_empty() {
+                return Err(format!(
+                    "{} nets or parts can go out of range",
+                    report.get_findings().len()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_time(text: &str) -> Result<f64, String> {
+    transient::parse_quantity(text).ok_or_else(|| format!("{} is not a number", text))
+}
+
+fn parse_supply(text: &str) -> Result<Interval, String> {
+    if text.contains("..") {
+        Interval::parse(text)
+    } else {
+        parse_time(text).map(|rail| Interval::new(-rail, rail))
+    }
+}
+
+/// puts the ```name=value``` arguments in the order of the circuit inputs, every input needs one unless
+/// ```default``` has one for it. ```what``` is what the value is called in the errors
+fn match_inputs<T: Clone>(
+    circuit: &Circuit,
+    arguments: &[String],
+    what: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+    default: impl Fn(&CircuitInput) -> Option<T>,
+) -> Result<Vec<T>, String> {
+    let mut given = vec![];
+    for argument in arguments {
+        let (name, value) = argument
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME={}, got {}", what.to_uppercase(), argument))?;
+        let value =
+            parse(value).map_err(|e| format!("cannot read the {} of {}: {}", what, name, e))?;
+        given.push((name.trim(), value));
+    }
+    let names = circuit
+        .get_program_inputs()
+        .iter()
+        .enumerate()
+        .map(|(i, input)| input.get_name().map_or(format!("in{}", i), str::to_string))
+        .collect::<Vec<_>>();
+    if let Some((unknown, _)) = given
+        .iter()
+        .find(|(name, _)| !names.iter().any(|n| n == name))
+    {
+        return Err(format!("the circuit has no input called {}", unknown));
+    }
+    names
+        .into_iter()
+        .zip(circuit.get_program_inputs())
+        .map(|(name, input)| {
+            given
+                .iter()
+                .find(|(given, _)| *given == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| default(input))
+                .ok_or_else(|| format!("no {} for the input {}", what, name))
+        })
+        .collect()
+}
+
+/// the analog values have to round to what the program is supposed to compute
+fn check_against_interpreter(
+    compiler: &Compiler,
+    node: &ASTNode,
+    circuit: &Circuit,
+    values: &[f64],
+    outputs: &[f64],
+) -> Result<(), String> {
+    let entry_point = &compiler.get_options().entry_point;
+    let ASTNode::Program(nodes) = node else {
+        return Ok(());
+    };
+    let Some(arguments) = nodes.iter().find_map(|node| match node {
+        ASTNode::FunctionDefinition(function) if function.get_name() == entry_point => {
+            Some(function.get_args())
+        }
+        _ => None,
+    }) else {
+        return Ok(());
+    };
+
+    // the circuit inputs are in whatever order the translator made them, the interpreter wants the arguments in order
+    let mut args = vec![None; arguments.len()];
+    for (input, value) in circuit.get_program_inputs().iter().zip(values) {
+        let Some(position) = arguments
+            .iter()
+            .position(|(name, _)| Some(name.as_str()) == input.get_name())
+        else {
+            log::info!("not checking against the interpreter, the circuit has inputs main doesn't");
+            return Ok(());
+        };
+        match arguments[position].1 {
+            Type::Float => {
+                args[position] = Some(Value::Float(*value));
+                continue;
+            }
+            Type::Bool if *value == 0.0 || *value == 1.0 => {
+                args[position] = Some(Value::Bool(*value == 1.0));
+                continue;
             }
-            let node = ASTNode::Program(all_nodes);
-            println!("{:#?}", node);
+            Type::Bool => {
+                log::info!(
+                    "not checking against the interpreter, {} is a Bool",
+                    input.get_name().unwrap_or_default()
+                );
+                return Ok(());
+            }
+            _ => {}
+        }
+        if value.fract() != 0.0 {
+            log::info!(
+                "not checking against the interpreter, {} is an Int",
+                input.get_name().unwrap_or_default()
+            );
+            return Ok(());
+        }
+        args[position] = Some(Value::Int(*value as i32));
+    }
+    let Some(args) = args.into_iter().collect::<Option<Vec<_>>>() else {
+        return Ok(());
+    };
+
+    let interpreter = Interpreter::with_parts(node, &compiler.get_options().parts)
+        .map_err(|e| format!("interpreter failed: {:?}", e))?;
+    let expected = match interpreter.call(entry_point, args) {
+        Ok(Some(Value::Int(value))) => vec![value as f64],
+        Ok(Some(Value::Float(value))) => vec![value],
+        Ok(Some(Value::Bool(value))) => vec![value as i32 as f64],
+        Ok(_) => vec![],
+        Err(e) => {
+            log::warn!(
+                "the interpreter failed so there is nothing to compare: {:?}",
+                e
+            );
+            return Ok(());
+        }
+    };
+    for (i, (expected, actual)) in expected.iter().zip(outputs).enumerate() {
+        if (expected - actual).abs() > SPICE_TOLERANCE * expected.abs().max(1.0) {
+            return Err(format!(
+                "ngspice gave {} for out{} but the interpreter gave {}",
+                actual, i, expected
+            ));
+        }
+    }
+    log::info!("ngspice agrees with the interpreter");
+    Ok(())
+}
+
+fn compile(compiler: &Compiler, path: &Path) -> Result<(ASTNode, Circuit), String> {
+    let unparsed_file =
+        fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+
+    // the path is only known out here so it gets added to parse errors on the way out
+    let with_path = |error: CompileError| match error {
+        CompileError::Parse(error) => {
+            format!(
+                "parsing error:\n{}",
+                error.with_path(&path.to_string_lossy())
+            )
+        }
+        other => other.to_string(),
+    };
+
+    let node = compiler.parse_program(&unparsed_file).map_err(with_path)?;
+    log::debug!("{:#?}", node);
+
+    let circuit = compiler.translate(&node).map_err(with_path)?;
+    log::debug!("{:#?}", circuit);
+
+    Ok((node, circuit))
+}
 
-            let mut translator = translator::Translator::new();
-            let circuit = translator.translate_ast(node);
-            println!("{:#?}", circuit);
+fn write_output(path: Option<&Path>, text: &str) -> Result<(), String> {
+    match path {
+        Some(path) => {
+            fs::write(path, text).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+            log::info!("wrote {}", path.display());
         }
-        Err(e) => println!("Parsing error: {}", e),
+        None => print!("{}", text),
     }
+    Ok(())
 }
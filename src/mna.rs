@@ -0,0 +1,382 @@
+//! DC operating point of a circuit with modified nodal analysis, no external simulator needed
+//!
+//! the circuit is flattened and every producing index becomes a node, parts with an ```mna_element``` are
+//! stamped as what they are electrically and every other part is an ideal source driven by ```evaluate```
+//! since those can be anything the solve is repeated until their outputs stop changing
+
+use crate::translator::Circuit;
+
+/// what a part is electrically
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Element {
+    /// between the first input and the first output, in ohms
+    Resistor(f64),
+    /// from the first input to ground, in ohms
+    Load(f64),
+    /// from the first input to ground, in farads, open at DC
+    Capacitor(f64),
+    /// the first output is held at this voltage
+    VoltageSource(f64),
+    /// the first output is ```gain * (in0 - in1 + offset)```
+    Vcvs { gain: f64, offset: f64 },
+}
+
+#[derive(Debug, Clone)]
+pub enum MnaError {
+    WrongInputCount {
+        expected: usize,
+        got: usize,
+    },
+    /// a behavioural part has an input that isn't driven by anything
+    UnconnectedInput(String),
+    /// the part didn't give a value for every output
+    NoBehavior(String),
+    /// the network has no single solution, like two sources fighting over one node
+    Singular,
+    /// the behavioural parts kept changing their outputs
+    NoConvergence,
+}
+
+/// the conductance every node has to ground so nothing is left floating, same as spice's gmin
+const GMIN: f64 = 1e-12;
+const MAX_ITERATIONS: usize = 100;
+const TOLERANCE: f64 = 1e-9;
+
+/// the solved voltages of the flattened circuit
+#[derive(Debug, Clone)]
+pub struct OperatingPoint {
+    circuit: Circuit,
+    voltages: Vec<f64>,
+    outputs: Vec<f64>,
+}
+
+impl OperatingPoint {
+    /// the circuit that was solved, the voltages are indexed by its producing indices
+    pub fn get_circuit(&self) -> &Circuit {
+        &self.circuit
+    }
+
+    pub fn get_voltages(&self) -> &Vec<f64> {
+        &self.voltages
+    }
+
+    /// the voltage of every program output, the same shape as ```simulator::simulate```
+    pub fn get_outputs(&self) -> &Vec<f64> {
+        &self.outputs
+    }
+}
+
+/// how a producing index is held, sources add a current to the unknowns
+pub(crate) enum Source {
+    /// the program input with this position, the value is given for every solve
+    Input(usize),
+    /// a constant
+    Fixed(f64),
+    Vcvs {
+        gain: f64,
+        offset: f64,
+        positive: Option<usize>,
+        negative: Option<usize>,
+    },
+    /// an output of a behavioural part, the value comes from the last iteration
+    Behavioral,
+}
+
+/// the flattened circuit taken apart into what gets stamped into the matrix
+pub(crate) struct Stamps {
+    pub(crate) circuit: Circuit,
+    pub(crate) node_count: usize,
+    /// the producing index every consuming index is connected to
+    pub(crate) drivers: Vec<Option<usize>>,
+    pub(crate) sources: Vec<(usize, Source)>,
+    /// conductances between two nodes, None is ground
+    pub(crate) conductances: Vec<(Option<usize>, Option<usize>, f64)>,
+    /// capacitances from a node to ground, open at DC
+    pub(crate) capacitors: Vec<(Option<usize>, f64)>,
+    /// the behavioural parts by position, the nodes of their inputs and where their outputs are in ```sources```
+    behavioral: Vec<(usize, Vec<usize>, usize)>,
+    /// the source holding a node when that is a behavioural part
+    behavioral_nodes: Vec<Option<usize>>,
+}
+
+impl Stamps {
+    /// the circuit has to be flat already
+    pub(crate) fn new(circuit: Circuit) -> Result<Self, MnaError> {
+        let node_count = circuit.get_next_output_index();
+        let mut drivers = vec![None; circuit.get_next_input_index()];
+        for &(from, to) in circuit.get_connections() {
+            drivers[to] = Some(from);
+        }
+
+        let mut sources = vec![];
+        for (i, input) in circuit.get_program_inputs().iter().enumerate() {
+            sources.push((input.get_index(), Source::Input(i)));
+        }
+        let mut conductances = vec![];
+        let mut capacitors = vec![];
+        let mut behavioral = vec![];
+        for (position, (part, offsets)) in circuit
+            .get_parts()
+            .iter()
+            .zip(circuit.get_part_offsets())
+            .enumerate()
+        {
+            let input = |i: usize| drivers[offsets.input_offset + i];
+            match part.mna_element() {
+                Some(Element::Resistor(resistance)) => {
+                    conductances.push((input(0), Some(offsets.output_offset), 1.0 / resistance));
+                }
+                Some(Element::Load(resistance)) => {
+                    conductances.push((input(0), None, 1.0 / resistance));
+                }
+                Some(Element::Capacitor(capacitance)) => capacitors.push((input(0), capacitance)),
+                Some(Element::VoltageSource(value)) => {
+                    sources.push((offsets.output_offset, Source::Fixed(value)));
+                }
+                Some(Element::Vcvs { gain, offset }) => sources.push((
+                    offsets.output_offset,
+                    Source::Vcvs {
+                        gain,
+                        offset,
+                        positive: input(0),
+                        negative: input(1),
+                    },
+                )),
+                None => {
+                    let inputs = (0..part.get_input_size())
+                        .map(|i| {
+                            input(i).ok_or_else(|| MnaError::UnconnectedInput(part.get_name()))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    behavioral.push((position, inputs, sources.len()));
+                    for i in 0..part.get_output_size() {
+                        sources.push((offsets.output_offset + i, Source::Behavioral));
+                    }
+                }
+            }
+        }
+
+        // a node held by a behavioural part is exactly its value, so a chain of them can be
+        // followed in one iteration instead of one part per solve
+        let mut behavioral_nodes = vec![None; node_count];
+        for (i, (node, source)) in sources.iter().enumerate() {
+            if let Source::Behavioral = source {
+                behavioral_nodes[*node] = Some(i);
+            }
+        }
+
+        Ok(Stamps {
+            circuit,
+            node_count,
+            drivers,
+            sources,
+            conductances,
+            capacitors,
+            behavioral,
+            behavioral_nodes,
+        })
+    }
+
+    /// solves until the behavioural parts agree with the node voltages, ```values``` holds their outputs
+    /// and is updated in place. ```follow``` turns what a part evaluated to into the value its source
+    /// takes, given the position of the source, for DC that is the value itself
+    pub(crate) fn settle(
+        &self,
+        network: &Network,
+        inputs: &[f64],
+        injections: &[f64],
+        values: &mut [f64],
+        mut follow: impl FnMut(usize, f64) -> f64,
+    ) -> Result<Vec<f64>, MnaError> {
+        let parts = self.circuit.get_parts();
+        for _ in 0..MAX_ITERATIONS {
+            let voltages = network.solve(&self.sources, inputs, values, injections);
+
+            let mut changed = false;
+            for (position, nodes, first_source) in &self.behavioral {
+                let part = &parts[*position];
+                let input_values = nodes
+                    .iter()
+                    .map(|&node| self.behavioral_nodes[node].map_or(voltages[node], |i| values[i]))
+                    .collect::<Vec<_>>();
+                let outputs = part.evaluate(&input_values);
+                if outputs.len() != part.get_output_size() {
+                    return Err(MnaError::NoBehavior(part.get_name()));
+                }
+                for (i, output) in outputs.into_iter().enumerate() {
+                    let source = first_source + i;
+                    let output = follow(source, output);
+                    let old = &mut values[source];
+                    if (output - *old).abs() > TOLERANCE * (1.0 + old.abs()) {
+                        changed = true;
+                    }
+                    *old = output;
+                }
+            }
+
+            if !changed {
+                return Ok(voltages);
+            }
+        }
+        Err(MnaError::NoConvergence)
+    }
+
+    /// the voltage of every program output, unconnected ones are 0
+    pub(crate) fn outputs(&self, voltages: &[f64]) -> Vec<f64> {
+        self.circuit
+            .get_program_outputs()
+            .iter()
+            .map(|&index| self.drivers[index].map_or(0.0, |node| voltages[node]))
+            .collect()
+    }
+}
+
+pub fn solve(circuit: &Circuit, inputs: &[f64]) -> Result<OperatingPoint, MnaError> {
+    let stamps = Stamps::new(circuit.flatten())?;
+    let expected = stamps.circuit.get_program_inputs().len();
+    if inputs.len() != expected {
+        return Err(MnaError::WrongInputCount {
+            expected,
+            got: inputs.len(),
+        });
+    }
+
+    // only the behavioural source values change between iterations so the matrix is factored once
+    let network = Network::new(stamps.node_count, &stamps.conductances, &stamps.sources)?;
+    let mut values = vec![0.0; stamps.sources.len()];
+    let voltages = stamps.settle(&network, inputs, &[], &mut values, |_, value| value)?;
+    let outputs = stamps.outputs(&voltages);
+    Ok(OperatingPoint {
+        circuit: stamps.circuit,
+        voltages,
+        outputs,
+    })
+}
+
+/// the LU factors of the network, the right hand side is filled in for every solve
+pub(crate) struct Network {
+    node_count: usize,
+    lu: Vec<Vec<f64>>,
+    // which row of the original matrix ended up in every row
+    permutation: Vec<usize>,
+}
+
+impl Network {
+    pub(crate) fn new(
+        node_count: usize,
+        conductances: &[(Option<usize>, Option<usize>, f64)],
+        sources: &[(usize, Source)],
+    ) -> Result<Self, MnaError> {
+        // the node voltages then the current through every source
+        let size = node_count + sources.len();
+        let mut matrix = vec![vec![0.0; size]; size];
+
+        for (node, row) in matrix.iter_mut().enumerate().take(node_count) {
+            row[node] += GMIN;
+        }
+        for &(a, b, conductance) in conductances {
+            if let Some(a) = a {
+                matrix[a][a] += conductance;
+            }
+            if let Some(b) = b {
+                matrix[b][b] += conductance;
+            }
+            if let (Some(a), Some(b)) = (a, b) {
+                matrix[a][b] -= conductance;
+                matrix[b][a] -= conductance;
+            }
+        }
+        for (i, (node, source)) in sources.iter().enumerate() {
+            let row = node_count + i;
+            matrix[*node][row] += 1.0;
+            matrix[row][*node] += 1.0;
+            if let Source::Vcvs {
+                gain,
+                positive,
+                negative,
+                ..
+            } = source
+            {
+                if let Some(positive) = positive {
+                    matrix[row][*positive] -= gain;
+                }
+                if let Some(negative) = negative {
+                    matrix[row][*negative] += gain;
+                }
+            }
+        }
+
+        let mut permutation = (0..size).collect::<Vec<_>>();
+        for column in 0..size {
+            // the biggest pivot keeps the rounding errors down
+            let pivot = (column..size)
+                .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))
+                .ok_or(MnaError::Singular)?;
+            if matrix[pivot][column].abs() < 1e-18 {
+                return Err(MnaError::Singular);
+            }
+            matrix.swap(column, pivot);
+            permutation.swap(column, pivot);
+
+            let (done, rest) = matrix.split_at_mut(column + 1);
+            let pivot_row = &done[column];
+            for row in rest {
+                let factor = row[column] / pivot_row[column];
+                row[column] = factor;
+                if factor == 0.0 {
+                    continue;
+                }
+                for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(column + 1) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+
+        Ok(Network {
+            node_count,
+            lu: matrix,
+            permutation,
+        })
+    }
+
+    /// the node voltages with the sources at their values, behavioural ones at the given values,
+    /// ```injections``` are currents into the nodes and can be left empty
+    fn solve(
+        &self,
+        sources: &[(usize, Source)],
+        inputs: &[f64],
+        values: &[f64],
+        injections: &[f64],
+    ) -> Vec<f64> {
+        let size = self.lu.len();
+        let mut rhs = vec![0.0; size];
+        rhs[..injections.len()].copy_from_slice(injections);
+        for (i, (_, source)) in sources.iter().enumerate() {
+            rhs[self.node_count + i] = match source {
+                Source::Input(input) => inputs[*input],
+                Source::Fixed(value) => *value,
+                Source::Behavioral => values[i],
+                Source::Vcvs { gain, offset, .. } => gain * offset,
+            };
+        }
+
+        // forward substitution with the unit lower triangle then back substitution with the upper one
+        let mut solution = self
+            .permutation
+            .iter()
+            .map(|&row| rhs[row])
+            .collect::<Vec<_>>();
+        for row in 0..size {
+            let sum = (0..row).map(|k| self.lu[row][k] * solution[k]).sum::<f64>();
+            solution[row] -= sum;
+        }
+        for row in (0..size).rev() {
+            let sum = (row + 1..size)
+                .map(|k| self.lu[row][k] * solution[k])
+                .sum::<f64>();
+            solution[row] = (solution[row] - sum) / self.lu[row][row];
+        }
+        solution.truncate(self.node_count);
+        solution
+    }
+}
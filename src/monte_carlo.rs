@@ -0,0 +1,261 @@
+//! Monte Carlo tolerance analysis, the part parameters are drawn from distributions and the circuit is
+//! simulated again for every draw so the spread of each program output shows how much the design
+//! cares about real parts
+//!
+//! the circuit is flattened first so every instance of a part gets its own draw
+
+use std::collections::BTreeMap;
+
+use crate::{
+    mna::{self, MnaError},
+    simulator::{self, SimulationError},
+    transient::parse_quantity,
+    translator::Circuit,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    /// anywhere within plus or minus the spread
+    Uniform,
+    /// normal with the spread as the standard deviation
+    Gaussian,
+}
+
+/// how one parameter of every part with a name varies
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variation {
+    pub part: String,
+    pub parameter: String,
+    pub distribution: Distribution,
+    pub spread: f64,
+    /// the spread is a fraction of the nominal value, otherwise it is added as is which is what an
+    /// offset that is nominally 0 needs
+    pub relative: bool,
+}
+
+impl Variation {
+    /// reads ```Part.parameter=spread```, the spread is relative when it ends in ```%``` and gaussian
+    /// when it starts with ```gauss:```, like ```Resistor.resistance=1%``` or ```OpAmp.offset=gauss:2m```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (target, spread) = text
+            .split_once('=')
+            .ok_or_else(|| format!("expected Part.parameter=spread, got {}", text))?;
+        let (part, parameter) = target
+            .trim()
+            .split_once('.')
+            .ok_or_else(|| format!("expected Part.parameter, got {}", target))?;
+        let spread = spread.trim();
+        let (distribution, spread) = match spread.strip_prefix("gauss:") {
+            Some(rest) => (Distribution::Gaussian, rest.trim()),
+            None => (Distribution::Uniform, spread),
+        };
+        let (relative, spread) = match spread.strip_suffix('%') {
+            Some(percent) => (true, parse_quantity(percent).map(|p| p / 100.0)),
+            None => (false, parse_quantity(spread)),
+        };
+        let spread = spread.ok_or_else(|| format!("bad spread in {}", text))?;
+        Ok(Variation {
+            part: part.to_string(),
+            parameter: parameter.to_string(),
+            distribution,
+            spread,
+            relative,
+        })
+    }
+
+    fn sample(&self, nominal: f64, rng: &mut fastrand::Rng) -> f64 {
+        let spread = if self.relative {
+            self.spread * nominal.abs()
+        } else {
+            self.spread
+        };
+        let deviation = match self.distribution {
+            Distribution::Uniform => (rng.f64() * 2.0 - 1.0) * spread,
+            Distribution::Gaussian => {
+                // box muller, 1 - f64 keeps the log away from 0
+                let radius = (-2.0 * (1.0 - rng.f64()).ln()).sqrt();
+                radius * (std::f64::consts::TAU * rng.f64()).cos() * spread
+            }
+        };
+        nominal + deviation
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// only parts whose ```evaluate``` uses the parameter see the variation, like the op-amp
+    Behavioral,
+    /// resistors and loads matter too
+    Mna,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonteCarloOptions {
+    pub runs: usize,
+    /// the same seed draws the same parts so a run can be repeated
+    pub seed: u64,
+    pub variations: Vec<Variation>,
+    pub engine: Engine,
+}
+
+impl Default for MonteCarloOptions {
+    fn default() -> Self {
+        MonteCarloOptions {
+            runs: 1000,
+            seed: 0,
+            variations: vec![],
+            engine: Engine::Mna,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MonteCarloError {
+    /// with no runs there is nothing to take statistics of
+    NoRuns,
+    /// nothing in the circuit is called that or has that parameter, most likely a typo
+    UnknownParameter {
+        part: String,
+        parameter: String,
+    },
+    /// the part has the parameter but can't be rebuilt with another value
+    NotAdjustable(String),
+    Simulation(SimulationError),
+    Mna(MnaError),
+}
+
+/// the spread of one program output over every run
+#[derive(Debug, Clone)]
+pub struct OutputStatistics {
+    nominal: f64,
+    samples: Vec<f64>,
+}
+
+impl OutputStatistics {
+    /// the output with every part at its nominal value
+    pub fn get_nominal(&self) -> f64 {
+        self.nominal
+    }
+
+    /// one value per run
+    pub fn get_samples(&self) -> &Vec<f64> {
+        &self.samples
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+
+    pub fn standard_deviation(&self) -> f64 {
+        let mean = self.mean();
+        let variance = self
+            .samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / self.samples.len() as f64;
+        variance.sqrt()
+    }
+
+    pub fn min(&self) -> f64 {
+        self.samples.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn max(&self) -> f64 {
+        self.samples
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    /// the furthest any run got from the nominal value
+    pub fn worst_error(&self) -> f64 {
+        self.samples
+            .iter()
+            .map(|sample| (sample - self.nominal).abs())
+            .fold(0.0, f64::max)
+    }
+
+    /// the fraction of runs within the tolerance of the nominal value, with 0.5 that is how many still
+    /// round to the right Int
+    pub fn yield_within(&self, tolerance: f64) -> f64 {
+        let good = self
+            .samples
+            .iter()
+            .filter(|sample| (*sample - self.nominal).abs() <= tolerance)
+            .count();
+        good as f64 / self.samples.len() as f64
+    }
+}
+
+/// one ```OutputStatistics``` per program output
+pub fn run(
+    circuit: &Circuit,
+    inputs: &[f64],
+    options: &MonteCarloOptions,
+) -> Result<Vec<OutputStatistics>, MonteCarloError> {
+    if options.runs == 0 {
+        return Err(MonteCarloError::NoRuns);
+    }
+    let flat = circuit.flatten();
+    for variation in &options.variations {
+        let found = flat.get_parts().iter().any(|part| {
+            part.get_name() == variation.part
+                && part.get_parameters().contains_key(&variation.parameter)
+        });
+        if !found {
+            return Err(MonteCarloError::UnknownParameter {
+                part: variation.part.clone(),
+                parameter: variation.parameter.clone(),
+            });
+        }
+    }
+
+    let simulate = |circuit: &Circuit| match options.engine {
+        Engine::Behavioral => {
+            simulator::simulate(circuit, inputs).map_err(MonteCarloError::Simulation)
+        }
+        Engine::Mna => mna::solve(circuit, inputs)
+            .map(|solution| solution.get_outputs().clone())
+            .map_err(MonteCarloError::Mna),
+    };
+    let mut statistics = simulate(&flat)?
+        .into_iter()
+        .map(|nominal| OutputStatistics {
+            nominal,
+            samples: Vec::with_capacity(options.runs),
+        })
+        .collect::<Vec<_>>();
+
+    let mut rng = fastrand::Rng::with_seed(options.seed);
+    for _ in 0..options.runs {
+        let mut varied = flat.clone();
+        for (index, part) in flat.get_parts().iter().enumerate() {
+            let nominal = part.get_parameters();
+            let mut parameters = BTreeMap::new();
+            for variation in &options.variations {
+                if variation.part != part.get_name() {
+                    continue;
+                }
+                if let Some(&value) = nominal.get(&variation.parameter) {
+                    parameters.insert(
+                        variation.parameter.clone(),
+                        variation.sample(value, &mut rng),
+                    );
+                }
+            }
+            if parameters.is_empty() {
+                continue;
+            }
+            let part = part
+                .with_parameters(&parameters)
+                .ok_or_else(|| MonteCarloError::NotAdjustable(part.get_name()))?;
+            varied.replace_part(index, part);
+        }
+
+        for (output, value) in statistics.iter_mut().zip(simulate(&varied)?) {
+            output.samples.push(value);
+        }
+    }
+    Ok(statistics)
+}
@@ -0,0 +1,275 @@
+//! runs a compiled circuit through a locally installed ngspice instead of the behavioural simulator
+//!
+//! the netlist gets a testbench with a DC source on every program input, ngspice is run in batch mode and
+//! the ascii raw file it writes is read back into values
+//! ```ACL_NGSPICE``` can point at the binary, otherwise ```ngspice``` is looked up on the path
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    spice_translator::{circuit_to_spice, input_port_names, output_port_names},
+    translator::Circuit,
+};
+
+pub const NGSPICE_VARIABLE: &str = "ACL_NGSPICE";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Analysis {
+    /// the operating point, one value per output
+    Op,
+    /// sweeps one of the program inputs, the others stay at their value
+    Dc {
+        input: usize,
+        start: f64,
+        stop: f64,
+        step: f64,
+    },
+    Tran {
+        step: f64,
+        stop: f64,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum NgspiceError {
+    /// there is no ngspice to run
+    NotFound,
+    Io(String),
+    WrongInputCount {
+        expected: usize,
+        got: usize,
+    },
+    /// the sweep points at an input that doesn't exist
+    InvalidSweep(usize),
+    /// ngspice ran but didn't finish cleanly, the output is whatever it printed
+    Failed {
+        status: Option<i32>,
+        output: String,
+    },
+    /// the raw file isn't something we can read
+    Raw(String),
+    /// a program output isn't in the results
+    MissingOutput(String),
+}
+
+impl From<std::io::Error> for NgspiceError {
+    fn from(error: std::io::Error) -> Self {
+        NgspiceError::Io(error.to_string())
+    }
+}
+
+/// the first plot of a raw file
+#[derive(Debug, Clone)]
+pub struct RawData {
+    pub plot: String,
+    pub variables: Vec<String>,
+    /// one row per point with a value for every variable
+    pub points: Vec<Vec<f64>>,
+}
+
+impl RawData {
+    /// every value of a variable, ```out0``` finds ```v(out0)``` too
+    pub fn get(&self, name: &str) -> Option<Vec<f64>> {
+        let name = name.to_lowercase();
+        let voltage = format!("v({})", name);
+        let index = self.variables.iter().position(|variable| {
+            let variable = variable.to_lowercase();
+            variable == name || variable == voltage
+        })?;
+        Some(self.points.iter().map(|point| point[index]).collect())
+    }
+}
+
+/// the ngspice binary, if there is one
+pub fn find_ngspice() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(NGSPICE_VARIABLE) {
+        return Some(PathBuf::from(path));
+    }
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|directory| directory.join("ngspice"))
+        .find(|path| path.is_file())
+}
+
+/// the netlist with a source on every input and the analysis to run
+pub fn testbench(
+    circuit: &Circuit,
+    inputs: &[f64],
+    analysis: Analysis,
+) -> Result<String, NgspiceError> {
+    let input_names = input_port_names(circuit);
+    if inputs.len() != input_names.len() {
+        return Err(NgspiceError::WrongInputCount {
+            expected: input_names.len(),
+            got: inputs.len(),
+        });
+    }
+
+    let netlist = circuit_to_spice(circuit);
+    let mut spice = netlist
+        .strip_suffix(".END\n")
+        .unwrap_or(&netlist)
+        .to_string();
+    spice.push_str("\n* testbench\n");
+    for (i, (name, value)) in input_names.iter().zip(inputs).enumerate() {
+        spice.push_str(&format!("Vin{} {} 0 DC {}\n", i, name, value));
+    }
+    let saved = output_port_names(circuit)
+        .iter()
+        .map(|name| format!("v({})", name))
+        .collect::<Vec<_>>();
+    if !saved.is_empty() {
+        spice.push_str(&format!(".save {}\n", saved.join(" ")));
+    }
+    match analysis {
+        Analysis::Op => spice.push_str(".op\n"),
+        Analysis::Dc {
+            input,
+            start,
+            stop,
+            step,
+        } => {
+            if input >= input_names.len() {
+                return Err(NgspiceError::InvalidSweep(input));
+            }
+            spice.push_str(&format!(".dc Vin{} {} {} {}\n", input, start, stop, step));
+        }
+        Analysis::Tran { step, stop } => spice.push_str(&format!(".tran {} {}\n", step, stop)),
+    }
+    spice.push_str(".END\n");
+    Ok(spice)
+}
+
+/// reads the first plot of an ascii raw file, complex values only keep their real part
+pub fn parse_raw(text: &str) -> Result<RawData, NgspiceError> {
+    let error = |message: &str| NgspiceError::Raw(message.to_string());
+    let mut lines = text.lines();
+
+    let mut plot = String::new();
+    let mut variable_count = None;
+    let mut point_count = None;
+    for line in lines.by_ref() {
+        if let Some(name) = line.strip_prefix("Plotname:") {
+            plot = name.trim().to_string();
+        } else if let Some(count) = line.strip_prefix("No. Variables:") {
+            variable_count = count.trim().parse::<usize>().ok();
+        } else if let Some(count) = line.strip_prefix("No. Points:") {
+            point_count = count.trim().parse::<usize>().ok();
+        } else if line.starts_with("Variables:") {
+            break;
+        } else if line.starts_with("Binary:") {
+            return Err(error("the raw file is binary"));
+        }
+    }
+    let variable_count = variable_count.ok_or_else(|| error("missing variable count"))?;
+    let point_count = point_count.ok_or_else(|| error("missing point count"))?;
+
+    let mut variables = vec![];
+    for line in lines.by_ref().take(variable_count) {
+        // index, name, type
+        let name = line
+            .split_whitespace()
+            .nth(1)
+            .ok_or_else(|| error("bad variable line"))?;
+        variables.push(name.to_string());
+    }
+    if variables.len() != variable_count
+        || !lines.next().is_some_and(|line| line.starts_with("Values:"))
+    {
+        return Err(error("missing values"));
+    }
+
+    // every point is its index followed by one value per variable
+    let mut tokens = lines.flat_map(|line| line.split_whitespace());
+    let mut points = vec![];
+    for _ in 0..point_count {
+        tokens.next().ok_or_else(|| error("missing point"))?;
+        let point = (0..variable_count)
+            .map(|_| {
+                let token = tokens.next().ok_or_else(|| error("missing value"))?;
+                let real = token.split(',').next().unwrap_or(token);
+                real.parse::<f64>()
+                    .map_err(|_| error(&format!("bad value {}", token)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        points.push(point);
+    }
+
+    Ok(RawData {
+        plot,
+        variables,
+        points,
+    })
+}
+
+// every run gets its own directory so runs in parallel don't trip over each other
+static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// runs the analysis and hands back everything ngspice saved
+pub fn run(circuit: &Circuit, inputs: &[f64], analysis: Analysis) -> Result<RawData, NgspiceError> {
+    let ngspice = find_ngspice().ok_or(NgspiceError::NotFound)?;
+    let spice = testbench(circuit, inputs, analysis)?;
+
+    let directory = env::temp_dir().join(format!(
+        "acl-ngspice-{}-{}",
+        std::process::id(),
+        RUN_COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&directory)?;
+    let result = run_in(&ngspice, &directory, &spice);
+    // the results are already read, a leftover directory is not worth failing over
+    let _ = fs::remove_dir_all(&directory);
+    result
+}
+
+fn run_in(ngspice: &Path, directory: &Path, spice: &str) -> Result<RawData, NgspiceError> {
+    let netlist_path = directory.join("testbench.cir");
+    let raw_path = directory.join("testbench.raw");
+    fs::write(&netlist_path, spice)?;
+
+    log::debug!(
+        "running {} on {}",
+        ngspice.display(),
+        netlist_path.display()
+    );
+    let output = Command::new(ngspice)
+        .arg("-b")
+        .arg("-r")
+        .arg(&raw_path)
+        .arg(&netlist_path)
+        .current_dir(directory)
+        .env("SPICE_ASCIIRAWFILE", "1")
+        .output()?;
+    let printed = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    if !output.status.success() || !raw_path.exists() {
+        return Err(NgspiceError::Failed {
+            status: output.status.code(),
+            output: printed,
+        });
+    }
+    log::trace!("{}", printed);
+
+    parse_raw(&fs::read_to_string(&raw_path)?)
+}
+
+/// the operating point of every program output, the same shape as ```simulator::simulate```
+pub fn simulate(circuit: &Circuit, inputs: &[f64]) -> Result<Vec<f64>, NgspiceError> {
+    let raw = run(circuit, inputs, Analysis::Op)?;
+    output_port_names(circuit)
+        .iter()
+        .map(|name| {
+            raw.get(name)
+                .and_then(|values| values.first().copied())
+                .ok_or_else(|| NgspiceError::MissingOutput(name.clone()))
+        })
+        .collect()
+}
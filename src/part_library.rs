@@ -0,0 +1,425 @@
+//! parts described in toml files instead of rust, so a cell library can be targeted without touching the compiler
+//!
+//! ```toml
+//! name = "Gain"
+//! parameters = { gain = 2.0 }
+//! # one ACL expression per output, it can use the input pins and the parameters
+//! behavior = ["signal * gain"]
+//! # the inside of the .SUBCKT, the ports are the pins and the parameters can be used as {name}
+//! spice = "B1 out 0 V=V(signal)*{gain}\n"
+//!
+//! [[inputs]]
+//! name = "signal"
+//! type = "analog"
+//!
+//! [[outputs]]
+//! name = "out"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use pest::Parser;
+use serde::Deserialize;
+
+use crate::{
+    ast::{build_expression, check_ints},
+    translator::{Part, Pin, PinType},
+    Expression, HLHDLParser, Operator, PartRegistry, Rule, Value,
+};
+
+#[derive(Debug, Clone)]
+pub enum LibraryError {
+    Io {
+        path: PathBuf,
+        message: String,
+    },
+    Toml {
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// the behavior isn't a valid expression
+    Equation {
+        part: String,
+        equation: String,
+        message: String,
+    },
+    /// the behavior uses something that is neither a pin nor a parameter
+    UnknownName {
+        part: String,
+        name: String,
+    },
+    /// there has to be exactly one behavior per output
+    WrongEquationCount {
+        part: String,
+        expected: usize,
+        got: usize,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PartFile {
+    name: String,
+    #[serde(default)]
+    inputs: Vec<PinData>,
+    outputs: Vec<PinData>,
+    #[serde(default)]
+    parameters: BTreeMap<String, f64>,
+    spice: Option<String>,
+    behavior: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PinData {
+    name: String,
+    #[serde(rename = "type", default)]
+    pin_type: PinType,
+}
+
+impl From<PinData> for Pin {
+    fn from(pin: PinData) -> Self {
+        Pin::new(pin.name, pin.pin_type)
+    }
+}
+
+/// a part loaded from a library file
+#[derive(Debug, Clone)]
+pub struct LibraryPart {
+    name: String,
+    inputs: Vec<Pin>,
+    outputs: Vec<Pin>,
+    parameters: BTreeMap<String, f64>,
+    spice: Option<String>,
+    // one per output
+    behavior: Vec<Expression>,
+}
+
+impl Part for LibraryPart {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get_input_size(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn get_output_size(&self) -> usize {
+        self.outputs.len()
+    }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        self.inputs.clone()
+    }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        self.outputs.clone()
+    }
+
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        self.parameters.clone()
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        let mut values = self.parameters.clone();
+        for (pin, value) in self.inputs.iter().zip(inputs) {
+            values.insert(pin.get_name().to_string(), *value);
+        }
+        self.behavior
+            .iter()
+            .map(|equation| evaluate_equation(equation, &values))
+            .collect()
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        self.spice.clone()
+    }
+
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Self> {
+        let mut part = self.clone();
+        for (name, value) in part.parameters.iter_mut() {
+            if let Some(new) = parameters.get(name) {
+                *value = *new;
+            }
+        }
+        Some(part)
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        let inputs = inputs
+            .iter()
+            .map(|net| format!("V({})", net))
+            .collect::<Vec<_>>();
+        Some(self.assignments(&inputs, outputs, |output, value| {
+            format!("V({}) <+ {};\n", output, value)
+        }))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(self.assignments(inputs, outputs, |output, value| {
+            format!("assign {} = {};\n", output, value)
+        }))
+    }
+}
+
+impl LibraryPart {
+    /// one statement per output with the behavior written in terms of the input values
+    fn assignments(
+        &self,
+        inputs: &[String],
+        outputs: &[String],
+        statement: impl Fn(&str, &str) -> String,
+    ) -> String {
+        let mut names = self
+            .parameters
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        for (pin, input) in self.inputs.iter().zip(inputs) {
+            names.insert(pin.get_name().to_string(), input.clone());
+        }
+        self.behavior
+            .iter()
+            .zip(outputs)
+            .map(|(equation, output)| statement(output, &equation_to_c(equation, &names)))
+            .collect()
+    }
+}
+
+/// reads a single part, the path only shows up in errors
+pub fn parse_part(text: &str) -> Result<LibraryPart, LibraryError> {
+    let file: PartFile = toml::from_str(text).map_err(|e| LibraryError::Toml {
+        path: None,
+        message: e.to_string(),
+    })?;
+
+    if file.behavior.len() != file.outputs.len() {
+        return Err(LibraryError::WrongEquationCount {
+            part: file.name,
+            expected: file.outputs.len(),
+            got: file.behavior.len(),
+        });
+    }
+
+    let behavior = file
+        .behavior
+        .iter()
+        .map(|equation| {
+            let expression = parse_equation(&file.name, equation)?;
+            check_names(&file, &expression)?;
+            Ok(expression)
+        })
+        .collect::<Result<Vec<_>, LibraryError>>()?;
+
+    Ok(LibraryPart {
+        name: file.name,
+        inputs: file.inputs.into_iter().map(Pin::from).collect(),
+        outputs: file.outputs.into_iter().map(Pin::from).collect(),
+        parameters: file.parameters,
+        spice: file.spice,
+        behavior,
+    })
+}
+
+pub fn load_part(path: &Path) -> Result<LibraryPart, LibraryError> {
+    let text = fs::read_to_string(path).map_err(|e| LibraryError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    parse_part(&text).map_err(|error| match error {
+        LibraryError::Toml { message, .. } => LibraryError::Toml {
+            path: Some(path.to_path_buf()),
+            message,
+        },
+        other => other,
+    })
+}
+
+/// registers every ```.toml``` file in the directory, returns the names of the parts in the order they were loaded
+/// a part with the same name as one already in the registry replaces it
+pub fn load_library(
+    directory: &Path,
+    registry: &mut PartRegistry,
+) -> Result<Vec<String>, LibraryError> {
+    let io_error = |e: std::io::Error| LibraryError::Io {
+        path: directory.to_path_buf(),
+        message: e.to_string(),
+    };
+
+    // sorted so the same directory always loads the same way
+    let mut paths = fs::read_dir(directory)
+        .map_err(io_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io_error)?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "toml")
+    });
+    paths.sort();
+
+    let mut names = vec![];
+    for path in paths {
+        let part = load_part(&path)?;
+        log::info!("loaded part {} from {}", part.name, path.display());
+        names.push(part.name.clone());
+        registry.register(part);
+    }
+    Ok(names)
+}
+
+fn parse_equation(part: &str, equation: &str) -> Result<Expression, LibraryError> {
+    let equation_error = |message: String| LibraryError::Equation {
+        part: part.to_string(),
+        equation: equation.to_string(),
+        message,
+    };
+
+    let mut pairs =
+        HLHDLParser::parse(Rule::equation, equation).map_err(|e| equation_error(e.to_string()))?;
+    check_ints(&pairs).map_err(|e| equation_error(e.to_string()))?;
+    let pair = pairs
+        .next()
+        .ok_or_else(|| equation_error("empty equation".to_string()))?;
+    Ok(build_expression(pair))
+}
+
+/// catches typos when the library is loaded instead of when the part is first simulated
+fn check_names(file: &PartFile, expression: &Expression) -> Result<(), LibraryError> {
+    let unsupported = |what: &str| LibraryError::Equation {
+        part: file.name.clone(),
+        equation: format!("{:?}", expression),
+        message: format!("{} can't be used in a behavior", what),
+    };
+
+    match expression {
+        Expression::Identifier(name) => {
+            let known = file.inputs.iter().any(|pin| pin.name == *name)
+                || file.parameters.contains_key(name);
+            if !known {
+                return Err(LibraryError::UnknownName {
+                    part: file.name.clone(),
+                    name: name.clone(),
+                });
+            }
+            Ok(())
+        }
+        Expression::Value(Value::Int(_) | Value::Float(_) | Value::Bool(_)) => Ok(()),
+        Expression::Value(_) => Err(unsupported("strings")),
+        Expression::ParenExpression(inner) | Expression::Not(inner) => check_names(file, inner),
+        Expression::Dyadic(dyadic) => {
+            check_names(file, dyadic.get_left())?;
+            check_names(file, dyadic.get_right())
+        }
+        Expression::IfExpression(if_expression) => {
+            if !if_expression.get_body().get_statements().is_empty()
+                || !if_expression.get_else_body().get_statements().is_empty()
+            {
+                return Err(unsupported("statements"));
+            }
+            check_names(file, if_expression.get_condition())?;
+            check_names(file, if_expression.get_body().get_value())?;
+            check_names(file, if_expression.get_else_body().get_value())
+        }
+        Expression::FunctionCall(_) => Err(unsupported("function calls")),
+    }
+}
+
+/// comparisons and the logical operators give the same levels as the comparator and the gates
+fn level(high: bool) -> f64 {
+    if high {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// the names were checked when the part was loaded so everything here is known
+fn evaluate_equation(expression: &Expression, values: &BTreeMap<String, f64>) -> f64 {
+    match expression {
+        Expression::Identifier(name) => values[name],
+        Expression::Value(Value::Int(value)) => *value as f64,
+        Expression::Value(Value::Float(value)) => *value,
+        Expression::Value(Value::Bool(value)) => level(*value),
+        Expression::ParenExpression(inner) => evaluate_equation(inner, values),
+        Expression::Not(inner) => level(evaluate_equation(inner, values) == 0.0),
+        Expression::Dyadic(dyadic) => {
+            let left = evaluate_equation(dyadic.get_left(), values);
+            let right = evaluate_equation(dyadic.get_right(), values);
+            match dyadic.get_operator() {
+                Operator::Plus => left + right,
+                Operator::Minus => left - right,
+                Operator::Multiply => left * right,
+                Operator::Divide => left / right,
+                Operator::Equal => level(left == right),
+                Operator::NotEqual => level(left != right),
+                Operator::LessThan => level(left < right),
+                Operator::LessThanOrEqual => level(left <= right),
+                Operator::GreaterThan => level(left > right),
+                Operator::GreaterThanOrEqual => level(left >= right),
+                Operator::And => level(left != 0.0 && right != 0.0),
+                Operator::Or => level(left != 0.0 || right != 0.0),
+            }
+        }
+        Expression::IfExpression(if_expression) => {
+            if evaluate_equation(if_expression.get_condition(), values) != 0.0 {
+                evaluate_equation(if_expression.get_body().get_value(), values)
+            } else {
+                evaluate_equation(if_expression.get_else_body().get_value(), values)
+            }
+        }
+        other => panic!("{:?} can't be in a behavior", other),
+    }
+}
+
+/// the behavior as a C like expression, which both Verilog-A and verilog understand
+/// the names are replaced with whatever they map to
+fn equation_to_c(expression: &Expression, names: &BTreeMap<String, String>) -> String {
+    match expression {
+        Expression::Identifier(name) => names[name].clone(),
+        Expression::Value(Value::Int(value)) => value.to_string(),
+        // debug keeps the decimal point so 2.0 stays a real in Verilog-A
+        Expression::Value(Value::Float(value)) => format!("{:?}", value),
+        Expression::Value(Value::Bool(value)) => (*value as i32).to_string(),
+        Expression::ParenExpression(inner) => {
+            format!("({})", equation_to_c(inner, names))
+        }
+        Expression::Not(inner) => format!("({} != 0 ? 0 : 1)", equation_to_c(inner, names)),
+        Expression::Dyadic(dyadic) => {
+            let left = equation_to_c(dyadic.get_left(), names);
+            let right = equation_to_c(dyadic.get_right(), names);
+            let operator = match dyadic.get_operator() {
+                Operator::Plus => "+",
+                Operator::Minus => "-",
+                Operator::Multiply => "*",
+                Operator::Divide => "/",
+                Operator::Equal => "==",
+                Operator::NotEqual => "!=",
+                Operator::LessThan => "<",
+                Operator::LessThanOrEqual => "<=",
+                Operator::GreaterThan => ">",
+                Operator::GreaterThanOrEqual => ">=",
+                Operator::And => "&&",
+                Operator::Or => "||",
+            };
+            // comparisons and the logical operators give the same levels as the comparator and the gates
+            match dyadic.get_operator() {
+                Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide => {
+                    format!("{} {} {}", left, operator, right)
+                }
+                _ => format!("({} {} {} ? 1 : 0)", left, operator, right),
+            }
+        }
+        Expression::IfExpression(if_expression) => format!(
+            "({} != 0 ? {} : {})",
+            equation_to_c(if_expression.get_condition(), names),
+            equation_to_c(if_expression.get_body().get_value(), names),
+            equation_to_c(if_expression.get_else_body().get_value(), names)
+        ),
+        other => panic!("{:?} can't be in a behavior", other),
+    }
+}
@@ -0,0 +1,73 @@
+//! the parts the translator can use, looked up by name
+//!
+//! the operators are built out of whatever is registered as ```Adder```, ```Comparator```... so those can be
+//! swapped for other cells, and ```extern fn``` declarations are bound to the part with the same name
+
+use std::collections::BTreeMap;
+
+use crate::{
+    sub_circuits::{Adder, And, Comparator, Divider, IfGate, Multiplier, Not, Or, Subtractor},
+    translator::{Part, PartInternal},
+};
+
+#[derive(Debug, Clone)]
+pub struct PartRegistry {
+    // every instance is a clone of the registered part
+    parts: BTreeMap<String, Box<dyn PartInternal>>,
+}
+
+impl PartRegistry {
+    /// no parts at all, not even the ones the operators need
+    pub fn empty() -> Self {
+        PartRegistry {
+            parts: BTreeMap::new(),
+        }
+    }
+
+    /// the parts from ```sub_circuits```
+    pub fn builtin() -> Self {
+        let mut registry = PartRegistry::empty();
+        registry.register(Adder {});
+        registry.register(Subtractor {});
+        registry.register(Multiplier {});
+        registry.register(Divider {});
+        registry.register(Comparator {});
+        registry.register(And {});
+        registry.register(Or {});
+        registry.register(Not {});
+        registry.register(IfGate {});
+        registry
+    }
+
+    /// adds the part under its name, replacing whatever had that name before
+    pub fn register(&mut self, part: impl Part) {
+        self.register_boxed(Box::new(part));
+    }
+
+    pub fn register_boxed(&mut self, part: Box<dyn PartInternal>) {
+        self.parts.insert(part.get_name(), part);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn PartInternal> {
+        self.parts.get(name).map(|part| &**part)
+    }
+
+    /// a fresh copy of the part to put in a circuit
+    pub fn instance(&self, name: &str) -> Option<Box<dyn PartInternal>> {
+        self.parts.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.parts.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.parts.keys().map(|name| name.as_str())
+    }
+}
+
+impl Default for PartRegistry {
+    fn default() -> Self {
+        PartRegistry::builtin()
+    }
+}
@@ -0,0 +1,310 @@
+//! interval analysis of every net, the declared input ranges are pushed through the flattened circuit to
+//! find nets that can go past the supply rails and parts whose output has no bound at all, like a divider
+//! whose denominator range includes zero
+//!
+//! intervals don't know two nets move together, so a range can be wider than what the circuit can really
+//! reach (the two branches of an if are summed at the end). a finding is worth a look, no findings means
+//! the circuit never clips
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    simulator::{self, Schedule, SimulationError},
+    translator::{Circuit, PartInternal},
+};
+
+/// the rails of a +-12V supply, what most of our boards run on
+pub const DEFAULT_SUPPLY: Interval = Interval {
+    low: -12.0,
+    high: 12.0,
+};
+
+/// the inputs beyond this many are too many corners to try, the outputs are left unbounded
+const MAX_CORNER_INPUTS: usize = 12;
+
+/// every value from ```low``` to ```high```, either can be infinite
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Interval {
+    pub const UNBOUNDED: Interval = Interval {
+        low: f64::NEG_INFINITY,
+        high: f64::INFINITY,
+    };
+
+    /// the bounds can be given either way around
+    pub fn new(a: f64, b: f64) -> Self {
+        Interval {
+            low: a.min(b),
+            high: a.max(b),
+        }
+    }
+
+    pub fn point(value: f64) -> Self {
+        Interval::new(value, value)
+    }
+
+    /// reads ```low..high``` or a single value, numbers can have spice suffixes
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let number = |text: &str| {
+            crate::transient::parse_quantity(text).ok_or_else(|| format!("bad number {}", text))
+        };
+        match text.split_once("..") {
+            Some((low, high)) => Ok(Interval::new(number(low)?, number(high)?)),
+            None => Ok(Interval::point(number(text)?)),
+        }
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.low.is_finite() && self.high.is_finite()
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.low <= value && value <= self.high
+    }
+
+    /// if every value of ```self``` is also in ```other```
+    pub fn is_within(&self, other: &Interval) -> bool {
+        other.low <= self.low && self.high <= other.high
+    }
+
+    pub fn union(&self, other: &Interval) -> Interval {
+        Interval::new(self.low.min(other.low), self.high.max(other.high))
+    }
+
+    pub fn add(&self, other: &Interval) -> Interval {
+        Interval::new(self.low + other.low, self.high + other.high)
+    }
+
+    pub fn sub(&self, other: &Interval) -> Interval {
+        Interval::new(self.low - other.high, self.high - other.low)
+    }
+
+    pub fn mul(&self, other: &Interval) -> Interval {
+        Interval::from_values(&[
+            self.low * other.low,
+            self.low * other.high,
+            self.high * other.low,
+            self.high * other.high,
+        ])
+    }
+
+    /// unbounded when the denominator can be 0
+    pub fn div(&self, other: &Interval) -> Interval {
+        if other.contains(0.0) {
+            return Interval::UNBOUNDED;
+        }
+        self.mul(&Interval::new(1.0 / other.low, 1.0 / other.high))
+    }
+
+    /// the smallest interval holding every value, a NaN means anything could happen
+    fn from_values(values: &[f64]) -> Interval {
+        if values.iter().any(|value| value.is_nan()) {
+            return Interval::UNBOUNDED;
+        }
+        let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Interval::new(low, high)
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}]", self.low, self.high)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// the net can go past the supply, whatever drives it will clip at the rail
+    ExceedsSupply { net: String, range: Interval },
+    /// the part's output has no bound even though its inputs do, a divider whose denominator range
+    /// includes zero is the usual one. nets after it aren't flagged again
+    Unbounded { part: String, inputs: Vec<Interval> },
+}
+
+#[derive(Debug, Clone)]
+pub enum RangeError {
+    WrongInputCount {
+        expected: usize,
+        got: usize,
+    },
+    /// the circuit can't be put in order, same reasons the simulator can't run it
+    Simulation(SimulationError),
+    /// the part gave a range for the wrong number of outputs
+    WrongOutputCount(String),
+}
+
+impl From<SimulationError> for RangeError {
+    fn from(error: SimulationError) -> Self {
+        RangeError::Simulation(error)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RangeReport {
+    nets: Vec<(String, Interval)>,
+    findings: Vec<Finding>,
+}
+
+impl RangeReport {
+    /// the program inputs, every part output named ```instance/pin``` then the program outputs
+    pub fn get_nets(&self) -> &Vec<(String, Interval)> {
+        &self.nets
+    }
+
+    pub fn get(&self, net: &str) -> Option<Interval> {
+        self.nets
+            .iter()
+            .find(|(name, _)| name == net)
+            .map(|(_, range)| *range)
+    }
+
+    pub fn get_findings(&self) -> &Vec<Finding> {
+        &self.findings
+    }
+
+    /// how much every value would have to be scaled by for each bounded net to fit in the supply, 1 when
+    /// they already do. only exact when the values just get added, a multiplier scales its output twice
+    ///
+    /// scaling keeps the sign, so None when no scale fits, like with anything negative on a 0..5V supply
+    pub fn scale_to_fit(&self, supply: Interval) -> Option<f64> {
+        // the scales that fit are above smallest and at most largest
+        let mut smallest: f64 = 0.0;
+        let mut largest: f64 = 1.0;
+        for range in self.nets.iter().map(|(_, range)| range) {
+            if !range.is_bounded() {
+                continue;
+            }
+            // scale * high <= supply.high and scale * -low <= -supply.low
+            for (bound, rail) in [(range.high, supply.high), (-range.low, -supply.low)] {
+                if bound > 0.0 {
+                    largest = largest.min(rail / bound);
+                } else if bound < 0.0 {
+                    smallest = smallest.max(rail / bound);
+                } else if rail < 0.0 {
+                    return None;
+                }
+            }
+        }
+        (0.0 < largest && smallest <= largest).then_some(largest)
+    }
+}
+
+/// one range per program input, in the order of ```get_program_inputs```
+pub fn analyze(
+    circuit: &Circuit,
+    inputs: &[Interval],
+    supply: Interval,
+) -> Result<RangeReport, RangeError> {
+    let circuit = circuit.flatten();
+    let program_inputs = circuit.get_program_inputs();
+    if inputs.len() != program_inputs.len() {
+        return Err(RangeError::WrongInputCount {
+            expected: program_inputs.len(),
+            got: inputs.len(),
+        });
+    }
+    let Schedule {
+        part_offsets,
+        drivers,
+        order,
+    } = simulator::schedule(&circuit)?;
+
+    let mut ranges = vec![Interval::UNBOUNDED; circuit.get_next_output_index()];
+    let mut names = vec![String::new(); circuit.get_next_output_index()];
+    for (i, (input, range)) in program_inputs.iter().zip(inputs).enumerate() {
+        ranges[input.get_index()] = *range;
+        names[input.get_index()] = input.get_name().map_or(format!("in{}", i), str::to_string);
+    }
+
+    let mut findings = vec![];
+    let instance_names = circuit.get_instance_names();
+    for part_index in order {
+        let part = &circuit.get_parts()[part_index];
+        let offsets = part_offsets[part_index];
+        let input_ranges = (offsets.input_offset..offsets.input_offset + part.get_input_size())
+            .map(|index| {
+                drivers[index]
+                    .map(|driver| ranges[driver])
+                    .ok_or(SimulationError::UnconnectedInput(index))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let output_ranges = part
+            .output_ranges(&input_ranges)
+            .unwrap_or_else(|| corner_ranges(&**part, &input_ranges));
+        if output_ranges.len() != part.get_output_size() {
+            return Err(RangeError::WrongOutputCount(part.get_name()));
+        }
+        let instance = &instance_names[part_index];
+        if input_ranges.iter().all(Interval::is_bounded)
+            && output_ranges.iter().any(|range| !range.is_bounded())
+        {
+            findings.push(Finding::Unbounded {
+                part: instance.clone(),
+                inputs: input_ranges,
+            });
+        }
+
+        for ((i, range), pin) in output_ranges
+            .into_iter()
+            .enumerate()
+            .zip(part.get_output_pins())
+        {
+            ranges[offsets.output_offset + i] = range;
+            names[offsets.output_offset + i] = format!("{}/{}", instance, pin.get_name());
+        }
+    }
+
+    let mut nets = names.into_iter().zip(ranges).collect::<Vec<_>>();
+    for (net, range) in &nets {
+        // an unbounded net was already flagged where it started
+        if range.is_bounded() && !range.is_within(&supply) {
+            findings.push(Finding::ExceedsSupply {
+                net: net.clone(),
+                range: *range,
+            });
+        }
+    }
+    for (i, &index) in circuit.get_program_outputs().iter().enumerate() {
+        let range = drivers[index].map_or(Interval::point(0.0), |driver| nets[driver].1);
+        nets.push((format!("out{}", i), range));
+    }
+    Ok(RangeReport { nets, findings })
+}
+
+/// the part evaluated at every combination of input bounds, exact for anything monotonic in each input
+/// like the comparator or a gate, too narrow for something like ```x * x``` that turns around inside
+fn corner_ranges(part: &dyn PartInternal, inputs: &[Interval]) -> Vec<Interval> {
+    let outputs = part.get_output_size();
+    if inputs.len() > MAX_CORNER_INPUTS || inputs.iter().any(|range| !range.is_bounded()) {
+        return vec![Interval::UNBOUNDED; outputs];
+    }
+    let mut values = vec![vec![]; outputs];
+    for corner in 0..1usize << inputs.len() {
+        let point = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, range)| {
+                if corner >> i & 1 == 0 {
+                    range.low
+                } else {
+                    range.high
+                }
+            })
+            .collect::<Vec<_>>();
+        for (values, value) in values.iter_mut().zip(part.evaluate(&point)) {
+            values.push(value);
+        }
+    }
+    values
+        .iter()
+        .map(|values| Interval::from_values(values))
+        .collect()
+}
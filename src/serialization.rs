@@ -0,0 +1,462 @@
+//! json interchange format for the ```ASTNode``` and the ```Circuit```
+//!
+//! every document carries a ```format_version``` and a ```kind``` so readers can reject files they don't understand
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    translator::{
+        Capacitor, Circuit, CircuitInput, CircuitInstance, Constant, Load, OpAmp, Part,
+        PartInternal, Resistor, SpicePart,
+    },
+    ASTNode, PartRegistry,
+};
+
+/// bump this whenever the layout changes in a way older readers can't handle
+pub const FORMAT_VERSION: u32 = 2;
+/// version 1 copied the whole function into every call, it can still be read
+pub const OLDEST_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub enum SerializationError {
+    Json(String),
+    UnsupportedVersion(u64),
+    /// the document is valid but holds something else (an ast when we wanted a circuit or the other way around)
+    WrongKind(String),
+    UnknownPartType(String),
+    MissingParameter {
+        part: String,
+        parameter: String,
+    },
+    /// the registered part has different parameters than the one that was saved
+    ParameterMismatch(String),
+    /// an instance points at a definition that isn't in the document
+    UnknownDefinition(String),
+    /// a definition ends up containing an instance of itself
+    RecursiveDefinition(String),
+    /// a connection or program input/output points past the indices its circuit has
+    IndexOutOfRange(usize),
+    /// a program input/output shares its index with another one or with a part
+    IndexInUse(usize),
+}
+
+impl From<serde_json::Error> for SerializationError {
+    fn from(error: serde_json::Error) -> Self {
+        SerializationError::Json(error.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    format_version: u32,
+    #[serde(flatten)]
+    contents: Contents,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Contents {
+    Ast { ast: ASTNode },
+    Circuit { circuit: CircuitData },
+}
+
+impl Contents {
+    fn kind(&self) -> &'static str {
+        match self {
+            Contents::Ast { .. } => "ast",
+            Contents::Circuit { .. } => "circuit",
+        }
+    }
+}
+
+/// the serialized form of a ```Circuit```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitData {
+    pub name: Option<String>,
+    pub parts: Vec<PartData>,
+    pub connections: Vec<(usize, usize)>,
+    pub program_inputs: Vec<CircuitInput>,
+    pub program_outputs: Vec<usize>,
+    /// every function the circuit calls, keyed by the id the ```Instance``` parts point at
+    /// only the outermost circuit has any, nested ones share them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub definitions: BTreeMap<String, CircuitData>,
+}
+
+/// the serialized form of a single part
+/// function calls are ```Instance``` parts that only keep the id of their ```definition```
+/// circuits added as a part directly keep their whole body in ```circuit```
+/// spice functions keep their netlist in ```spice``` and their behaviour, if any, in ```circuit```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartData {
+    #[serde(rename = "type")]
+    pub part_type: String,
+    /// left out by older writers, those parts get the default ```Adder#0``` style name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub definition: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit: Option<CircuitData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spice: Option<SpiceData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpiceData {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub output_size: usize,
+    pub netlist: String,
+}
+
+impl From<&Circuit> for CircuitData {
+    fn from(circuit: &Circuit) -> Self {
+        let mut writer = DefinitionWriter::default();
+        let data = writer.circuit(circuit);
+        CircuitData {
+            definitions: writer.definitions,
+            ..data
+        }
+    }
+}
+
+/// writes every function definition once and gives it an id for the calls to point at
+#[derive(Default)]
+struct DefinitionWriter {
+    ids: Vec<(Rc<Circuit>, String)>,
+    definitions: BTreeMap<String, CircuitData>,
+}
+
+impl DefinitionWriter {
+    fn circuit(&mut self, circuit: &Circuit) -> CircuitData {
+        CircuitData {
+            name: circuit.get_circuit_name().map(|name| name.to_string()),
+            parts: circuit
+                .get_parts()
+                .iter()
+                .zip(circuit.get_instance_names())
+                .map(|(part, instance_name)| PartData {
+                    instance: Some(instance_name.clone()),
+                    ..self.part(&**part)
+                })
+                .collect(),
+            connections: circuit.get_connections().clone(),
+            program_inputs: circuit.get_program_inputs().clone(),
+            program_outputs: circuit.get_program_outputs().clone(),
+            definitions: BTreeMap::new(),
+        }
+    }
+
+    fn part(&mut self, part: &dyn PartInternal) -> PartData {
+        let empty = PartData {
+            part_type: part.get_name(),
+            instance: None,
+            parameters: BTreeMap::new(),
+            definition: None,
+            circuit: None,
+            spice: None,
+        };
+
+        if let Some(instance) = part.as_any().downcast_ref::<CircuitInstance>() {
+            return PartData {
+                part_type: "Instance".to_string(),
+                definition: Some(self.definition_id(instance.get_definition())),
+                ..empty
+            };
+        }
+
+        if let Some(spice_part) = part.as_any().downcast_ref::<SpicePart>() {
+            return PartData {
+                part_type: "Spice".to_string(),
+                circuit: spice_part
+                    .get_behavior()
+                    .map(|behavior| self.circuit(behavior)),
+                spice: Some(SpiceData {
+                    name: part.get_name(),
+                    inputs: spice_part.get_input_names().clone(),
+                    output_size: part.get_output_size(),
+                    netlist: spice_part.get_spice().to_string(),
+                }),
+                ..empty
+            };
+        }
+
+        match part.as_circuit() {
+            // a circuit that was added as a part directly instead of through a call
+            Some(circuit) => PartData {
+                part_type: "Circuit".to_string(),
+                circuit: Some(self.circuit(circuit)),
+                ..empty
+            },
+            None => PartData {
+                parameters: part.get_parameters(),
+                ..empty
+            },
+        }
+    }
+
+    fn definition_id(&mut self, definition: &Rc<Circuit>) -> String {
+        if let Some((_, id)) = self
+            .ids
+            .iter()
+            .find(|(other, _)| Rc::ptr_eq(other, definition))
+        {
+            return id.clone();
+        }
+
+        // circuits put together by hand can share a name without being the same circuit
+        let name = Part::get_name(&**definition);
+        let mut id = name.clone();
+        let mut count = 0;
+        while self.ids.iter().any(|(_, other)| *other == id) {
+            count += 1;
+            id = format!("{}#{}", name, count);
+        }
+        self.ids.push((Rc::clone(definition), id.clone()));
+
+        let data = self.circuit(definition);
+        self.definitions.insert(id.clone(), data);
+        id
+    }
+}
+
+impl CircuitData {
+    /// parts other than the electrical primitives and circuits are looked up in the registry
+    pub fn to_circuit(&self, parts: &PartRegistry) -> Result<Circuit, SerializationError> {
+        let mut reader = DefinitionReader {
+            parts,
+            definitions: &self.definitions,
+            loaded: HashMap::new(),
+            loading: vec![],
+        };
+        reader.circuit(self)
+    }
+}
+
+/// loads every definition the first time it is used, later calls share it
+struct DefinitionReader<'a> {
+    parts: &'a PartRegistry,
+    definitions: &'a BTreeMap<String, CircuitData>,
+    loaded: HashMap<String, Rc<Circuit>>,
+    // the definitions being loaded right now, a definition can't call itself
+    loading: Vec<String>,
+}
+
+impl DefinitionReader<'_> {
+    fn circuit(&mut self, data: &CircuitData) -> Result<Circuit, SerializationError> {
+        let parts = data
+            .parts
+            .iter()
+            .map(|part| Ok((self.part(part)?, part.instance.clone())))
+            .collect::<Result<Vec<_>, SerializationError>>()?;
+
+        let circuit = Circuit::from_parts(
+            data.name.clone(),
+            parts,
+            data.connections.clone(),
+            data.program_inputs.clone(),
+            data.program_outputs.clone(),
+        );
+        check_indices(&circuit)?;
+        Ok(circuit)
+    }
+
+    fn definition(&mut self, id: &str) -> Result<Rc<Circuit>, SerializationError> {
+        if let Some(definition) = self.loaded.get(id) {
+            return Ok(Rc::clone(definition));
+        }
+        if self.loading.iter().any(|other| other == id) {
+            return Err(SerializationError::RecursiveDefinition(id.to_string()));
+        }
+        let data = self
+            .definitions
+            .get(id)
+            .ok_or_else(|| SerializationError::UnknownDefinition(id.to_string()))?;
+
+        self.loading.push(id.to_string());
+        let definition = Rc::new(self.circuit(data)?);
+        self.loading.pop();
+        self.loaded.insert(id.to_string(), Rc::clone(&definition));
+        Ok(definition)
+    }
+
+    fn part(&mut self, data: &PartData) -> Result<Box<dyn PartInternal>, SerializationError> {
+        let part: Box<dyn PartInternal> = match data.part_type.as_str() {
+            "Constant" => Box::new(Constant {
+                value: data.get_parameter("value")?,
+            }),
+            "Resistor" => Box::new(Resistor {
+                resistance: data.get_parameter("resistance")?,
+            }),
+            "Load" => Box::new(Load {
+                resistance: data.get_parameter("resistance")?,
+            }),
+            "Capacitor" => Box::new(Capacitor {
+                capacitance: data.get_parameter("capacitance")?,
+            }),
+            // older files have no offset
+            "OpAmp" => Box::new(OpAmp {
+                gain: data.get_parameter("gain")?,
+                offset: data.parameters.get("offset").copied().unwrap_or(0.0),
+            }),
+            "Instance" => {
+                let id = data
+                    .definition
+                    .as_ref()
+                    .ok_or_else(|| data.missing("definition"))?;
+                Box::new(CircuitInstance::new(self.definition(id)?))
+            }
+            "Circuit" => {
+                let circuit = data
+                    .circuit
+                    .as_ref()
+                    .ok_or_else(|| data.missing("circuit"))?;
+                Box::new(self.circuit(circuit)?)
+            }
+            "Spice" => {
+                let spice = data.spice.as_ref().ok_or_else(|| data.missing("spice"))?;
+                let behavior = match &data.circuit {
+                    Some(circuit) => Some(self.circuit(circuit)?),
+                    None => None,
+                };
+                Box::new(SpicePart::new(
+                    spice.name.clone(),
+                    spice.inputs.clone(),
+                    spice.output_size,
+                    spice.netlist.clone(),
+                    behavior,
+                ))
+            }
+            name => {
+                let part = self
+                    .parts
+                    .instance(name)
+                    .ok_or_else(|| SerializationError::UnknownPartType(name.to_string()))?;
+                if part.get_parameters() != data.parameters {
+                    return Err(SerializationError::ParameterMismatch(name.to_string()));
+                }
+                part
+            }
+        };
+        Ok(part)
+    }
+}
+
+impl PartData {
+    fn missing(&self, parameter: &str) -> SerializationError {
+        SerializationError::MissingParameter {
+            part: self.part_type.clone(),
+            parameter: parameter.to_string(),
+        }
+    }
+
+    fn get_parameter(&self, parameter: &str) -> Result<f64, SerializationError> {
+        self.parameters
+            .get(parameter)
+            .copied()
+            .ok_or_else(|| self.missing(parameter))
+    }
+}
+
+/// the indices are only trusted by everything after the loader, so a document can't be allowed to break them
+fn check_indices(circuit: &Circuit) -> Result<(), SerializationError> {
+    let mut consumers = vec![false; circuit.get_next_input_index()];
+    let mut producers = vec![false; circuit.get_next_output_index()];
+    let claim = |owned: &mut [bool], index: usize| match owned.get_mut(index) {
+        None => Err(SerializationError::IndexOutOfRange(index)),
+        Some(true) => Err(SerializationError::IndexInUse(index)),
+        Some(claimed) => {
+            *claimed = true;
+            Ok(())
+        }
+    };
+
+    for &index in circuit.get_program_outputs() {
+        claim(&mut consumers, index)?;
+    }
+    for input in circuit.get_program_inputs() {
+        claim(&mut producers, input.index)?;
+    }
+    // the parts fill the gaps in order, a program input/output in the middle of a part would split it
+    for (part, offsets) in circuit.get_parts().iter().zip(circuit.get_part_offsets()) {
+        for index in offsets.input_offset..offsets.input_offset + part.get_input_size() {
+            claim(&mut consumers, index)?;
+        }
+        for index in offsets.output_offset..offsets.output_offset + part.get_output_size() {
+            claim(&mut producers, index)?;
+        }
+    }
+
+    for &(from, to) in circuit.get_connections() {
+        if from >= producers.len() {
+            return Err(SerializationError::IndexOutOfRange(from));
+        }
+        if to >= consumers.len() {
+            return Err(SerializationError::IndexOutOfRange(to));
+        }
+    }
+    Ok(())
+}
+
+fn to_json(contents: Contents) -> String {
+    let document = Document {
+        format_version: FORMAT_VERSION,
+        contents,
+    };
+    serde_json::to_string_pretty(&document).expect("serializing to a string can't fail")
+}
+
+fn from_json(json: &str) -> Result<Contents, SerializationError> {
+    // check the version before trying to make sense of the rest of the document
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let version = value
+        .get("format_version")
+        .and_then(|version| version.as_u64())
+        .ok_or_else(|| SerializationError::Json("missing format_version".to_string()))?;
+    if !(OLDEST_FORMAT_VERSION as u64..=FORMAT_VERSION as u64).contains(&version) {
+        return Err(SerializationError::UnsupportedVersion(version));
+    }
+
+    let document: Document = serde_json::from_value(value)?;
+    Ok(document.contents)
+}
+
+pub fn ast_to_json(node: &ASTNode) -> String {
+    to_json(Contents::Ast { ast: node.clone() })
+}
+
+pub fn ast_from_json(json: &str) -> Result<ASTNode, SerializationError> {
+    match from_json(json)? {
+        Contents::Ast { ast } => Ok(ast),
+        other => Err(SerializationError::WrongKind(other.kind().to_string())),
+    }
+}
+
+pub fn circuit_to_json(circuit: &Circuit) -> String {
+    to_json(Contents::Circuit {
+        circuit: CircuitData::from(circuit),
+    })
+}
+
+pub fn circuit_from_json(json: &str) -> Result<Circuit, SerializationError> {
+    circuit_from_json_with_parts(json, &PartRegistry::builtin())
+}
+
+/// for circuits that use parts outside of the builtin ones
+pub fn circuit_from_json_with_parts(
+    json: &str,
+    parts: &PartRegistry,
+) -> Result<Circuit, SerializationError> {
+    match from_json(json)? {
+        Contents::Circuit { circuit } => circuit.to_circuit(parts),
+        other => Err(SerializationError::WrongKind(other.kind().to_string())),
+    }
+}
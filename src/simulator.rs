@@ -0,0 +1,164 @@
+//! evaluates a ```Circuit``` with ideal values, no external spice run needed
+
+use std::collections::VecDeque;
+
+use crate::translator::{Circuit, PartInfo};
+
+#[derive(Debug, Clone)]
+pub enum SimulationError {
+    WrongInputCount {
+        expected: usize,
+        got: usize,
+    },
+    /// the consuming index isn't driven by anything
+    UnconnectedInput(usize),
+    /// the consuming index is driven by more than one producer
+    MultipleDrivers(usize),
+    /// a connection points at an index that doesn't exist
+    InvalidConnection(usize, usize),
+    /// the parts feed back into each other so there is no order to evaluate them in
+    CombinationalLoop,
+    /// the part didn't give a value for every output, like a spice function without an ACL body
+    NoBehavior(String),
+    /// a program input nothing gives a value for, like a name the function never defined
+    UnknownInput(String),
+}
+
+/// run the circuit on the given program inputs and return the value of each program output
+pub fn simulate(circuit: &Circuit, inputs: &[f64]) -> Result<Vec<f64>, SimulationError> {
+    let program_inputs = circuit.get_program_inputs();
+    if inputs.len() != program_inputs.len() {
+        return Err(SimulationError::WrongInputCount {
+            expected: program_inputs.len(),
+            got: inputs.len(),
+        });
+    }
+
+    let Schedule {
+        part_offsets,
+        drivers,
+        order,
+    } = schedule(circuit)?;
+
+    let mut values = vec![0.0; circuit.get_next_output_index()];
+    for (input, value) in program_inputs.iter().zip(inputs) {
+        values[input.index] = *value;
+    }
+
+    for part_index in order {
+        let part = &circuit.get_parts()[part_index];
+        let offsets = part_offsets[part_index];
+        let input_values = (offsets.input_offset..offsets.input_offset + part.get_input_size())
+            .map(|index| {
+                drivers[index]
+                    .map(|driver| values[driver])
+                    .ok_or(SimulationError::UnconnectedInput(index))
+            })
+            .collect::<Result<Vec<f64>, _>>()?;
+
+        // go into nested circuits ourselves so their errors aren't turned into panics
+        let output_values = match part.as_circuit() {
+            Some(nested) => simulate(nested, &input_values)?,
+            None => part.evaluate(&input_values),
+        };
+        if output_values.len() != part.get_output_size() {
+            return Err(SimulationError::NoBehavior(part.get_name()));
+        }
+
+        for (i, value) in output_values.into_iter().enumerate() {
+            values[offsets.output_offset + i] = value;
+        }
+    }
+
+    circuit
+        .get_program_outputs()
+        .iter()
+        .map(|&index| {
+            drivers[index]
+                .map(|driver| values[driver])
+                .ok_or(SimulationError::UnconnectedInput(index))
+        })
+        .collect()
+}
+
+/// what every evaluation of the circuit needs to know, shared with the range analysis
+pub(crate) struct Schedule {
+    pub(crate) part_offsets: Vec<PartInfo>,
+    /// the producing index driving every consuming index
+    pub(crate) drivers: Vec<Option<usize>>,
+    /// every part after the parts that drive it
+    pub(crate) order: Vec<usize>,
+}
+
+pub(crate) fn schedule(circuit: &Circuit) -> Result<Schedule, SimulationError> {
+    // figure out which part produces every value, program inputs are left as None
+    let part_offsets = circuit.get_part_offsets();
+    let mut producers = vec![None; circuit.get_next_output_index()];
+    for (part_index, (part, offsets)) in circuit.get_parts().iter().zip(&part_offsets).enumerate() {
+        for i in 0..part.get_output_size() {
+            producers[offsets.output_offset + i] = Some(part_index);
+        }
+    }
+
+    // which producing index drives each consuming index
+    let mut drivers = vec![None; circuit.get_next_input_index()];
+    for &(from, to) in circuit.get_connections() {
+        if from >= producers.len() || to >= drivers.len() {
+            return Err(SimulationError::InvalidConnection(from, to));
+        }
+        if drivers[to].is_some() {
+            return Err(SimulationError::MultipleDrivers(to));
+        }
+        drivers[to] = Some(from);
+    }
+
+    let order = evaluation_order(circuit, &part_offsets, &producers, &drivers)?;
+    Ok(Schedule {
+        part_offsets,
+        drivers,
+        order,
+    })
+}
+
+/// orders the parts so every part comes after the parts that drive it
+fn evaluation_order(
+    circuit: &Circuit,
+    part_offsets: &[PartInfo],
+    producers: &[Option<usize>],
+    drivers: &[Option<usize>],
+) -> Result<Vec<usize>, SimulationError> {
+    let part_count = circuit.get_parts().len();
+    let mut dependents = vec![vec![]; part_count];
+    let mut waiting_on = vec![0; part_count];
+
+    for (part_index, (part, offsets)) in circuit.get_parts().iter().zip(part_offsets).enumerate() {
+        let part_drivers =
+            &drivers[offsets.input_offset..offsets.input_offset + part.get_input_size()];
+        for driver in part_drivers {
+            let driver = driver.and_then(|driver| producers[driver]);
+            if let Some(driver_part) = driver {
+                dependents[driver_part].push(part_index);
+                waiting_on[part_index] += 1;
+            }
+        }
+    }
+
+    let mut ready = (0..part_count)
+        .filter(|&part_index| waiting_on[part_index] == 0)
+        .collect::<VecDeque<_>>();
+    let mut order = vec![];
+    while let Some(part_index) = ready.pop_front() {
+        order.push(part_index);
+        for &dependent in &dependents[part_index] {
+            waiting_on[dependent] -= 1;
+            if waiting_on[dependent] == 0 {
+                ready.push_back(dependent);
+            }
+        }
+    }
+
+    if order.len() != part_count {
+        return Err(SimulationError::CombinationalLoop);
+    }
+    Ok(order)
+}
@@ -1,9 +1,236 @@
 //! translates a ```Circuit``` to a spice netlist
+//!
+//! every circuit becomes a ```.SUBCKT``` with its inputs and outputs as ports, the main circuit is
+//! instanced once at the top level so its nets are named after its inputs and ```out0```, ```out1```...
+//! the arithmetic parts are ideal behavioral sources for now
 
-use crate::translator::Circuit;
+use std::collections::BTreeMap;
 
-struct SpiceTranslator {
+use crate::translator::{Circuit, PartInternal};
+
+pub struct SpiceTranslator {
     circuit: Circuit,
     spice: String,
 }
 
+impl SpiceTranslator {
+    pub fn new(circuit: Circuit) -> Self {
+        SpiceTranslator {
+            circuit,
+            spice: String::new(),
+        }
+    }
+
+    pub fn translate(mut self) -> String {
+        let circuit = self.circuit.clone();
+        let top_name = subcircuit_name(&circuit);
+        self.spice
+            .push_str(&format!("* {} generated by ACL\n", top_name));
+
+        // the primitives and functions are defined before anything uses them
+        let mut primitives = BTreeMap::new();
+        let mut functions = BTreeMap::new();
+        collect_definitions(&circuit, &mut primitives, &mut functions);
+        for model in primitives.values() {
+            self.spice.push('\n');
+            self.spice.push_str(model);
+        }
+        for function in functions.values() {
+            self.spice.push('\n');
+            self.spice.push_str(&subcircuit(function));
+        }
+        self.spice.push('\n');
+        self.spice.push_str(&subcircuit(&circuit));
+
+        // instance the main circuit so the testbench has something to drive
+        let mut ports = input_port_names(&circuit);
+        ports.extend(output_port_names(&circuit));
+        self.spice.push('\n');
+        self.spice
+            .push_str(&format!("X{} {} {}\n", top_name, ports.join(" "), top_name));
+        self.spice.push_str(".END\n");
+        self.spice
+    }
+}
+
+pub fn circuit_to_spice(circuit: &Circuit) -> String {
+    SpiceTranslator::new(circuit.clone()).translate()
+}
+
+pub(crate) fn subcircuit_name(circuit: &Circuit) -> String {
+    sanitize(circuit.get_circuit_name().unwrap_or("circuit"))
+}
+
+/// spice node and subcircuit names can't have anything fancy in them
+pub(crate) fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+pub(crate) fn input_port_names(circuit: &Circuit) -> Vec<String> {
+    circuit
+        .get_program_inputs()
+        .iter()
+        .enumerate()
+        .map(|(i, input)| match &input.name {
+            Some(name) => sanitize(name),
+            None => format!("in{}", i),
+        })
+        .collect()
+}
+
+pub(crate) fn output_port_names(circuit: &Circuit) -> Vec<String> {
+    (0..circuit.get_program_outputs().len())
+        .map(|i| format!("out{}", i))
+        .collect()
+}
+
+/// finds every primitive and nested circuit, keyed by name so each one is only defined once
+fn collect_definitions(
+    circuit: &Circuit,
+    primitives: &mut BTreeMap<String, String>,
+    functions: &mut BTreeMap<String, Circuit>,
+) {
+    for part in circuit.get_parts() {
+        match part.as_circuit() {
+            Some(nested) => {
+                // every call to a function shares the definition, it only has to be looked at once
+                let name = subcircuit_name(nested);
+                if functions.contains_key(&name) {
+                    continue;
+                }
+                collect_definitions(nested, primitives, functions);
+                functions.insert(name, nested.clone());
+            }
+            None => {
+                if let Some(model) = primitive_model(&**part) {
+                    primitives.entry(part.get_name()).or_insert(model);
+                }
+            }
+        }
+    }
+}
+
+/// the model of a primitive from its ```spice_model```, None for parts that are written out inline
+fn primitive_model(part: &dyn PartInternal) -> Option<String> {
+    let name = part.get_name();
+    let mut ports = part
+        .get_input_pins()
+        .iter()
+        .map(|pin| sanitize(pin.get_name()))
+        .collect::<Vec<_>>();
+    let outputs = part
+        .get_output_pins()
+        .iter()
+        .map(|pin| sanitize(pin.get_name()))
+        .collect::<Vec<_>>();
+
+    let body = match part.spice_model() {
+        Some(body) => body,
+        None if part.spice_inline("0", &ports, &outputs).is_some() => return None,
+        None => format!("* no model for {} yet\n", name),
+    };
+    ports.extend(outputs);
+
+    // the parameters of the first instance become the defaults
+    let parameters = part
+        .get_parameters()
+        .iter()
+        .map(|(name, value)| format!(" {}={}", sanitize(name), value))
+        .collect::<String>();
+    let parameters = if parameters.is_empty() {
+        parameters
+    } else {
+        format!(" PARAMS:{}", parameters)
+    };
+
+    Some(format!(
+        ".SUBCKT {} {}{}\n{}.ENDS {}\n",
+        sanitize(&name),
+        ports.join(" "),
+        parameters,
+        body,
+        sanitize(&name)
+    ))
+}
+
+/// the ```.SUBCKT``` for a whole circuit
+fn subcircuit(circuit: &Circuit) -> String {
+    let name = subcircuit_name(circuit);
+    let input_names = input_port_names(circuit);
+    let output_names = output_port_names(circuit);
+
+    // every net is named after whatever produces it
+    let mut net_names = (0..circuit.get_next_output_index())
+        .map(|index| format!("n{}", index))
+        .collect::<Vec<_>>();
+    for (input, name) in circuit.get_program_inputs().iter().zip(&input_names) {
+        net_names[input.index] = name.clone();
+    }
+    let mut drivers = vec![None; circuit.get_next_input_index()];
+    for &(from, to) in circuit.get_connections() {
+        drivers[to] = Some(from);
+    }
+    let consumer_net = |index: usize| match drivers[index] {
+        Some(driver) => net_names[driver].clone(),
+        None => format!("unconnected{}", index),
+    };
+
+    let mut ports = input_names.clone();
+    ports.extend(output_names.iter().cloned());
+    let mut spice = format!(".SUBCKT {} {}\n", name, ports.join(" "));
+
+    let part_offsets = circuit.get_part_offsets();
+    for (i, (part, offsets)) in circuit.get_parts().iter().zip(part_offsets).enumerate() {
+        let inputs = (0..part.get_input_size())
+            .map(|input| consumer_net(offsets.input_offset + input))
+            .collect::<Vec<_>>();
+        let outputs = (0..part.get_output_size())
+            .map(|output| net_names[offsets.output_offset + output].clone())
+            .collect::<Vec<_>>();
+        let parameters = part
+            .get_parameters()
+            .iter()
+            .map(|(name, value)| format!("{}={}", sanitize(name), value))
+            .collect::<Vec<_>>();
+
+        let inline = part.spice_inline(&i.to_string(), &inputs, &outputs);
+        let line = match (part.as_circuit(), inline) {
+            (None, Some(line)) => line,
+            (Some(nested), _) => format!(
+                "X{} {} {} {}",
+                i,
+                inputs.join(" "),
+                outputs.join(" "),
+                subcircuit_name(nested)
+            ),
+            (None, None) => format!(
+                "X{} {} {} {} {}",
+                i,
+                inputs.join(" "),
+                outputs.join(" "),
+                sanitize(&part.get_name()),
+                parameters.join(" ")
+            ),
+        };
+        // no inputs leaves a double space behind, an inline part can be more than one line
+        for line in line.lines() {
+            spice.push_str(&line.split_whitespace().collect::<Vec<_>>().join(" "));
+            spice.push('\n');
+        }
+    }
+
+    // the outputs are buffered so a port is never tied straight to another port
+    for (output_name, &index) in output_names.iter().zip(circuit.get_program_outputs()) {
+        spice.push_str(&format!(
+            "E{} {} 0 {} 0 1\n",
+            output_name,
+            output_name,
+            consumer_net(index)
+        ));
+    }
+
+    spice.push_str(&format!(".ENDS {}\n", name));
+    spice
+}
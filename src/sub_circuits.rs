@@ -1,6 +1,22 @@
-use crate::translator::Part;
+use crate::{
+    range_analysis::Interval,
+    translator::{Part, Pin, PinType},
+};
 // ! these are placeholders for now, they should be circuits of transistors or something
 
+// logic levels are 0 and 1, anything above the midpoint counts as high
+fn is_high(value: f64) -> bool {
+    value > 0.5
+}
+
+fn as_level(high: bool) -> f64 {
+    if high {
+        1.0
+    } else {
+        0.0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Multiplier {}
 
@@ -16,6 +32,32 @@ impl Part for Multiplier {
     fn get_output_size(&self) -> usize {
         1
     }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![inputs[0] * inputs[1]]
+    }
+
+    fn output_ranges(&self, inputs: &[Interval]) -> Option<Vec<Interval>> {
+        Some(vec![inputs[0].mul(&inputs[1])])
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=V(in0)*V(in1)\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ V({}) * V({});\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {} = {} * {};\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -33,9 +75,36 @@ impl Part for Adder {
     fn get_output_size(&self) -> usize {
         1
     }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![inputs[0] + inputs[1]]
+    }
+
+    fn output_ranges(&self, inputs: &[Interval]) -> Option<Vec<Interval>> {
+        Some(vec![inputs[0].add(&inputs[1])])
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=V(in0)+V(in1)\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ V({}) + V({});\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {} = {} + {};\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
+/// divides exactly, the quotient is a ```Float``` so there is no verilog model, ```/``` there would truncate it
 pub struct Divider {}
 
 impl Part for Divider {
@@ -50,6 +119,25 @@ impl Part for Divider {
     fn get_output_size(&self) -> usize {
         1
     }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![inputs[0] / inputs[1]]
+    }
+
+    fn output_ranges(&self, inputs: &[Interval]) -> Option<Vec<Interval>> {
+        Some(vec![inputs[0].div(&inputs[1])])
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=V(in0)/V(in1)\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ V({}) / V({});\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -67,6 +155,32 @@ impl Part for Subtractor {
     fn get_output_size(&self) -> usize {
         1
     }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![inputs[0] - inputs[1]]
+    }
+
+    fn output_ranges(&self, inputs: &[Interval]) -> Option<Vec<Interval>> {
+        Some(vec![inputs[0].sub(&inputs[1])])
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=V(in0)-V(in1)\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ V({}) - V({});\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {} = {} - {};\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -85,10 +199,36 @@ impl Part for Comparator {
     fn get_output_size(&self) -> usize {
         1
     }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        vec![Pin::new("out0", PinType::Logic)]
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![as_level(inputs[0] > inputs[1])]
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=V(in0)>V(in1) ? 1 : 0\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ V({}) > V({}) ? 1 : 0;\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {} = {} > {} ? 1 : 0;\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
-/// A comparator that outputs 1 if the first input is greater than the second, 0 otherwise
+/// outputs 1 if both inputs are high, 0 otherwise, ```&&``` is built from it
 pub struct And {}
 
 impl Part for And {
@@ -103,6 +243,138 @@ impl Part for And {
     fn get_output_size(&self) -> usize {
         1
     }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        vec![
+            Pin::new("in0", PinType::Logic),
+            Pin::new("in1", PinType::Logic),
+        ]
+    }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        vec![Pin::new("out0", PinType::Logic)]
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![as_level(is_high(inputs[0]) && is_high(inputs[1]))]
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=(V(in0)>0.5 && V(in1)>0.5) ? 1 : 0\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ (V({}) > 0.5 && V({}) > 0.5) ? 1 : 0;\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {} = ({} != 0 && {} != 0) ? 1 : 0;\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// outputs 1 if either input is high, 0 otherwise, ```||``` is built from it
+pub struct Or {}
+
+impl Part for Or {
+    fn get_name(&self) -> String {
+        "Or".to_string()
+    }
+
+    fn get_input_size(&self) -> usize {
+        2
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        vec![
+            Pin::new("in0", PinType::Logic),
+            Pin::new("in1", PinType::Logic),
+        ]
+    }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        vec![Pin::new("out0", PinType::Logic)]
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![as_level(is_high(inputs[0]) || is_high(inputs[1]))]
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=(V(in0)>0.5 || V(in1)>0.5) ? 1 : 0\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ (V({}) > 0.5 || V({}) > 0.5) ? 1 : 0;\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {} = ({} != 0 || {} != 0) ? 1 : 0;\n",
+            outputs[0], inputs[0], inputs[1]
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// outputs 1 if the input is low and 0 if it is high, ```!``` is built from it
+pub struct Not {}
+
+impl Part for Not {
+    fn get_name(&self) -> String {
+        "Not".to_string()
+    }
+
+    fn get_input_size(&self) -> usize {
+        1
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        vec![Pin::new("in0", PinType::Logic)]
+    }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        vec![Pin::new("out0", PinType::Logic)]
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![as_level(!is_high(inputs[0]))]
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=V(in0)>0.5 ? 0 : 1\n".to_string())
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({}) <+ V({}) > 0.5 ? 0 : 1;\n",
+            outputs[0], inputs[0]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {} = {} != 0 ? 0 : 1;\n",
+            outputs[0], inputs[0]
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -124,4 +396,49 @@ impl Part for IfGate {
     fn get_output_size(&self) -> usize {
         2
     }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        vec![
+            Pin::new("in0", PinType::Logic),
+            Pin::new("in1", PinType::Analog),
+        ]
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        if is_high(inputs[0]) {
+            vec![0.0, inputs[1]]
+        } else {
+            vec![inputs[1], 0.0]
+        }
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some(
+            concat!(
+                "B1 out0 0 V=V(in0)>0.5 ? 0 : V(in1)\n",
+                "B2 out1 0 V=V(in0)>0.5 ? V(in1) : 0\n"
+            )
+            .to_string(),
+        )
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "V({out0}) <+ V({control}) > 0.5 ? 0 : V({input});\nV({out1}) <+ V({control}) > 0.5 ? V({input}) : 0;\n",
+            out0 = outputs[0],
+            out1 = outputs[1],
+            control = inputs[0],
+            input = inputs[1]
+        ))
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "assign {out0} = {control} != 0 ? 0 : {input};\nassign {out1} = {control} != 0 ? {input} : 0;\n",
+            out0 = outputs[0],
+            out1 = outputs[1],
+            control = inputs[0],
+            input = inputs[1]
+        ))
+    }
 }
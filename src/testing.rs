@@ -0,0 +1,230 @@
+//! runs the ```test``` blocks of a program, once through the interpreter and once with every function the
+//! test calls swapped for the simulated circuit of that function, so what the program should do and
+//! what the hardware does are checked against the same ```assert```s
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    interpreter::{Interpreter, InterpreterError},
+    simulator::{self, SimulationError},
+    ASTNode, Circuit, CompileError, Compiler, CompilerOptions, Expression, Part,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Interpreter,
+    /// the simulator from ```simulator::simulate```
+    Circuit,
+}
+
+#[derive(Debug, Clone)]
+pub enum TestFailure {
+    Interpreter(InterpreterError),
+    /// a function the test calls couldn't be turned into a circuit
+    Compile(CompileError),
+    /// the circuit of a function the test calls couldn't be simulated
+    Simulation(SimulationError),
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    name: String,
+    backend: Backend,
+    result: Result<(), TestFailure>,
+}
+
+impl TestResult {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn get_result(&self) -> &Result<(), TestFailure> {
+        &self.result
+    }
+
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// every test with the interpreter then with the circuits, in the order they are in the file
+pub fn run_tests(
+    program: &ASTNode,
+    options: &CompilerOptions,
+) -> Result<Vec<TestResult>, InterpreterError> {
+    let interpreter = Interpreter::with_parts(program, &options.parts)?;
+    let ASTNode::Program(nodes) = program else {
+        return Err(InterpreterError::NotAProgram);
+    };
+    let functions = nodes
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::FunctionDefinition(function) => Some(function),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // every function is only compiled once however many tests call it
+    let mut circuits: HashMap<String, Result<SimulatedFunction, CompileError>> = HashMap::new();
+    // the interpreter only sees a function without a value when its circuit fails, this keeps why
+    let simulation_error = Rc::new(RefCell::new(None));
+    let mut results = vec![];
+    for test in interpreter.get_tests() {
+        let name = test.get_name().to_string();
+        results.push(TestResult {
+            name: name.clone(),
+            backend: Backend::Interpreter,
+            result: interpreter
+                .run_test(&name)
+                .map_err(TestFailure::Interpreter),
+        });
+
+        let mut called = vec![];
+        for node in test.get_body() {
+            called_functions(node, &mut called);
+        }
+        let mut simulated = Interpreter::with_parts(program, &options.parts)?;
+        let mut result = Ok(());
+        for function in functions.iter().filter(|f| called.contains(&f.get_name())) {
+            let circuit = circuits
+                .entry(function.get_name().to_string())
+                .or_insert_with(|| {
+                    let compiler = Compiler::with_options(CompilerOptions {
+                        entry_point: function.get_name().to_string(),
+                        ..options.clone()
+                    });
+                    compiler
+                        .translate(program)
+                        .map(|circuit| SimulatedFunction {
+                            circuit: Rc::new(circuit),
+                            arguments: function.get_args().iter().map(|(a, _)| a.clone()).collect(),
+                            error: Rc::clone(&simulation_error),
+                        })
+                });
+            match circuit {
+                Ok(circuit) => simulated.bind(function.get_name(), Box::new(circuit.clone())),
+                Err(error) => result = Err(TestFailure::Compile(error.clone())),
+            }
+        }
+        let result = result.and_then(|()| {
+            let outcome = simulated.run_test(&name);
+            match simulation_error.borrow_mut().take() {
+                Some(error) => Err(TestFailure::Simulation(error)),
+                None => outcome.map_err(TestFailure::Interpreter),
+            }
+        });
+        results.push(TestResult {
+            name: name.clone(),
+            backend: Backend::Circuit,
+            result,
+        });
+    }
+    Ok(results)
+}
+
+/// the names of every function called anywhere in the statement, not looking inside the functions
+fn called_functions<'a>(node: &'a ASTNode, called: &mut Vec<&'a str>) {
+    match node {
+        ASTNode::Expression(expression) => called_in_expression(expression, called),
+        ASTNode::Return(inner) => called_functions(inner, called),
+        ASTNode::Assignment(assignment) | ASTNode::Reassignment(assignment) => {
+            called_in_expression(assignment.get_value(), called)
+        }
+        ASTNode::Assert(assertion) => called_in_expression(assertion.get_condition(), called),
+        ASTNode::IfStatement(statement) => {
+            called_in_expression(statement.get_condition(), called);
+            for node in statement.get_body().iter().chain(statement.get_else_body()) {
+                called_functions(node, called);
+            }
+        }
+        ASTNode::ForLoop(for_loop) => {
+            for node in for_loop.get_body() {
+                called_functions(node, called);
+            }
+        }
+        ASTNode::Program(_)
+        | ASTNode::FunctionDefinition(_)
+        | ASTNode::ExternFunction(_)
+        | ASTNode::Test(_) => (),
+    }
+}
+
+fn called_in_expression<'a>(expression: &'a Expression, called: &mut Vec<&'a str>) {
+    match expression {
+        Expression::FunctionCall(call) => {
+            called.push(call.get_name());
+            for arg in call.get_args() {
+                called_functions(arg, called);
+            }
+        }
+        Expression::Dyadic(dyadic) => {
+            called_in_expression(dyadic.get_left(), called);
+            called_in_expression(dyadic.get_right(), called);
+        }
+        Expression::ParenExpression(inner) | Expression::Not(inner) => {
+            called_in_expression(inner, called)
+        }
+        Expression::IfExpression(if_expression) => {
+            called_in_expression(if_expression.get_condition(), called);
+            for block in [if_expression.get_body(), if_expression.get_else_body()] {
+                for node in block.get_statements() {
+                    called_functions(node, called);
+                }
+                called_in_expression(block.get_value(), called);
+            }
+        }
+        Expression::Value(_) | Expression::Identifier(_) => (),
+    }
+}
+
+/// a function as the part the interpreter calls, the arguments are matched to the circuit inputs by name
+/// since the translator makes the inputs in whatever order it meets them
+#[derive(Debug, Clone)]
+struct SimulatedFunction {
+    circuit: Rc<Circuit>,
+    arguments: Vec<String>,
+    /// the first simulation that failed, ```evaluate``` can only give back no outputs
+    error: Rc<RefCell<Option<SimulationError>>>,
+}
+
+impl Part for SimulatedFunction {
+    fn get_name(&self) -> String {
+        Part::get_name(&*self.circuit)
+    }
+
+    fn get_input_size(&self) -> usize {
+        self.arguments.len()
+    }
+
+    fn get_output_size(&self) -> usize {
+        self.circuit.get_program_outputs().len()
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        // an input that isn't an argument is a name the function never defined
+        let inputs = self
+            .circuit
+            .get_program_inputs()
+            .iter()
+            .map(|input| {
+                let name = input.get_name().unwrap_or_default();
+                self.arguments
+                    .iter()
+                    .position(|argument| argument == name)
+                    .map(|position| inputs[position])
+                    .ok_or_else(|| SimulationError::UnknownInput(name.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        match inputs.and_then(|inputs| simulator::simulate(&self.circuit, &inputs)) {
+            Ok(outputs) => outputs,
+            Err(error) => {
+                self.error.borrow_mut().get_or_insert(error);
+                vec![]
+            }
+        }
+    }
+}
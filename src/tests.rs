@@ -1,30 +1,1845 @@
 use super::*;
-use test_each_file::test_each_file;
+use std::{collections::BTreeMap, env, fs, path::Path};
+use test_each_file::{test_each_file, test_each_path};
 
 test_each_file! { in "./test_scripts" => test_script }
 
 // test the test scripts compile
 fn test_script(unparsed_file: &str) {
-    let parse_result = HLHDLParser::parse(super::Rule::program, &unparsed_file);
-
-    match parse_result {
-        Ok(parsed) => {
-            let mut all_nodes = vec![];
-            for pair in parsed {
-                let ast = super::build_ast(pair.clone());
-                if let Some(ast) = ast {
-                    all_nodes.push(ast);
-                }
-                // none is returned for like EOI
+    let circuit = Compiler::new()
+        .compile(unparsed_file)
+        .unwrap_or_else(|e| panic!("{}", e));
+    println!("{:#?}", circuit);
+}
+
+test_each_file! { in "./test_scripts" as test_blocks => test_blocks_pass }
+
+// the test blocks in the scripts are their expected behaviour
+fn test_blocks_pass(unparsed_file: &str) {
+    let program = parse_program(unparsed_file);
+    let results = testing::run_tests(&program, &CompilerOptions::default()).unwrap();
+    for result in results {
+        assert!(
+            result.passed(),
+            "{} ({:?}) failed: {:?}",
+            result.get_name(),
+            result.get_backend(),
+            result.get_result()
+        );
+    }
+}
+
+test_each_file! { in "./test_scripts" as differential => differential_test }
+
+// every input takes each of these values when there are few enough inputs to try every combination
+const GRID_VALUES: [i32; 7] = [-7, -3, -1, 0, 1, 2, 5];
+const MAX_GRID_SIZE: usize = 2401;
+// otherwise the inputs are sampled from this range instead
+const RANDOM_SAMPLES: usize = 500;
+const RANDOM_RANGE: i32 = 100;
+
+// runs the interpreter and the compiled circuit on the same inputs, they have to agree
+fn differential_test(unparsed_file: &str) {
+    let program = parse_program(unparsed_file);
+    let interpreter = interpreter::Interpreter::new(&program).expect("failed to load program");
+    let main_args = main_arg_types(&program);
+    let circuit = translate_program(&program);
+    // flattening must not change what the circuit does and neither must saving it
+    let flat_circuit = circuit.flatten();
+    let loaded_circuit =
+        serialization::circuit_from_json(&serialization::circuit_to_json(&circuit))
+            .expect("failed to load circuit");
+
+    // the circuit inputs are matched up with the arguments of main by name
+    let input_order = circuit
+        .get_program_inputs()
+        .iter()
+        .map(|input| {
+            let name = input.name.clone().unwrap_or_default();
+            main_args
+                .iter()
+                .position(|(arg, _)| *arg == name)
+                .unwrap_or_else(|| panic!("circuit input {:?} isn't an argument of main", name))
+        })
+        .collect::<Vec<_>>();
+
+    let mut failures = vec![];
+    for args in test_inputs(main_args.len()) {
+        // the type checker already rules out calls outside an Int<low..high> and a Bool is only ever a level
+        let in_range = args
+            .iter()
+            .zip(&main_args)
+            .all(|(&arg, (_, arg_type))| match arg_type {
+                Type::Bool => arg == 0 || arg == 1,
+                _ => arg_type
+                    .get_range()
+                    .is_none_or(|range| range.contains(arg as f64)),
+            });
+        if !in_range {
+            continue;
+        }
+        let values = args
+            .iter()
+            .zip(&main_args)
+            .map(|(&arg, (_, arg_type))| match arg_type {
+                Type::Bool => super::Value::Bool(arg != 0),
+                _ => super::Value::Int(arg),
+            })
+            .collect();
+        let expected = match interpreter.run_main(values) {
+            Ok(Some(super::Value::Int(value))) => vec![value as f64],
+            Ok(Some(super::Value::Float(value))) => vec![value],
+            Ok(Some(super::Value::Bool(value))) => vec![value as i32 as f64],
+            Ok(None) => vec![],
+            // the circuit has no notion of these, so there is nothing to compare
+            Err(interpreter::InterpreterError::Overflow)
+            | Err(interpreter::InterpreterError::DivisionByZero) => continue,
+            other => panic!("interpreter failed on {:?}: {:?}", args, other),
+        };
+
+        let circuit_inputs = input_order
+            .iter()
+            .map(|&arg| args[arg] as f64)
+            .collect::<Vec<_>>();
+        let actual = simulator::simulate(&circuit, &circuit_inputs)
+            .unwrap_or_else(|e| panic!("simulation failed on {:?}: {:?}", args, e));
+        let flat_actual = simulator::simulate(&flat_circuit, &circuit_inputs)
+            .unwrap_or_else(|e| panic!("flat simulation failed on {:?}: {:?}", args, e));
+        assert_eq!(
+            actual, flat_actual,
+            "flattening changed the output for {:?}",
+            args
+        );
+        let loaded_actual = simulator::simulate(&loaded_circuit, &circuit_inputs)
+            .unwrap_or_else(|e| panic!("loaded simulation failed on {:?}: {:?}", args, e));
+        assert_eq!(
+            actual, loaded_actual,
+            "saving to json changed the output for {:?}",
+            args
+        );
+
+        let agrees = expected.len() == actual.len()
+            && expected
+                .iter()
+                .zip(&actual)
+                .all(|(expected, actual)| (expected - actual).abs() < 1e-9);
+        if !agrees {
+            failures.push((args, expected, actual));
+        }
+    }
+
+    // report the smallest input that disagrees, it is the easiest one to debug
+    let failure_count = failures.len();
+    let minimal = failures.into_iter().min_by_key(|(args, _, _)| {
+        (
+            args.iter().map(|arg| arg.unsigned_abs()).sum::<u32>(),
+            args.clone(),
+        )
+    });
+    if let Some((args, expected, actual)) = minimal {
+        panic!(
+            "interpreter and circuit disagree on {} inputs, smallest is {:?}: expected {:?} got {:?}",
+            failure_count, args, expected, actual
+        );
+    }
+}
+
+fn main_arg_types(program: &super::ASTNode) -> Vec<(String, super::Type)> {
+    let super::ASTNode::Program(nodes) = program else {
+        panic!("expected a program");
+    };
+    nodes
+        .iter()
+        .find_map(|node| match node {
+            super::ASTNode::FunctionDefinition(func_def) if func_def.get_name() == "main" => {
+                Some(func_def.get_args().clone())
             }
-            let node = super::ASTNode::Program(all_nodes);
+            _ => None,
+        })
+        .expect("program has no main")
+}
+
+fn test_inputs(arity: usize) -> Vec<Vec<i32>> {
+    let grid_size = GRID_VALUES.len().checked_pow(arity as u32);
+    if grid_size.is_some_and(|size| size <= MAX_GRID_SIZE) {
+        let mut inputs = vec![vec![]];
+        for _ in 0..arity {
+            inputs = inputs
+                .into_iter()
+                .flat_map(|prefix: Vec<i32>| {
+                    GRID_VALUES.iter().map(move |value| {
+                        let mut input = prefix.clone();
+                        input.push(*value);
+                        input
+                    })
+                })
+                .collect();
+        }
+        return inputs;
+    }
+
+    // xorshift with a fixed seed so failures can be reproduced
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % (2 * RANDOM_RANGE as u64 + 1)) as i32 - RANDOM_RANGE
+    };
+    (0..RANDOM_SAMPLES)
+        .map(|_| (0..arity).map(|_| next()).collect())
+        .collect()
+}
 
-            let mut translator = super::translator::Translator::new();
-            let circuit = translator.translate_ast(node);
-            println!("{:#?}", circuit);
+test_each_path! { in "./test_scripts" as golden => golden_test }
+
+// set this to overwrite the expected files with the current output instead of comparing against them
+const BLESS_VARIABLE: &str = "ACL_BLESS";
+
+// the AST, circuit and netlist of every script are checked in under golden/ so changes show up in review
+fn golden_test(path: &Path) {
+    let unparsed_file = fs::read_to_string(path).expect("cannot read file");
+    let program = parse_program(&unparsed_file);
+    let ast_dump = format!("{:#?}\n", program);
+    let circuit = translate_program(&program);
+    let artifacts = [
+        ("ast", ast_dump),
+        ("circuit.json", serialization::circuit_to_json(&circuit)),
+        ("cir", spice_translator::circuit_to_spice(&circuit)),
+        // a script verilog or Verilog-A can't say has the reason checked in instead
+        (
+            "va",
+            verilog_a_translator::circuit_to_verilog_a(&circuit)
+                .unwrap_or_else(|error| format!("{:?}\n", error)),
+        ),
+        (
+            "v",
+            verilog_translator::circuit_to_verilog(&circuit, verilog_translator::DEFAULT_WIDTH)
+                .unwrap_or_else(|error| format!("{:?}\n", error)),
+        ),
+    ];
+
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("golden");
+    let bless = env::var_os(BLESS_VARIABLE).is_some();
+
+    let mut mismatches = vec![];
+    for (extension, actual) in artifacts {
+        let golden_path = golden_dir.join(format!("{}.{}", stem, extension));
+        if bless {
+            fs::create_dir_all(&golden_dir).expect("cannot create golden directory");
+            fs::write(&golden_path, actual).expect("cannot write golden file");
+            continue;
         }
-        Err(e) => {
-            panic!("Parsing error: {}", e);
+
+        match fs::read_to_string(&golden_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => mismatches.push(format!(
+                "{} changed:\n{}",
+                golden_path.display(),
+                first_difference(&expected, &actual)
+            )),
+            Err(_) => mismatches.push(format!("{} is missing", golden_path.display())),
         }
     }
+
+    if !mismatches.is_empty() {
+        panic!(
+            "{}\nrun the tests with {}=1 to accept the new output",
+            mismatches.join("\n"),
+            BLESS_VARIABLE
+        );
+    }
+}
+
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(expected), Some(actual)) if expected == actual => line += 1,
+            (None, None) => return "only the line endings differ".to_string(),
+            (expected, actual) => {
+                return format!(
+                    "line {}\n- {}\n+ {}",
+                    line,
+                    expected.unwrap_or("<end of file>"),
+                    actual.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+}
+
+fn parse_program(unparsed_file: &str) -> super::ASTNode {
+    Compiler::new()
+        .parse_program(unparsed_file)
+        .expect("failed to parse program")
+}
+
+fn translate_program(program: &super::ASTNode) -> super::translator::Circuit {
+    Compiler::new()
+        .translate(program)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[test]
+fn ast_json_round_trip() {
+    let node = parse_program(include_str!("../test_scripts/function_tester.acl"));
+    let json = serialization::ast_to_json(&node);
+    let loaded = serialization::ast_from_json(&json).expect("failed to load ast");
+    assert_eq!(serialization::ast_to_json(&loaded), json);
+}
+
+#[test]
+fn circuit_json_round_trip() {
+    let circuit = compile_program(include_str!("../test_scripts/function_tester.acl"));
+    let json = serialization::circuit_to_json(&circuit);
+    let loaded = serialization::circuit_from_json(&json).expect("failed to load circuit");
+    assert_eq!(serialization::circuit_to_json(&loaded), json);
+}
+
+#[test]
+fn json_rejects_other_versions_and_kinds() {
+    let node = parse_program(include_str!("../test_scripts/basic_adder.acl"));
+    let json = serialization::ast_to_json(&node);
+    assert!(matches!(
+        serialization::circuit_from_json(&json),
+        Err(serialization::SerializationError::WrongKind(_))
+    ));
+
+    let future = json.replacen(
+        &format!("\"format_version\": {}", serialization::FORMAT_VERSION),
+        "\"format_version\": 999",
+        1,
+    );
+    assert!(matches!(
+        serialization::ast_from_json(&future),
+        Err(serialization::SerializationError::UnsupportedVersion(999))
+    ));
+}
+
+#[test]
+fn json_rejects_indices_the_circuit_does_not_have() {
+    let circuit = compile_program(include_str!("../test_scripts/basic_adder.acl"));
+    let json: serde_json::Value =
+        serde_json::from_str(&serialization::circuit_to_json(&circuit)).unwrap();
+    let load = |pointer: &str, value: serde_json::Value| {
+        let mut json = json.clone();
+        *json.pointer_mut(pointer).unwrap() = value;
+        serialization::circuit_from_json(&json.to_string())
+    };
+
+    assert!(matches!(
+        load("/circuit/program_outputs/0", 7.into()),
+        Err(serialization::SerializationError::IndexOutOfRange(7))
+    ));
+    assert!(matches!(
+        load("/circuit/connections/0/0", 9.into()),
+        Err(serialization::SerializationError::IndexOutOfRange(9))
+    ));
+    assert!(matches!(
+        load("/circuit/program_inputs/1/index", 0.into()),
+        Err(serialization::SerializationError::IndexInUse(0))
+    ));
+}
+
+fn compile_program(unparsed_file: &str) -> super::translator::Circuit {
+    translate_program(&parse_program(unparsed_file))
+}
+
+#[test]
+fn simulate_basic_scripts() {
+    let adder = compile_program(include_str!("../test_scripts/basic_adder.acl"));
+    assert_eq!(simulator::simulate(&adder, &[2.0, 3.0]).unwrap(), vec![5.0]);
+
+    let subtracter = compile_program(include_str!("../test_scripts/basic_subtracter.acl"));
+    assert_eq!(
+        simulator::simulate(&subtracter, &[7.0, 3.0]).unwrap(),
+        vec![4.0]
+    );
+}
+
+#[test]
+fn simulate_function_call() {
+    let circuit = compile_program(include_str!("../test_scripts/function_tester.acl"));
+    assert_eq!(
+        simulator::simulate(&circuit, &[-4.0, 1.5]).unwrap(),
+        vec![-2.5]
+    );
+}
+
+#[test]
+fn simulate_rejects_wrong_input_count() {
+    let circuit = compile_program(include_str!("../test_scripts/basic_adder.acl"));
+    assert!(matches!(
+        simulator::simulate(&circuit, &[1.0]),
+        Err(simulator::SimulationError::WrongInputCount {
+            expected: 2,
+            got: 1
+        })
+    ));
+
+    // a circuit that can't be simulated has no behavior as a part instead of panicking
+    let json = r#"{
+        "format_version": 2,
+        "kind": "circuit",
+        "circuit": {
+            "name": "broken",
+            "parts": [{ "type": "Adder" }],
+            "connections": [[1, 0], [0, 2]],
+            "program_inputs": [{ "index": 1, "name": "a" }],
+            "program_outputs": [2]
+        }
+    }"#;
+    let circuit = serialization::circuit_from_json(json).expect("failed to load circuit");
+    assert!(matches!(
+        simulator::simulate(&circuit, &[1.0]),
+        Err(simulator::SimulationError::UnconnectedInput(1))
+    ));
+    assert!(Part::evaluate(&circuit, &[1.0]).is_empty());
+}
+
+fn interpret_main(unparsed_file: &str, args: &[i32]) -> Option<super::Value> {
+    let interpreter = interpreter::Interpreter::new(&parse_program(unparsed_file))
+        .expect("failed to load program");
+    let args = args.iter().map(|arg| super::Value::Int(*arg)).collect();
+    interpreter.run_main(args).expect("failed to run main")
+}
+
+#[test]
+fn interpret_assignment_and_calls() {
+    let assignment = include_str!("../test_scripts/adder_with_assignment.acl");
+    assert_eq!(
+        interpret_main(assignment, &[2, 3]),
+        Some(super::Value::Int(5))
+    );
+
+    let function = include_str!("../test_scripts/function_tester.acl");
+    assert_eq!(
+        interpret_main(function, &[-4, 1]),
+        Some(super::Value::Int(-3))
+    );
+}
+
+#[test]
+fn interpret_if_else() {
+    let script = include_str!("../test_scripts/if_statement_tester.acl");
+    assert_eq!(interpret_main(script, &[5, 3]), Some(super::Value::Int(2)));
+    assert_eq!(interpret_main(script, &[1, 3]), Some(super::Value::Int(4)));
+}
+
+#[test]
+fn interpret_recursion() {
+    let script = include_str!("../example.acl");
+    assert_eq!(interpret_main(script, &[]), Some(super::Value::Int(120)));
+}
+
+#[test]
+fn simulate_assignment() {
+    let circuit = compile_program(include_str!("../test_scripts/adder_with_assignment.acl"));
+    assert_eq!(
+        simulator::simulate(&circuit, &[2.0, 3.0]).unwrap(),
+        vec![5.0]
+    );
+}
+
+#[test]
+fn simulate_if_statements_and_expressions() {
+    // both branches are built and the comparison picks which one reaches the output
+    let statement = compile_program(include_str!("../test_scripts/if_statement_tester.acl"));
+    assert_eq!(
+        simulator::simulate(&statement, &[5.0, 3.0]).unwrap(),
+        vec![2.0]
+    );
+    assert_eq!(
+        simulator::simulate(&statement, &[1.0, 3.0]).unwrap(),
+        vec![4.0]
+    );
+
+    let expression = compile_program(include_str!("../test_scripts/if_statement_assignment.acl"));
+    assert_eq!(
+        simulator::simulate(&expression, &[5.0, 3.0]).unwrap(),
+        vec![2.0]
+    );
+    assert_eq!(
+        simulator::simulate(&expression, &[3.0, 3.0]).unwrap(),
+        vec![6.0]
+    );
+
+    // 7 after the loop, clamped to 3 and multiplied by a <= c
+    let script = include_str!("../test_scripts/comparison_tester.acl");
+    let comparisons = compile_program(script);
+    assert_eq!(
+        simulator::simulate(&comparisons, &[1.0, 2.0, 3.0]).unwrap(),
+        vec![3.0]
+    );
+    assert_eq!(
+        interpret_main(script, &[1, 2, 3]),
+        Some(super::Value::Int(3))
+    );
+}
+
+#[test]
+fn for_loops_unroll_and_reassign() {
+    let script = "fn main(a: Int) -> Int { let total = a; for i in 0..4 { let step = i * 2; total = total + step; } return total; }";
+    assert_eq!(interpret_main(script, &[1]), Some(super::Value::Int(13)));
+    assert_eq!(
+        simulator::simulate(&compile_program(script), &[1.0]).unwrap(),
+        vec![13.0]
+    );
+
+    // a let inside a block shadows until the block ends, a plain = changes the outer variable
+    let script = "fn main(a: Int) -> Int { let x = 1; let y = 0; if a > 0 { let x = 5; y = x; } return x + y; }";
+    assert_eq!(interpret_main(script, &[1]), Some(super::Value::Int(6)));
+    assert_eq!(interpret_main(script, &[0]), Some(super::Value::Int(1)));
+    let circuit = compile_program(script);
+    assert_eq!(simulator::simulate(&circuit, &[1.0]).unwrap(), vec![6.0]);
+    assert_eq!(simulator::simulate(&circuit, &[0.0]).unwrap(), vec![1.0]);
+
+    // a main that never returns is a circuit without outputs
+    let circuit = compile_program(include_str!("../test_scripts/for_loop_tester.acl"));
+    assert!(circuit.get_program_outputs().is_empty());
+    assert_eq!(
+        interpret_main(include_str!("../test_scripts/for_loop_tester.acl"), &[1, 0]),
+        None
+    );
+
+    // every iteration is a copy of the body, so a huge range is refused instead of being built
+    let script = "fn main(a: Int) -> Int { let total = a; for i in 0..100000 { total = total + i; } return total; }";
+    assert!(matches!(
+        Compiler::new().compile(script),
+        Err(CompileError::Type(
+            type_checker::TypeError::TooManyIterations {
+                iterations: 100000,
+                ..
+            }
+        ))
+    ));
+    assert!(matches!(
+        translator::Translator::new().translate_ast(parse_program(script)),
+        Err(translator::TranslationError::Unsupported(_))
+    ));
+    let interpreter = interpreter::Interpreter::new(&parse_program(script)).unwrap();
+    assert!(matches!(
+        interpreter.run_main(vec![super::Value::Int(0)]),
+        Err(interpreter::InterpreterError::TooManyIterations(100000))
+    ));
+}
+
+#[test]
+fn if_statements_merge_instead_of_copying() {
+    // every if adds a multiplexer for the variable, the rest of the function isn't built again per branch
+    let counter = |ifs: usize| {
+        let body = "if a > 0 { total = total + 1; } ".repeat(ifs);
+        compile_program(&format!(
+            "fn main(a: Int) -> Int {{ let total = 0; {}return total; }}",
+            body
+        ))
+    };
+    let (none, ten, twenty) = (counter(0), counter(10), counter(20));
+    assert_eq!(
+        twenty.get_parts().len() - ten.get_parts().len(),
+        ten.get_parts().len() - none.get_parts().len()
+    );
+    assert_eq!(simulator::simulate(&twenty, &[1.0]).unwrap(), vec![20.0]);
+    assert_eq!(simulator::simulate(&twenty, &[0.0]).unwrap(), vec![0.0]);
+
+    // a return taken in an earlier iteration wins over the ones after it
+    let script =
+        "fn main(a: Int) -> Int { for i in 0..5 { if i == a { return i * 10; } } return 0 - 1; }";
+    let circuit = compile_program(script);
+    for (a, expected) in [(0, 0), (3, 30), (7, -1)] {
+        assert_eq!(
+            interpret_main(script, &[a]),
+            Some(super::Value::Int(expected))
+        );
+        assert_eq!(
+            simulator::simulate(&circuit, &[a as f64]).unwrap(),
+            vec![expected as f64]
+        );
+    }
+
+    // nothing to output when only some paths return
+    assert!(matches!(
+        Compiler::new().compile("fn main(a: Int) -> Int { if a > 0 { return a; } }"),
+        Err(CompileError::Translation(
+            translator::TranslationError::MismatchedReturns
+        ))
+    ));
+}
+
+#[test]
+fn compiler_reports_errors() {
+    let compiler = Compiler::new();
+    assert!(matches!(
+        compiler.compile("fn main( {"),
+        Err(CompileError::Parse(_))
+    ));
+    assert!(matches!(
+        compiler.compile("fn main(a: Int) -> Int { return missing(a); }"),
+        Err(CompileError::Translation(
+            translator::TranslationError::UndefinedFunction(_)
+        ))
+    ));
+    assert!(matches!(
+        compiler.compile("fn other(a: Int) -> Int { return a; }"),
+        Err(CompileError::Translation(
+            translator::TranslationError::NoEntryPoint(_)
+        ))
+    ));
+
+    // the messages are written for whoever wrote the program, not the Debug output
+    let message = |source: &str| compiler.compile(source).err().unwrap().to_string();
+    assert_eq!(
+        message("fn main(a: Int) -> Int { return missing(a); }"),
+        "translation error: no function named missing"
+    );
+    assert_eq!(
+        message("fn main(a: Int<0..3>) -> Int<0..5> { return a * 2; }"),
+        "type error: main can return [0, 6], outside of its return type Int<0..5>"
+    );
+}
+
+#[test]
+fn compiler_entry_point_option() {
+    let compiler = Compiler::with_options(CompilerOptions {
+        entry_point: "double".to_string(),
+        ..CompilerOptions::default()
+    });
+    let circuit = compiler
+        .compile("fn double(a: Int) -> Int { return a + a; }")
+        .expect("failed to compile");
+    assert_eq!(simulator::simulate(&circuit, &[4.0]).unwrap(), vec![8.0]);
+    assert!(compiler
+        .emit(&circuit, EmitFormat::Spice)
+        .unwrap()
+        .contains(".SUBCKT double a out0"));
+}
+
+// a user defined cell with a parameter, the kind of thing a team would register
+#[derive(Debug, Clone)]
+struct Gain {
+    gain: f64,
+}
+
+impl Part for Gain {
+    fn get_name(&self) -> String {
+        "Gain".to_string()
+    }
+
+    fn get_input_size(&self) -> usize {
+        1
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        vec![Pin::new("signal", PinType::Analog)]
+    }
+
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        BTreeMap::from([("gain".to_string(), self.gain)])
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![inputs[0] * self.gain]
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some("B1 out0 0 V=V(signal)*{gain}\n".to_string())
+    }
+}
+
+#[test]
+fn registered_parts_can_be_called() {
+    let mut parts = PartRegistry::builtin();
+    parts.register(Gain { gain: 3.0 });
+    let compiler = Compiler::with_options(CompilerOptions {
+        parts: parts.clone(),
+        ..CompilerOptions::default()
+    });
+    let circuit = compiler
+        .compile(
+            "extern fn Gain(signal: Int) -> Int; fn main(a: Int) -> Int { return Gain(a) + 1; }",
+        )
+        .expect("failed to compile");
+    assert_eq!(simulator::simulate(&circuit, &[2.0]).unwrap(), vec![7.0]);
+
+    let spice = compiler.emit(&circuit, EmitFormat::Spice).unwrap();
+    assert!(
+        spice.contains(".SUBCKT Gain signal out0 PARAMS: gain=3\nB1 out0 0 V=V(signal)*{gain}\n")
+    );
+    assert!(spice.contains("X0 a n1 Gain gain=3\n"));
+
+    let json = serialization::circuit_to_json(&circuit);
+    assert!(matches!(
+        serialization::circuit_from_json(&json),
+        Err(serialization::SerializationError::UnknownPartType(_))
+    ));
+    let loaded = serialization::circuit_from_json_with_parts(&json, &parts).unwrap();
+    assert_eq!(simulator::simulate(&loaded, &[2.0]).unwrap(), vec![7.0]);
+}
+
+#[test]
+fn registered_parts_replace_the_builtins() {
+    // an adder that is off by one, so it is obvious which one got used
+    #[derive(Debug, Clone)]
+    struct SloppyAdder {}
+
+    impl Part for SloppyAdder {
+        fn get_name(&self) -> String {
+            "Adder".to_string()
+        }
+
+        fn get_input_size(&self) -> usize {
+            2
+        }
+
+        fn get_output_size(&self) -> usize {
+            1
+        }
+
+        fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+            vec![inputs[0] + inputs[1] + 1.0]
+        }
+    }
+
+    let mut parts = PartRegistry::builtin();
+    parts.register(SloppyAdder {});
+    let compiler = Compiler::with_options(CompilerOptions {
+        parts,
+        ..CompilerOptions::default()
+    });
+    let circuit = compiler
+        .compile(include_str!("../test_scripts/basic_adder.acl"))
+        .unwrap();
+    assert_eq!(
+        simulator::simulate(&circuit, &[2.0, 3.0]).unwrap(),
+        vec![6.0]
+    );
+
+    let empty = Compiler::with_options(CompilerOptions {
+        parts: PartRegistry::empty(),
+        ..CompilerOptions::default()
+    });
+    assert!(matches!(
+        empty.compile(include_str!("../test_scripts/basic_adder.acl")),
+        Err(CompileError::Translation(
+            translator::TranslationError::UndefinedPart(_)
+        ))
+    ));
+}
+
+#[test]
+fn part_library_loads_a_directory() {
+    let mut parts = PartRegistry::builtin();
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_parts");
+    let names = part_library::load_library(&directory, &mut parts).expect("failed to load parts");
+    assert_eq!(names, vec!["Gain", "Window"]);
+
+    let compiler = Compiler::with_options(CompilerOptions {
+        parts,
+        ..CompilerOptions::default()
+    });
+    let circuit = compiler
+        .compile(
+            "extern fn Gain(x: Int) -> Int;
+            extern fn Window(x: Int) -> Int;
+            fn main(a: Int) -> Int { return Gain(a) + Window(a); }",
+        )
+        .expect("failed to compile");
+    assert_eq!(simulator::simulate(&circuit, &[1.0]).unwrap(), vec![4.0]);
+    assert_eq!(simulator::simulate(&circuit, &[5.0]).unwrap(), vec![15.0]);
+
+    let spice = compiler.emit(&circuit, EmitFormat::Spice).unwrap();
+    assert!(spice.contains(".SUBCKT Window signal inside clamped PARAMS: high=2 low=-2\n"));
+
+    // the behavior doubles as the verilog-a model
+    let verilog_a = compiler.emit(&circuit, EmitFormat::VerilogA).unwrap();
+    assert!(verilog_a.contains("        V(n1) <+ V(a) * 3;\n"));
+    assert!(verilog_a.contains(
+        "        V(n3) <+ ((V(a) < -2 ? 1 : 0) != 0 ? -2 : ((V(a) > 2 ? 1 : 0) != 0 ? 2 : V(a)));\n"
+    ));
+}
+
+#[test]
+fn part_library_rejects_bad_parts() {
+    let unknown = r#"
+        name = "Broken"
+        behavior = ["signal * gian"]
+        parameters = { gain = 2.0 }
+        inputs = [{ name = "signal" }]
+        outputs = [{ name = "out" }]
+    "#;
+    assert!(matches!(
+        part_library::parse_part(unknown),
+        Err(part_library::LibraryError::UnknownName { name, .. }) if name == "gian"
+    ));
+
+    let missing_behavior = r#"
+        name = "Broken"
+        behavior = []
+        outputs = [{ name = "out" }]
+    "#;
+    assert!(matches!(
+        part_library::parse_part(missing_behavior),
+        Err(part_library::LibraryError::WrongEquationCount {
+            expected: 1,
+            got: 0,
+            ..
+        })
+    ));
+
+    let misspelled_field = r#"
+        name = "Broken"
+        behaviour = ["1"]
+        outputs = [{ name = "out" }]
+    "#;
+    assert!(matches!(
+        part_library::parse_part(misspelled_field),
+        Err(part_library::LibraryError::Toml { .. })
+    ));
+}
+
+#[test]
+fn extern_functions_bind_to_parts() {
+    let source = "
+        fn main(a: Int, b: Int) -> Int {
+            return Gain(a + b);
+        }
+        // declared after main, externs don't have to come first
+        extern fn Gain(x: Int) -> Int;
+    ";
+    let mut parts = PartRegistry::builtin();
+    parts.register(Gain { gain: 3.0 });
+    let compiler = Compiler::with_options(CompilerOptions {
+        parts: parts.clone(),
+        ..CompilerOptions::default()
+    });
+    let circuit = compiler.compile(source).expect("failed to compile");
+    assert_eq!(
+        simulator::simulate(&circuit, &[1.0, 2.0]).unwrap(),
+        vec![9.0]
+    );
+
+    let program = parse_program(source);
+    let interpreter = interpreter::Interpreter::with_parts(&program, &parts).unwrap();
+    assert_eq!(
+        interpreter
+            .run_main(vec![super::Value::Int(1), super::Value::Int(2)])
+            .unwrap(),
+        Some(super::Value::Int(9))
+    );
+    let unbound = interpreter::Interpreter::new(&program).unwrap();
+    assert!(matches!(
+        unbound.run_main(vec![super::Value::Int(1), super::Value::Int(2)]),
+        Err(interpreter::InterpreterError::UnboundExtern(_))
+    ));
+
+    // no part to bind to, or the part doesn't match the declaration
+    assert!(matches!(
+        Compiler::new().compile(source),
+        Err(CompileError::Translation(
+            translator::TranslationError::UndefinedPart(_)
+        ))
+    ));
+    assert!(matches!(
+        compiler
+            .compile("extern fn Gain(x: Int, y: Int) -> Int; fn main(a: Int) -> Int { return a; }"),
+        Err(CompileError::Translation(
+            translator::TranslationError::WrongPinCount { .. }
+        ))
+    ));
+    // registered parts can't be called without declaring them
+    assert!(matches!(
+        compiler.compile("fn main(a: Int) -> Int { return Gain(a); }"),
+        Err(CompileError::Translation(
+            translator::TranslationError::UndefinedFunction(_)
+        ))
+    ));
+}
+
+#[test]
+fn spice_functions_without_a_body() {
+    let source = "
+        fn divider(x: Int) -> Int spice {
+            R1 x out0 1k
+            R2 out0 0 1k
+        }
+
+        fn main(a: Int) -> Int {
+            return divider(a);
+        }
+    ";
+    let circuit = compile_program(source);
+    let spice = spice_translator::circuit_to_spice(&circuit);
+    assert!(spice.contains(".SUBCKT divider x out0\nR1 x out0 1k\nR2 out0 0 1k\n.ENDS divider\n"));
+    assert!(matches!(
+        simulator::simulate(&circuit, &[1.0]),
+        Err(simulator::SimulationError::NoBehavior(name)) if name == "divider"
+    ));
+
+    let json = serialization::circuit_to_json(&circuit);
+    let loaded = serialization::circuit_from_json(&json).expect("failed to load circuit");
+    assert_eq!(spice_translator::circuit_to_spice(&loaded), spice);
+
+    assert!(matches!(
+        Compiler::new().compile("fn main(a: Int) -> Int spice { R1 a out0 1k }"),
+        Err(CompileError::Translation(
+            translator::TranslationError::Unsupported(_)
+        ))
+    ));
+
+    // a body that doesn't return what the function declares can't be its behavior
+    assert!(matches!(
+        Compiler::new().compile(
+            "fn buffer(x: Int) { return x; } spice { E1 out0 0 x 0 {1} } fn main(a: Int) -> Int { return a; }"
+        ),
+        Err(CompileError::Translation(
+            translator::TranslationError::WrongPinCount { part, inputs: 1, outputs: 1 }
+        )) if part == "buffer"
+    ));
+}
+
+#[test]
+fn flatten_inlines_function_calls() {
+    let source = "
+        fn add(a: Int, b: Int) -> Int {
+            return a + b;
+        }
+
+        fn same(a: Int) -> Int {
+            return a;
+        }
+
+        fn main(a: Int, b: Int) -> Int {
+            return add(same(a), b) - add(b, 1);
+        }
+    ";
+    let circuit = compile_program(source);
+    assert!(circuit
+        .get_parts()
+        .iter()
+        .any(|part| part.as_circuit().is_some()));
+
+    let flat = circuit.flatten();
+    assert!(flat
+        .get_parts()
+        .iter()
+        .all(|part| part.as_circuit().is_none()));
+    assert_eq!(
+        flat.get_instance_names(),
+        &vec![
+            "main/add#0/Adder#0".to_string(),
+            "main/Constant#0".to_string(),
+            "main/add#1/Adder#0".to_string(),
+            "main/Subtractor#0".to_string(),
+        ]
+    );
+    for inputs in [[3.0, 4.0], [-2.0, 7.0]] {
+        assert_eq!(
+            simulator::simulate(&flat, &inputs).unwrap(),
+            simulator::simulate(&circuit, &inputs).unwrap()
+        );
+    }
+
+    // the paths survive a round trip through json
+    let loaded = serialization::circuit_from_json(&serialization::circuit_to_json(&flat))
+        .expect("failed to load circuit");
+    assert_eq!(loaded.get_instance_names(), flat.get_instance_names());
+    assert!(!spice_translator::circuit_to_spice(&flat).contains(".SUBCKT add"));
+
+    let compiler = Compiler::with_options(CompilerOptions {
+        flatten: true,
+        ..CompilerOptions::default()
+    });
+    let compiled = compiler.compile(source).unwrap();
+    assert_eq!(compiled.get_instance_names(), flat.get_instance_names());
+}
+
+#[test]
+fn function_calls_share_one_definition() {
+    let source = "
+        fn add(a: Int, b: Int) -> Int {
+            return a + b;
+        }
+
+        fn twice(a: Int) -> Int {
+            return add(a, a);
+        }
+
+        fn main(a: Int, b: Int) -> Int {
+            return add(twice(a), twice(b));
+        }
+    ";
+    let circuit = compile_program(source);
+    let instances = circuit
+        .get_parts()
+        .iter()
+        .filter_map(|part| part.as_any().downcast_ref::<translator::CircuitInstance>())
+        .collect::<Vec<_>>();
+    assert_eq!(instances.len(), 3);
+    assert!(std::rc::Rc::ptr_eq(
+        instances[0].get_definition(),
+        instances[1].get_definition()
+    ));
+
+    let spice = spice_translator::circuit_to_spice(&circuit);
+    assert_eq!(spice.matches(".SUBCKT add ").count(), 1);
+    assert_eq!(spice.matches(".SUBCKT twice ").count(), 1);
+
+    // the json has one copy of each function, and loading it shares them again
+    let json = serialization::circuit_to_json(&circuit);
+    assert_eq!(json.matches("\"name\": \"add\"").count(), 1);
+    let loaded = serialization::circuit_from_json(&json).expect("failed to load circuit");
+    assert_eq!(serialization::circuit_to_json(&loaded), json);
+    assert_eq!(
+        simulator::simulate(&loaded, &[2.0, 5.0]).unwrap(),
+        vec![14.0]
+    );
+
+    let missing = json.replace("\"definition\": \"twice\"", "\"definition\": \"thrice\"");
+    assert!(matches!(
+        serialization::circuit_from_json(&missing),
+        Err(serialization::SerializationError::UnknownDefinition(id)) if id == "thrice"
+    ));
+}
+
+#[test]
+fn json_reads_what_older_writers_wrote() {
+    // test_json has what they really wrote, before definitions every call carried its own copy of the function
+    let json = include_str!("../test_json/version_1_function_call.circuit.json");
+    let circuit = serialization::circuit_from_json(json).expect("failed to load circuit");
+    assert_eq!(circuit.get_instance_names(), &vec!["add#0".to_string()]);
+    assert_eq!(
+        simulator::simulate(&circuit, &[3.0, 4.0]).unwrap(),
+        vec![7.0]
+    );
+}
+
+#[test]
+fn verilog_uses_the_int_width() {
+    let compiler = Compiler::with_options(CompilerOptions {
+        int_width: 8,
+        ..CompilerOptions::default()
+    });
+    let circuit = compiler
+        .compile(include_str!("../test_scripts/if_statement_tester.acl"))
+        .unwrap();
+    let verilog = compiler.emit(&circuit, EmitFormat::Verilog).unwrap();
+    assert!(verilog.contains("module main #(parameter WIDTH = 8) (\n"));
+    assert!(verilog.contains("    input signed [WIDTH-1:0] number_1,\n"));
+    assert!(verilog.contains("    assign n2 = number_1 > number_2 ? 1 : 0;\n"));
+    assert!(verilog.contains("    assign n6 = n2 != 0 ? n3 : 0;\n"));
+
+    // declared ranges only ever make it narrower, -3 to 13 fits in 5 bits
+    let circuit = compiler
+        .compile(include_str!("../test_scripts/ranged_int_tester.acl"))
+        .unwrap();
+    let verilog = compiler.emit(&circuit, EmitFormat::Verilog).unwrap();
+    assert!(verilog.contains("module main #(parameter WIDTH = 5) (\n"));
+    let circuit = Compiler::new()
+        .compile("fn main(a: Int<0..100>) -> Int { return a * 1000; }")
+        .unwrap();
+    let verilog = Compiler::new().emit(&circuit, EmitFormat::Verilog).unwrap();
+    assert!(verilog.contains("module main #(parameter WIDTH = 18) (\n"));
+
+    // verilog only has the integers, so a Float constant is refused instead of left undriven
+    let circuit = Compiler::new()
+        .compile(include_str!("../test_scripts/float_tester.acl"))
+        .unwrap();
+    assert!(matches!(
+        Compiler::new().emit(&circuit, EmitFormat::Verilog),
+        Err(CompileError::Verilog(verilog_translator::VerilogError::FractionalConstant {
+            module,
+            value,
+            ..
+        })) if module == "attenuate" && value == 0.25
+    ));
+    // so is a quotient, and a part with no verilog model at all
+    let circuit = Compiler::new()
+        .compile(include_str!("../test_scripts/divider_tester.acl"))
+        .unwrap();
+    assert!(matches!(
+        Compiler::new().emit(&circuit, EmitFormat::Verilog),
+        Err(CompileError::Verilog(
+            verilog_translator::VerilogError::FractionalQuotient { .. }
+        ))
+    ));
+    let circuit = Compiler::new()
+        .compile(include_str!("../test_scripts/spice_block_tester.acl"))
+        .unwrap();
+    assert_eq!(
+        verilog_translator::circuit_to_verilog(&circuit, verilog_translator::DEFAULT_WIDTH),
+        Err(verilog_translator::VerilogError::NoModel(
+            "buffer".to_string()
+        ))
+    );
+    assert!(matches!(
+        Compiler::new().emit(&circuit, EmitFormat::VerilogA),
+        Err(CompileError::VerilogA(
+            verilog_a_translator::VerilogAError::NoModel(part)
+        )) if part == "buffer"
+    ));
+
+    // library parts are written from their behavior
+    let mut parts = PartRegistry::builtin();
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_parts");
+    part_library::load_library(&directory, &mut parts).expect("failed to load parts");
+    let compiler = Compiler::with_options(CompilerOptions {
+        parts,
+        ..CompilerOptions::default()
+    });
+    let circuit = compiler
+        .compile("extern fn Gain(x: Int) -> Int; fn main(a: Int) -> Int { return Gain(a); }")
+        .unwrap();
+    let verilog = compiler.emit(&circuit, EmitFormat::Verilog).unwrap();
+    assert!(verilog.contains("module main #(parameter WIDTH = 32) (\n"));
+    assert!(verilog.contains("    assign n1 = a * 3;\n"));
+}
+
+#[test]
+fn kicad_netlist_uses_the_mapping() {
+    let mut circuit = translator::Circuit::new();
+    circuit.set_name("divider".to_string());
+    let input = circuit.add_program_input(Some("vin".to_string()));
+    let resistor = circuit.add_part(translator::Resistor::new(1000.0));
+    let constant = circuit.add_part(translator::Constant::new(5.0));
+    let adder = circuit.add_part(sub_circuits::Adder {});
+    circuit.connect(input, resistor.input_offset);
+    circuit.connect(resistor.output_offset, adder.input_offset);
+    circuit.connect(constant.output_offset, adder.input_offset + 1);
+    let output = circuit.add_program_output();
+    circuit.connect(adder.output_offset, output);
+
+    let mapping = kicad_translator::KicadMapping::parse(
+        r#"
+        [parts.Adder]
+        symbol = "Amplifier_Operational:LM358"
+        footprint = "Package_SO:SOIC-8_3.9x4.9mm_P1.27mm"
+        pins = ["3", "2", "1"]
+        "#,
+    )
+    .expect("failed to parse mapping");
+    let netlist = kicad_translator::circuit_to_kicad(&circuit, &mapping);
+
+    assert!(netlist.contains("(comp (ref \"R1\")\n      (value \"1000\")\n      (footprint \"Resistor_SMD:R_0603_1608Metric\")"));
+    assert!(netlist.contains("(libsource (lib \"Amplifier_Operational\") (part \"LM358\")"));
+    assert!(netlist.contains("(property (name \"acl_instance\") (value \"divider/Adder#0\"))"));
+    assert!(netlist
+        .contains("(net (code \"1\") (name \"GND\")\n      (node (ref \"V1\") (pin \"2\")))"));
+    assert!(netlist.contains(
+        "(name \"n1\")\n      (node (ref \"R1\") (pin \"2\"))\n      (node (ref \"U1\") (pin \"3\")))"
+    ));
+    assert!(netlist.contains("(name \"out0\")\n      (node (ref \"U1\") (pin \"1\")))"));
+
+    assert!(kicad_translator::KicadMapping::parse("[parts.Adder]\nfootprint = \"x\"").is_err());
+}
+
+#[test]
+fn ngspice_testbench_and_raw_files() {
+    let circuit = compile_program(include_str!("../test_scripts/basic_adder.acl"));
+    let testbench = ngspice::testbench(
+        &circuit,
+        &[1.0, -2.0],
+        ngspice::Analysis::Dc {
+            input: 1,
+            start: 0.0,
+            stop: 1.0,
+            step: 0.5,
+        },
+    )
+    .unwrap();
+    assert!(testbench.ends_with(
+        "Vin0 number_1 0 DC 1\nVin1 number_2 0 DC -2\n.save v(out0)\n.dc Vin1 0 1 0.5\n.END\n"
+    ));
+    assert_eq!(testbench.matches(".END\n").count(), 1);
+    assert!(matches!(
+        ngspice::testbench(&circuit, &[1.0], ngspice::Analysis::Op),
+        Err(ngspice::NgspiceError::WrongInputCount {
+            expected: 2,
+            got: 1
+        })
+    ));
+
+    let raw = "Title: * main generated by ACL\n\
+        Date: Thu Jan  1 00:00:00  2026\n\
+        Plotname: DC transfer characteristic\n\
+        Flags: real\n\
+        No. Variables: 2\n\
+        No. Points: 3\n\
+        Variables:\n\
+        \t0\tv-sweep\tvoltage\n\
+        \t1\tv(out0)\tvoltage\n\
+        Values:\n \
+        0\t0.000000000000000e+00\n\t1.000000000000000e+00\n \
+        1\t5.000000000000000e-01\n\t1.500000000000000e+00\n \
+        2\t1.000000000000000e+00\n\t2.000000000000000e+00\n";
+    let data = ngspice::parse_raw(raw).unwrap();
+    assert_eq!(data.plot, "DC transfer characteristic");
+    assert_eq!(data.get("out0"), Some(vec![1.0, 1.5, 2.0]));
+    assert_eq!(data.get("v-sweep"), Some(vec![0.0, 0.5, 1.0]));
+    assert!(ngspice::parse_raw(&raw.replace("No. Points: 3", "No. Points: 4")).is_err());
+}
+
+// needs a real ngspice, skipped when there isn't one
+#[test]
+fn ngspice_agrees_with_the_simulator() {
+    if ngspice::find_ngspice().is_none() {
+        eprintln!("ngspice not found, skipping");
+        return;
+    }
+    let circuit = compile_program(include_str!("../test_scripts/if_statement_tester.acl"));
+    for inputs in [[3.0, 1.0], [1.0, 3.0], [-2.0, 5.0]] {
+        let expected = simulator::simulate(&circuit, &inputs).unwrap();
+        let actual = ngspice::simulate(&circuit, &inputs).expect("ngspice failed");
+        assert_eq!(expected.len(), actual.len());
+        for (expected, actual) in expected.iter().zip(&actual) {
+            assert!(
+                (expected - actual).abs() < 1e-6,
+                "{:?}: expected {} got {}",
+                inputs,
+                expected,
+                actual
+            );
+        }
+    }
+}
+
+#[test]
+fn mna_solves_resistive_networks() {
+    // a divider, the load pulls the middle down to half the input
+    let mut divider = translator::Circuit::new();
+    let input = divider.add_program_input(Some("vin".to_string()));
+    let top = divider.add_part(translator::Resistor::new(1000.0));
+    let bottom = divider.add_part(translator::Load::new(1000.0));
+    divider.connect(input, top.input_offset);
+    divider.connect(top.output_offset, bottom.input_offset);
+    let output = divider.add_program_output();
+    divider.connect(top.output_offset, output);
+
+    let solution = mna::solve(&divider, &[5.0]).unwrap();
+    assert!((solution.get_outputs()[0] - 2.5).abs() < 1e-6);
+    // the behavioural simulator can't see the load
+    assert_eq!(simulator::simulate(&divider, &[5.0]).unwrap(), vec![5.0]);
+
+    // a non inverting amplifier with a gain of 1 + 3k / 1k
+    let mut amplifier = translator::Circuit::new();
+    let input = amplifier.add_program_input(Some("vin".to_string()));
+    let op_amp = amplifier.add_part(translator::OpAmp::default());
+    let feedback = amplifier.add_part(translator::Resistor::new(3000.0));
+    let ground = amplifier.add_part(translator::Load::new(1000.0));
+    amplifier.connect(input, op_amp.input_offset);
+    amplifier.connect(feedback.output_offset, op_amp.input_offset + 1);
+    amplifier.connect(op_amp.output_offset, feedback.input_offset);
+    amplifier.connect(feedback.output_offset, ground.input_offset);
+    let output = amplifier.add_program_output();
+    amplifier.connect(op_amp.output_offset, output);
+
+    let solution = mna::solve(&amplifier, &[0.5]).unwrap();
+    assert!((solution.get_outputs()[0] - 2.0).abs() < 1e-4);
+    let spice = spice_translator::circuit_to_spice(&amplifier);
+    assert!(spice.contains("E0 n1 0 vin n2 1000000\n"));
+    assert!(spice.contains("R2 n2 0 1000\n"));
+
+    // the primitives survive a round trip through json
+    let json = serialization::circuit_to_json(&amplifier);
+    let loaded = serialization::circuit_from_json(&json).unwrap();
+    assert_eq!(serialization::circuit_to_json(&loaded), json);
+}
+
+#[test]
+fn mna_agrees_with_the_simulator() {
+    for source in [
+        include_str!("../test_scripts/function_tester.acl"),
+        include_str!("../test_scripts/if_statement_tester.acl"),
+        include_str!("../test_scripts/comparison_tester.acl"),
+    ] {
+        let circuit = compile_program(source);
+        for inputs in [[3.0, 1.0, 4.0], [1.0, 3.0, -2.0], [-2.0, 5.0, 0.0]] {
+            let inputs = &inputs[..circuit.get_program_inputs().len()];
+            let expected = simulator::simulate(&circuit, inputs).unwrap();
+            let solution = mna::solve(&circuit, inputs).unwrap();
+            for (expected, actual) in expected.iter().zip(solution.get_outputs()) {
+                assert!(
+                    (expected - actual).abs() < 1e-6,
+                    "{} != {}",
+                    expected,
+                    actual
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn transient_charges_a_capacitor() {
+    // an rc low pass with a time constant of 1us
+    let mut filter = translator::Circuit::new();
+    let input = filter.add_program_input(Some("vin".to_string()));
+    let resistor = filter.add_part(translator::Resistor::new(1000.0));
+    let capacitor = filter.add_part(translator::Capacitor::new(1e-9));
+    filter.connect(input, resistor.input_offset);
+    filter.connect(resistor.output_offset, capacitor.input_offset);
+    let output = filter.add_program_output();
+    filter.connect(resistor.output_offset, output);
+
+    // the input ramps up to 1 over the first step, after that the exact answer is
+    // 1 - tau / dt * (1 - e^(-dt / tau)) * e^(-(t - dt) / tau)
+    let (tau, dt) = (1e-6f64, 100e-9);
+    let ramp = [transient::Stimulus::parse("pwl(0 0 100n 1)").unwrap()];
+    let exact = |t: f64| 1.0 - tau / dt * (1.0 - (-dt / tau).exp()) * (-(t - dt) / tau).exp();
+    for (integration, tolerance) in [
+        (transient::Integration::BackwardEuler, 1e-2),
+        (transient::Integration::Trapezoidal, 1e-3),
+    ] {
+        let options = transient::TransientOptions {
+            step: dt,
+            stop: 5e-6,
+            integration,
+            ..transient::TransientOptions::default()
+        };
+        let waveforms = transient::simulate(&filter, &ramp, &options).unwrap();
+        let out = waveforms.get("out0").unwrap();
+        assert_eq!(out[0], 0.0);
+        // the error of the first few steps dies out with the rest of the transient
+        for (&t, &value) in waveforms.get_times().iter().zip(&out).skip(10) {
+            assert!(
+                (value - exact(t)).abs() < tolerance,
+                "{:?} gave {} at {}",
+                integration,
+                value,
+                t
+            );
+        }
+    }
+
+    // at DC the capacitor is open
+    assert!((mna::solve(&filter, &[1.0]).unwrap().get_outputs()[0] - 1.0).abs() < 1e-6);
+    let spice = spice_translator::circuit_to_spice(&filter);
+    assert!(spice.contains("C1 n1 0 0.000000001\n"), "{}", spice);
+}
+
+#[test]
+fn transient_shows_if_gate_glitches() {
+    let circuit = compile_program(include_str!("../test_scripts/if_statement_tester.acl"));
+    // number_1 goes from 0 to 10 and back so the comparison flips twice
+    let stimuli = [
+        transient::Stimulus::parse("pulse(0 10 20n 1n 1n 100n)").unwrap(),
+        transient::Stimulus::parse("dc 5").unwrap(),
+    ];
+    let options = transient::TransientOptions {
+        step: 1e-9,
+        stop: 200e-9,
+        time_constant: 2e-9,
+        probe_nets: true,
+        ..transient::TransientOptions::default()
+    };
+    let waveforms = transient::simulate(&circuit, &stimuli, &options).unwrap();
+    let out = waveforms.get("out0").unwrap();
+    // settled it is 0 + 5, then 10 - 5, then 0 + 5 again
+    assert!((out[19] - 5.0).abs() < 1e-6);
+    assert!((out[110] - 5.0).abs() < 1e-6);
+    assert!((out[199] - 5.0).abs() < 1e-6);
+    // the comparator and the two branches don't switch together so the output wanders off on the way
+    assert!(out[20..40].iter().any(|value| (value - 5.0).abs() > 0.5));
+    assert!(waveforms
+        .get_names()
+        .iter()
+        .any(|name| name.starts_with("main/Comparator#0/")));
+
+    // without a time constant everything switches in the same step and there is no glitch
+    let ideal = transient::TransientOptions {
+        time_constant: 0.0,
+        ..options
+    };
+    let waveforms = transient::simulate(&circuit, &stimuli, &ideal).unwrap();
+    assert!(waveforms
+        .get("out0")
+        .unwrap()
+        .iter()
+        .all(|value| (value - 5.0).abs() < 1e-6));
+
+    let csv = waveforms.to_csv();
+    assert!(csv.starts_with("time,number_1,number_2,out0,"));
+    assert_eq!(csv.lines().count(), 202);
+    let vcd = waveforms.to_vcd("main");
+    assert!(vcd.contains("$var real 64 ! number_1 $end\n"));
+    assert!(vcd.contains("#22000000\nr10 !\n"), "{}", vcd);
+}
+
+#[test]
+fn stimuli_parse_like_spice_sources() {
+    assert_eq!(transient::parse_quantity("4.7k"), Some(4700.0));
+    assert_eq!(transient::parse_quantity("10ns"), Some(10e-9));
+    assert_eq!(transient::parse_quantity("2meg"), Some(2e6));
+    assert_eq!(transient::parse_quantity("-1e-3"), Some(-1e-3));
+    assert_eq!(transient::parse_quantity("volts"), None);
+
+    let pulse = transient::Stimulus::parse("PULSE(0, 1, 1n, 1n, 1n, 2n, 10n)").unwrap();
+    assert_eq!(pulse.value(0.0), 0.0);
+    assert!((pulse.value(1.5e-9) - 0.5).abs() < 1e-9);
+    assert_eq!(pulse.value(3e-9), 1.0);
+    assert!((pulse.value(4.5e-9) - 0.5).abs() < 1e-9);
+    assert_eq!(pulse.value(13e-9), 1.0);
+
+    let pwl = transient::Stimulus::parse("pwl(0 0 1u 5 2u 5)").unwrap();
+    assert_eq!(pwl.value(0.5e-6), 2.5);
+    assert_eq!(pwl.value(3e-6), 5.0);
+    assert!(transient::Stimulus::parse("pwl(1u 0 0 1)").is_err());
+    assert!(transient::Stimulus::parse("sine(0 1 1k)").is_err());
+}
+
+#[test]
+fn failed_asserts_say_what_they_got() {
+    let program = parse_program(
+        "fn main(a: Int, b: Int) -> Int { return a * b; }
+        fn square(x: Int) -> Int { return main(x, x); }
+        test multiplies { assert main(2, 3) == 6; assert square(3) == 9; }
+        test adds { let x = main(2, 3); assert x == 5; }",
+    );
+    // tests aren't hardware
+    let circuit = translate_program(&program);
+    assert_eq!(circuit.get_program_inputs().len(), 2);
+
+    let results = testing::run_tests(&program, &CompilerOptions::default()).unwrap();
+    let summary = results
+        .iter()
+        .map(|result| (result.get_name(), result.get_backend(), result.passed()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            ("multiplies", testing::Backend::Interpreter, true),
+            ("multiplies", testing::Backend::Circuit, true),
+            ("adds", testing::Backend::Interpreter, false),
+            ("adds", testing::Backend::Circuit, false),
+        ]
+    );
+    match results[3].get_result() {
+        Err(testing::TestFailure::Interpreter(interpreter::InterpreterError::AssertionFailed(
+            message,
+        ))) => assert_eq!(message, "x == 5 (left is 6, right is 5)"),
+        other => panic!("{:?}", other),
+    }
+
+    // a circuit that can't be simulated says why instead of looking like a missing return value
+    let program = parse_program(
+        "fn divider(x: Int) -> Int spice { R1 x out0 1k }
+        fn main(a: Int) -> Int { return divider(a); }
+        test divides { assert main(2) == 1; }",
+    );
+    let results = testing::run_tests(&program, &CompilerOptions::default()).unwrap();
+    match results[1].get_result() {
+        Err(testing::TestFailure::Simulation(simulator::SimulationError::NoBehavior(part))) => {
+            assert_eq!(part, "divider")
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // and a name the function never defined isn't quietly given 0
+    let program = parse_program(
+        "fn offset(x: Int) -> Int { return x + y; }
+        test offsets { assert offset(1) == 1; }",
+    );
+    let results = testing::run_tests(&program, &CompilerOptions::default()).unwrap();
+    assert!(matches!(
+        results[1].get_result(),
+        Err(testing::TestFailure::Simulation(
+            simulator::SimulationError::UnknownInput(name)
+        )) if name == "y"
+    ));
+}
+
+#[test]
+fn monte_carlo_spreads_the_outputs() {
+    let mut divider = translator::Circuit::new();
+    let input = divider.add_program_input(Some("vin".to_string()));
+    let top = divider.add_part(translator::Resistor::new(1000.0));
+    let bottom = divider.add_part(translator::Load::new(1000.0));
+    divider.connect(input, top.input_offset);
+    divider.connect(top.output_offset, bottom.input_offset);
+    let output = divider.add_program_output();
+    divider.connect(top.output_offset, output);
+
+    let options = monte_carlo::MonteCarloOptions {
+        runs: 200,
+        seed: 3,
+        variations: vec![
+            monte_carlo::Variation::parse("Resistor.resistance=1%").unwrap(),
+            monte_carlo::Variation::parse("Load.resistance=1%").unwrap(),
+        ],
+        engine: monte_carlo::Engine::Mna,
+    };
+    let outputs = monte_carlo::run(&divider, &[5.0], &options).unwrap();
+    let out = &outputs[0];
+    assert!((out.get_nominal() - 2.5).abs() < 1e-6);
+    assert_eq!(out.get_samples().len(), 200);
+    // the worst case is one resistor 1% high and the other 1% low
+    assert!(out.min() >= 5.0 * 990.0 / 2000.0 - 1e-6);
+    assert!(out.max() <= 5.0 * 1010.0 / 2000.0 + 1e-6);
+    assert!(out.standard_deviation() > 1e-3);
+    assert_eq!(out.yield_within(0.5), 1.0);
+    // the same seed draws the same parts
+    let again = monte_carlo::run(&divider, &[5.0], &options).unwrap();
+    assert_eq!(again[0].get_samples(), out.get_samples());
+
+    // the behavioural simulator passes resistors straight through so it sees nothing
+    let behavioral = monte_carlo::MonteCarloOptions {
+        engine: monte_carlo::Engine::Behavioral,
+        ..options.clone()
+    };
+    let outputs = monte_carlo::run(&divider, &[5.0], &behavioral).unwrap();
+    assert_eq!(outputs[0].standard_deviation(), 0.0);
+
+    let typo = monte_carlo::MonteCarloOptions {
+        variations: vec![monte_carlo::Variation::parse("Resistor.resistence=1%").unwrap()],
+        ..options.clone()
+    };
+    assert!(matches!(
+        monte_carlo::run(&divider, &[5.0], &typo),
+        Err(monte_carlo::MonteCarloError::UnknownParameter { .. })
+    ));
+    let none = monte_carlo::MonteCarloOptions { runs: 0, ..options };
+    assert!(matches!(
+        monte_carlo::run(&divider, &[5.0], &none),
+        Err(monte_carlo::MonteCarloError::NoRuns)
+    ));
+}
+
+#[test]
+fn op_amp_offsets_vary() {
+    // a non inverting amplifier with a gain of 4, the offset is amplified along with the input
+    let mut amplifier = translator::Circuit::new();
+    let input = amplifier.add_program_input(Some("vin".to_string()));
+    let op_amp = amplifier.add_part(translator::OpAmp::with_offset(1e6, 1e-3));
+    let feedback = amplifier.add_part(translator::Resistor::new(3000.0));
+    let ground = amplifier.add_part(translator::Load::new(1000.0));
+    amplifier.connect(input, op_amp.input_offset);
+    amplifier.connect(feedback.output_offset, op_amp.input_offset + 1);
+    amplifier.connect(op_amp.output_offset, feedback.input_offset);
+    amplifier.connect(feedback.output_offset, ground.input_offset);
+    let output = amplifier.add_program_output();
+    amplifier.connect(op_amp.output_offset, output);
+
+    let solution = mna::solve(&amplifier, &[0.5]).unwrap();
+    assert!((solution.get_outputs()[0] - 2.004).abs() < 1e-4);
+    let spice = spice_translator::circuit_to_spice(&amplifier);
+    assert!(
+        spice.contains("V0os os0 vin 0.001\nE0 n1 0 os0 n2 1000000\n"),
+        "{}",
+        spice
+    );
+    let json = serialization::circuit_to_json(&amplifier);
+    let loaded = serialization::circuit_from_json(&json).unwrap();
+    assert_eq!(serialization::circuit_to_json(&loaded), json);
+
+    let variation = monte_carlo::Variation::parse("OpAmp.offset=gauss:2m").unwrap();
+    assert_eq!(variation.distribution, monte_carlo::Distribution::Gaussian);
+    assert!(!variation.relative);
+    assert!((variation.spread - 2e-3).abs() < 1e-12);
+    let options = monte_carlo::MonteCarloOptions {
+        runs: 2000,
+        variations: vec![variation],
+        ..monte_carlo::MonteCarloOptions::default()
+    };
+    let outputs = monte_carlo::run(&amplifier, &[0.5], &options).unwrap();
+    // an offset with a standard deviation of 2mV comes out 4 times bigger
+    let deviation = outputs[0].standard_deviation();
+    assert!((deviation - 8e-3).abs() < 1e-3, "{}", deviation);
+    assert!((outputs[0].mean() - 2.004).abs() < 1e-3);
+}
+
+#[test]
+fn ranges_flag_what_leaves_the_supply() {
+    let circuit = Compiler::new()
+        .compile("fn main (a: Int, b: Int) -> Int { let sum = a + b; return sum * 2; }")
+        .unwrap();
+    let report = range_analysis::analyze(
+        &circuit,
+        &[
+            range_analysis::Interval::new(0.0, 5.0),
+            range_analysis::Interval::new(-1.0, 1.0),
+        ],
+        range_analysis::DEFAULT_SUPPLY,
+    )
+    .unwrap();
+    assert_eq!(
+        report.get("a"),
+        Some(range_analysis::Interval::new(0.0, 5.0))
+    );
+    assert_eq!(
+        report.get("out0"),
+        Some(range_analysis::Interval::new(-2.0, 12.0))
+    );
+    assert!(report.get_findings().is_empty());
+
+    // the sum alone fits but doubled it can reach 16
+    let report = range_analysis::analyze(
+        &circuit,
+        &[
+            range_analysis::Interval::new(0.0, 5.0),
+            range_analysis::Interval::new(-1.0, 3.0),
+        ],
+        range_analysis::DEFAULT_SUPPLY,
+    )
+    .unwrap();
+    let exceeding = report
+        .get_findings()
+        .iter()
+        .map(|finding| match finding {
+            range_analysis::Finding::ExceedsSupply { range, .. } => *range,
+            other => panic!("unexpected {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(exceeding, vec![range_analysis::Interval::new(-2.0, 16.0)]);
+    assert_eq!(
+        report.scale_to_fit(range_analysis::DEFAULT_SUPPLY),
+        Some(0.75)
+    );
+
+    // scaling can't make the -2 fit on a supply that doesn't go below 0
+    assert_eq!(
+        report.scale_to_fit(range_analysis::Interval::new(0.0, 24.0)),
+        None
+    );
+}
+
+#[test]
+fn ranges_flag_dividing_by_something_that_can_be_zero() {
+    let circuit = Compiler::new()
+        .compile("fn main (a: Int, b: Int) -> Float { return a / b; }")
+        .unwrap();
+    let safe = range_analysis::analyze(
+        &circuit,
+        &[
+            range_analysis::Interval::new(-4.0, 4.0),
+            range_analysis::Interval::new(1.0, 2.0),
+        ],
+        range_analysis::DEFAULT_SUPPLY,
+    )
+    .unwrap();
+    assert_eq!(
+        safe.get("out0"),
+        Some(range_analysis::Interval::new(-4.0, 4.0))
+    );
+    assert!(safe.get_findings().is_empty());
+
+    let report = range_analysis::analyze(
+        &circuit,
+        &[
+            range_analysis::Interval::new(-4.0, 4.0),
+            range_analysis::Interval::new(-1.0, 2.0),
+        ],
+        range_analysis::DEFAULT_SUPPLY,
+    )
+    .unwrap();
+    assert_eq!(
+        report.get("out0"),
+        Some(range_analysis::Interval::UNBOUNDED)
+    );
+    match report.get_findings().as_slice() {
+        [range_analysis::Finding::Unbounded { part, inputs }] => {
+            assert!(part.contains("Divider"), "{}", part);
+            assert_eq!(inputs[1], range_analysis::Interval::new(-1.0, 2.0));
+        }
+        other => panic!("expected one unbounded divider, got {:?}", other),
+    }
+}
+
+#[test]
+fn ranged_ints_are_type_checked() {
+    let type_error = |source: &str| match Compiler::new().compile(source) {
+        Err(CompileError::Type(error)) => error,
+        other => panic!("expected a type error, got {:?}", other.map(|_| ())),
+    };
+    assert_eq!(
+        type_error(
+            "fn f(x: Int<0..10>) -> Int { return x; } fn main(a: Int<0..20>) -> Int { return f(a); }"
+        ),
+        type_checker::TypeError::ArgumentOutOfRange {
+            function: "main".to_string(),
+            call: "f".to_string(),
+            argument: "x".to_string(),
+            range: range_analysis::Interval::new(0.0, 20.0),
+            declared: Type::RangedInt { low: 0, high: 10 },
+        }
+    );
+    assert!(matches!(
+        type_error("fn main(a: Int<-3..3>) -> Int<0..9> { return a * a; }"),
+        type_checker::TypeError::ReturnOutOfRange { range, .. }
+            if range == range_analysis::Interval::new(-9.0, 9.0)
+    ));
+    assert!(matches!(
+        type_error("fn main(a: Int<5..1>) -> Int { return a; }"),
+        type_checker::TypeError::EmptyRange { .. }
+    ));
+    // a bound that doesn't fit in an Int is pointed at like any other parse error
+    assert!(matches!(
+        Compiler::new().compile("fn main(a: Int<0..99999999999>) -> Int { return a; }"),
+        Err(CompileError::Parse(_))
+    ));
+    assert!(matches!(
+        type_error("fn main(a: Int) -> Int { return a + \"volts\"; }"),
+        type_checker::TypeError::Mismatch { .. }
+    ));
+
+    // the values in between have to fit in the verilog width
+    let source = "fn main(a: Int<0..100>) -> Int { let big = a * 10; return big - 990; }";
+    let narrow = Compiler::with_options(CompilerOptions {
+        int_width: 8,
+        ..CompilerOptions::default()
+    });
+    assert!(matches!(
+        narrow.compile(source),
+        Err(CompileError::Type(type_checker::TypeError::Overflow { .. }))
+    ));
+    let circuit = Compiler::new().compile(source).unwrap();
+    assert_eq!(
+        circuit.get_program_inputs()[0].get_range(),
+        Some(range_analysis::Interval::new(0.0, 100.0))
+    );
+
+    // a plain Int can be anything, so the values made from it can't overflow but don't fit a range either
+    Compiler::new()
+        .compile("fn main(a: Int) -> Int { return a * 1000000; }")
+        .unwrap();
+    assert!(matches!(
+        type_error("fn main(a: Int) -> Int<0..1> { return a; }"),
+        type_checker::TypeError::ReturnOutOfRange { .. }
+    ));
+}
+
+#[test]
+fn floats_are_exact_constants() {
+    let circuit = Compiler::new()
+        .compile("fn main(v: Float) -> Float { return v * 1.5e-3; }")
+        .unwrap();
+    let constant = circuit
+        .get_parts()
+        .iter()
+        .find(|part| part.get_name() == "Constant")
+        .unwrap();
+    assert_eq!(constant.get_parameters()["value"], 1.5e-3);
+    assert_eq!(simulator::simulate(&circuit, &[2.0]).unwrap(), vec![3e-3]);
+
+    // an Int can go where a Float is expected but not the other way around
+    Compiler::new()
+        .compile("fn half(x: Float) -> Float { return x / 2; } fn main(a: Int) -> Float { return half(a); }")
+        .unwrap();
+    assert!(matches!(
+        Compiler::new().compile("fn main(v: Float) -> Int { return v + 1; }"),
+        Err(CompileError::Type(type_checker::TypeError::Mismatch {
+            expected: Type::Int,
+            found: Type::Float,
+            ..
+        }))
+    ));
+
+    // division is exact like the divider, even between Ints, so it can't be returned as an Int
+    let program = parse_program(
+        "fn main(a: Int, b: Float) -> Float { let q = a / 2; let r = b / 4; return q + r; }",
+    );
+    let interpreter = interpreter::Interpreter::new(&program).unwrap();
+    assert_eq!(
+        interpreter
+            .run_main(vec![Value::Int(5), Value::Int(1)])
+            .unwrap(),
+        Some(Value::Float(2.75))
+    );
+    assert!(matches!(
+        Compiler::new().compile("fn main(a: Int, b: Int) -> Int { return a / b; }"),
+        Err(CompileError::Type(type_checker::TypeError::Mismatch {
+            expected: Type::Int,
+            found: Type::Float,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn logical_operators_become_gates() {
+    // && binds looser than the comparisons and || looser still, arithmetic is left to right
+    let program = parse_program("fn main(a: Int, b: Int, c: Int) -> Int { return a - b - c * 2; }");
+    let interpreter = interpreter::Interpreter::new(&program).unwrap();
+    assert_eq!(
+        interpreter
+            .run_main(vec![Value::Int(10), Value::Int(3), Value::Int(2)])
+            .unwrap(),
+        Some(Value::Int(3))
+    );
+
+    let circuit = Compiler::new()
+        .compile("fn main(a: Int, b: Int, c: Bool) -> Int { if a > 0 && b < 10 || !c { return 1; } return 0; }")
+        .unwrap();
+    let names = circuit
+        .get_parts()
+        .iter()
+        .map(|part| part.get_name())
+        .collect::<Vec<_>>();
+    for gate in ["And", "Or", "Not", "IfGate"] {
+        assert!(
+            names.iter().any(|name| name == gate),
+            "no {} in {:?}",
+            gate,
+            names
+        );
+    }
+    for (inputs, expected) in [
+        ([1.0, 5.0, 1.0], 1.0),
+        ([0.0, 5.0, 1.0], 0.0),
+        ([1.0, 10.0, 1.0], 0.0),
+        ([0.0, 10.0, 0.0], 1.0),
+    ] {
+        assert_eq!(
+            simulator::simulate(&circuit, &inputs).unwrap(),
+            vec![expected],
+            "{:?}",
+            inputs
+        );
+    }
+
+    // a Bool argument or variable already is the 0/1 level, it drives the gates without a comparator
+    let circuit = Compiler::new()
+        .compile("fn main(a: Int, c: Bool) -> Int { let d = !c; if c { return a; } if d { return 1; } return 0; }")
+        .unwrap();
+    let names = circuit
+        .get_parts()
+        .iter()
+        .map(|part| part.get_name())
+        .collect::<Vec<_>>();
+    assert!(
+        !names.iter().any(|name| name == "Comparator"),
+        "{:?}",
+        names
+    );
+    assert_eq!(
+        simulator::simulate(&circuit, &[7.0, 1.0]).unwrap(),
+        vec![7.0]
+    );
+    assert_eq!(
+        simulator::simulate(&circuit, &[7.0, 0.0]).unwrap(),
+        vec![1.0]
+    );
+
+    // conditions have to be a Bool, but a Bool can be used as the Int it is in the circuit
+    assert!(matches!(
+        Compiler::new().compile("fn main(a: Int) -> Int { if a { return 1; } return 0; }"),
+        Err(CompileError::Type(type_checker::TypeError::Mismatch {
+            expected: Type::Bool,
+            found: Type::Int,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        Compiler::new().compile("fn main(a: Int) -> Bool { return a && true; }"),
+        Err(CompileError::Type(type_checker::TypeError::Mismatch { .. }))
+    ));
+    Compiler::new()
+        .compile("fn main(a: Int) -> Int<0..2> { return (a > 0) + (a > 5); }")
+        .unwrap();
 }
@@ -0,0 +1,426 @@
+//! time domain simulation of the flattened circuit with the same stamps as the DC solver
+//!
+//! capacitors are replaced by their companion models and every behavioural output follows what its part
+//! evaluates to with a first order lag, so a comparator or ```IfGate``` takes a moment to switch and the
+//! glitches and settling that the ideal evaluator hides show up in the waveforms
+
+use std::fmt::Write;
+
+use crate::{
+    mna::{MnaError, Network, Stamps},
+    translator::Circuit,
+};
+
+/// how the derivatives are discretised
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integration {
+    /// first order and very damped, never rings
+    #[default]
+    BackwardEuler,
+    /// second order, more accurate for the same step but can ring on sharp edges
+    Trapezoidal,
+}
+
+/// what an input does over time, same shapes as spice's independent sources
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stimulus {
+    Dc(f64),
+    Pulse {
+        low: f64,
+        high: f64,
+        delay: f64,
+        rise: f64,
+        fall: f64,
+        width: f64,
+        /// 0 means it only happens once
+        period: f64,
+    },
+    /// straight lines between (time, value) points, flat before the first and after the last
+    Pwl(Vec<(f64, f64)>),
+}
+
+impl Stimulus {
+    pub fn value(&self, time: f64) -> f64 {
+        match self {
+            Stimulus::Dc(value) => *value,
+            Stimulus::Pulse {
+                low,
+                high,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            } => {
+                let mut time = time - delay;
+                if time < 0.0 {
+                    return *low;
+                }
+                if *period > 0.0 {
+                    time %= period;
+                }
+                if time < *rise {
+                    low + (high - low) * time / rise
+                } else if time < rise + width {
+                    *high
+                } else if time < rise + width + fall {
+                    high + (low - high) * (time - rise - width) / fall
+                } else {
+                    *low
+                }
+            }
+            Stimulus::Pwl(points) => {
+                let Some(&(_, first)) = points.first() else {
+                    return 0.0;
+                };
+                let mut value = first;
+                for pair in points.windows(2) {
+                    let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+                    if time >= t1 {
+                        value = v1;
+                    } else if time > t0 {
+                        return v0 + (v1 - v0) * (time - t0) / (t1 - t0);
+                    }
+                }
+                value
+            }
+        }
+    }
+
+    /// reads ```1.5```, ```pulse(low high delay rise fall width period)``` or ```pwl(t0 v0 t1 v1 ...)```,
+    /// numbers can have spice suffixes and commas work as separators too
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let lower = text.to_lowercase();
+        let arguments = |prefix: &str| -> Result<Option<Vec<f64>>, String> {
+            let Some(rest) = lower.strip_prefix(prefix) else {
+                return Ok(None);
+            };
+            let inner = rest
+                .trim()
+                .strip_prefix('(')
+                .and_then(|rest| rest.strip_suffix(')'))
+                .ok_or_else(|| format!("expected {}(...)", prefix))?;
+            inner
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty())
+                .map(|word| parse_quantity(word).ok_or_else(|| format!("bad number {}", word)))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some)
+        };
+
+        if let Some(values) = arguments("pulse")? {
+            if values.len() < 2 || values.len() > 7 {
+                return Err("pulse takes between 2 and 7 values".to_string());
+            }
+            let get = |i: usize| values.get(i).copied().unwrap_or(0.0);
+            return Ok(Stimulus::Pulse {
+                low: get(0),
+                high: get(1),
+                delay: get(2),
+                rise: get(3),
+                fall: get(4),
+                width: values.get(5).copied().unwrap_or(f64::INFINITY),
+                period: get(6),
+            });
+        }
+        if let Some(values) = arguments("pwl")? {
+            if values.is_empty() || values.len() % 2 != 0 {
+                return Err("pwl takes pairs of time and value".to_string());
+            }
+            let points = values
+                .chunks(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect::<Vec<_>>();
+            if points.windows(2).any(|pair| pair[1].0 <= pair[0].0) {
+                return Err("pwl times have to go up".to_string());
+            }
+            return Ok(Stimulus::Pwl(points));
+        }
+        let value = lower.strip_prefix("dc").unwrap_or(&lower).trim();
+        parse_quantity(value)
+            .map(Stimulus::Dc)
+            .ok_or_else(|| format!("bad stimulus {}", text))
+    }
+}
+
+/// a number with an optional spice scale suffix like ```10n``` or ```4.7k```, anything after the suffix is ignored
+/// like spice does so ```1ns``` is fine
+pub fn parse_quantity(text: &str) -> Option<f64> {
+    let text = text.trim().to_lowercase();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == 'e'))
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let scale = if suffix.starts_with("meg") {
+        1e6
+    } else {
+        match suffix.chars().next() {
+            None => 1.0,
+            Some('f') => 1e-15,
+            Some('p') => 1e-12,
+            Some('n') => 1e-9,
+            Some('u') => 1e-6,
+            Some('m') => 1e-3,
+            Some('k') => 1e3,
+            Some('g') => 1e9,
+            Some('t') => 1e12,
+            Some(c) if c.is_alphabetic() => 1.0,
+            Some(_) => return None,
+        }
+    };
+    Some(number * scale)
+}
+
+#[derive(Debug, Clone)]
+pub struct TransientOptions {
+    /// the fixed time step in seconds
+    pub step: f64,
+    pub stop: f64,
+    pub integration: Integration,
+    /// how long a behavioural output takes to follow its part, in seconds, 0 switches instantly
+    pub time_constant: f64,
+    /// record every net of the flattened circuit and not just the program inputs and outputs
+    pub probe_nets: bool,
+}
+
+impl Default for TransientOptions {
+    fn default() -> Self {
+        TransientOptions {
+            step: 1e-9,
+            stop: 1e-6,
+            integration: Integration::default(),
+            time_constant: 0.0,
+            probe_nets: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TransientError {
+    WrongStimulusCount {
+        expected: usize,
+        got: usize,
+    },
+    /// the step has to be positive and no bigger than the stop time
+    BadStep,
+    Mna(MnaError),
+}
+
+impl From<MnaError> for TransientError {
+    fn from(error: MnaError) -> Self {
+        TransientError::Mna(error)
+    }
+}
+
+/// a value for every signal at every time point
+#[derive(Debug, Clone)]
+pub struct Waveforms {
+    names: Vec<String>,
+    times: Vec<f64>,
+    /// one row per time point, in the order of ```names```
+    values: Vec<Vec<f64>>,
+}
+
+impl Waveforms {
+    pub fn get_names(&self) -> &Vec<String> {
+        &self.names
+    }
+
+    pub fn get_times(&self) -> &Vec<f64> {
+        &self.times
+    }
+
+    /// every value of one signal
+    pub fn get(&self, name: &str) -> Option<Vec<f64>> {
+        let column = self.names.iter().position(|n| n == name)?;
+        Some(self.values.iter().map(|row| row[column]).collect())
+    }
+
+    /// a header of ```time``` and the signal names then one line per time point
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time");
+        for name in &self.names {
+            csv.push(',');
+            csv.push_str(name);
+        }
+        csv.push('\n');
+        for (time, row) in self.times.iter().zip(&self.values) {
+            write!(csv, "{}", time).unwrap();
+            for value in row {
+                write!(csv, ",{}", value).unwrap();
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// a value change dump with a real variable per signal, only the changes are written
+    pub fn to_vcd(&self, module: &str) -> String {
+        let mut vcd = String::new();
+        vcd.push_str("$timescale 1fs $end\n");
+        writeln!(vcd, "$scope module {} $end", module).unwrap();
+        let codes = (0..self.names.len()).map(vcd_code).collect::<Vec<_>>();
+        for (name, code) in self.names.iter().zip(&codes) {
+            writeln!(vcd, "$var real 64 {} {} $end", code, name).unwrap();
+        }
+        vcd.push_str("$upscope $end\n$enddefinitions $end\n");
+
+        let mut last: Option<&Vec<f64>> = None;
+        for (time, row) in self.times.iter().zip(&self.values) {
+            let changes = row
+                .iter()
+                .zip(&codes)
+                .enumerate()
+                .filter(|(i, (value, _))| last.is_none_or(|last| last[*i] != **value))
+                .map(|(_, change)| change)
+                .collect::<Vec<_>>();
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(vcd, "#{}", (time * 1e15).round() as u64).unwrap();
+            for (value, code) in changes {
+                writeln!(vcd, "r{} {}", value, code).unwrap();
+            }
+            last = Some(row);
+        }
+        vcd
+    }
+}
+
+/// the short identifiers vcd uses for variables, printable ascii from ```!``` to ```~```
+fn vcd_code(mut index: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
+
+/// runs from 0 to ```options.stop```, starting from the DC operating point with every stimulus at its value at 0
+pub fn simulate(
+    circuit: &Circuit,
+    stimuli: &[Stimulus],
+    options: &TransientOptions,
+) -> Result<Waveforms, TransientError> {
+    let stamps = Stamps::new(circuit.flatten())?;
+    let program_inputs = stamps.circuit.get_program_inputs();
+    if stimuli.len() != program_inputs.len() {
+        return Err(TransientError::WrongStimulusCount {
+            expected: program_inputs.len(),
+            got: stimuli.len(),
+        });
+    }
+    if !(options.step > 0.0 && options.step <= options.stop) {
+        return Err(TransientError::BadStep);
+    }
+
+    // the signals are the program inputs, the program outputs then optionally every other net
+    let mut names = program_inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| input.get_name().map_or(format!("in{}", i), str::to_string))
+        .collect::<Vec<_>>();
+    names.extend((0..stamps.circuit.get_program_outputs().len()).map(|i| format!("out{}", i)));
+    let mut probes = vec![];
+    if options.probe_nets {
+        let circuit = &stamps.circuit;
+        for ((part, offsets), instance) in circuit
+            .get_parts()
+            .iter()
+            .zip(circuit.get_part_offsets())
+            .zip(circuit.get_instance_names())
+        {
+            for (i, pin) in part.get_output_pins().iter().enumerate() {
+                names.push(format!("{}/{}", instance, pin.get_name()));
+                probes.push(offsets.output_offset + i);
+            }
+        }
+    }
+    let record = |time: f64, inputs: &[f64], voltages: &[f64]| {
+        let mut row = inputs.to_vec();
+        row.extend(stamps.outputs(voltages));
+        row.extend(probes.iter().map(|&node| voltages[node]));
+        (time, row)
+    };
+
+    // capacitors are open at DC
+    let dc = Network::new(stamps.node_count, &stamps.conductances, &stamps.sources)?;
+    let inputs = stimuli.iter().map(|s| s.value(0.0)).collect::<Vec<_>>();
+    let mut values = vec![0.0; stamps.sources.len()];
+    let mut voltages = stamps.settle(&dc, &inputs, &[], &mut values, |_, value| value)?;
+    let mut points = vec![record(0.0, &inputs, &voltages)];
+
+    // every capacitor becomes a conductance with a current source carrying its history
+    let dt = options.step;
+    let companion = match options.integration {
+        Integration::BackwardEuler => 1.0 / dt,
+        Integration::Trapezoidal => 2.0 / dt,
+    };
+    let mut conductances = stamps.conductances.clone();
+    let capacitors = stamps
+        .capacitors
+        .iter()
+        .filter_map(|&(node, capacitance)| Some((node?, capacitance * companion)))
+        .collect::<Vec<_>>();
+    conductances.extend(capacitors.iter().map(|&(node, g)| (Some(node), None, g)));
+    let network = Network::new(stamps.node_count, &conductances, &stamps.sources)?;
+    let mut currents = vec![0.0; capacitors.len()];
+
+    // what the behavioural parts evaluated to last step, the trapezoidal lag averages it with this step's
+    let mut targets = values.clone();
+    let steps = (options.stop / dt).round() as usize;
+    for n in 1..=steps {
+        // on the femtosecond grid of the vcd so the times print cleanly
+        let time = (n as f64 * dt * 1e15).round() / 1e15;
+        let inputs = stimuli.iter().map(|s| s.value(time)).collect::<Vec<_>>();
+        let mut injections = vec![0.0; stamps.node_count];
+        for (&(node, g), current) in capacitors.iter().zip(&currents) {
+            injections[node] += match options.integration {
+                Integration::BackwardEuler => g * voltages[node],
+                Integration::Trapezoidal => g * voltages[node] + current,
+            };
+        }
+
+        let previous = values.clone();
+        let mut new_targets = targets.clone();
+        let tau = options.time_constant;
+        let next = stamps.settle(&network, &inputs, &injections, &mut values, |i, target| {
+            new_targets[i] = target;
+            if tau <= 0.0 {
+                return target;
+            }
+            match options.integration {
+                Integration::BackwardEuler => {
+                    let b = dt / tau;
+                    (previous[i] + b * target) / (1.0 + b)
+                }
+                Integration::Trapezoidal => {
+                    let a = dt / (2.0 * tau);
+                    (previous[i] * (1.0 - a) + a * (target + targets[i])) / (1.0 + a)
+                }
+            }
+        })?;
+        targets = new_targets;
+
+        if options.integration == Integration::Trapezoidal {
+            for (&(node, g), current) in capacitors.iter().zip(&mut currents) {
+                *current = g * (next[node] - voltages[node]) - *current;
+            }
+        }
+        voltages = next;
+        points.push(record(time, &inputs, &voltages));
+    }
+
+    let (times, values) = points.into_iter().unzip();
+    Ok(Waveforms {
+        names,
+        times,
+        values,
+    })
+}
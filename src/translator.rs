@@ -1,18 +1,50 @@
 //! turns functions into circuits
 
-use std::{collections::HashMap, fmt::Debug, hash::Hash, vec};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{self, Debug},
+    rc::Rc,
+    vec,
+};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    sub_circuits::{Adder, Divider, Multiplier, Subtractor},
-    ASTNode, Expression, FunctionDefinition, IfStatement, Operator,
+    mna::Element, part_registry::PartRegistry, range_analysis::Interval, simulator::simulate,
+    ASTNode, Assignment, Expression, ExternFunction, FunctionDefinition, IfStatement, Operator,
+    Type, Value, ValueBlock, MAX_LOOP_ITERATIONS,
 };
 
-#[derive(Debug, Clone)]
-struct CircuitInput {
-    index: usize,
-    name: Option<String>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitInput {
+    pub(crate) index: usize,
+    pub(crate) name: Option<String>,
+    /// the range from an ```Int<low..high>``` argument
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) range: Option<Interval>,
+}
+
+impl CircuitInput {
+    /// where the input sits in the producing index space
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// the values the input was declared to take, None when it is a plain ```Int```
+    pub fn get_range(&self) -> Option<Interval> {
+        self.range
+    }
 }
 
+/// a graph of parts
+/// there are two index spaces, one for everything that consumes a value (part inputs and program outputs)
+/// and one for everything that produces a value (part outputs and program inputs)
+/// connections always go from the producing index to the consuming index
 #[derive(Debug, Clone)]
 pub struct Circuit {
     parts: Vec<Box<dyn PartInternal>>,
@@ -24,13 +56,30 @@ pub struct Circuit {
     next_input_index: usize,
     next_output_index: usize,
     name: Option<String>,
+    // one per part, like ```Adder#0```, or a path like ```main/add#0/Adder#0``` once flattened
+    instance_names: Vec<String>,
 }
 
-// an object safe version of Part
-trait PartInternal {
+/// an object safe version of ```Part```, every ```Part``` gets it for free so there is no need to implement it
+pub trait PartInternal {
     fn get_name(&self) -> String;
     fn get_input_size(&self) -> usize;
     fn get_output_size(&self) -> usize;
+    fn get_input_pins(&self) -> Vec<Pin>;
+    fn get_output_pins(&self) -> Vec<Pin>;
+    fn get_parameters(&self) -> BTreeMap<String, f64>;
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64>;
+    fn spice_model(&self) -> Option<String>;
+    fn spice_inline(&self, instance: &str, inputs: &[String], outputs: &[String])
+        -> Option<String>;
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String>;
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String>;
+    fn mna_element(&self) -> Option<Element>;
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Box<dyn PartInternal>>;
+    fn output_ranges(&self, inputs: &[Interval]) -> Option<Vec<Interval>>;
+    fn as_circuit(&self) -> Option<&Circuit>;
+    /// for getting back at the concrete part, like the serializer does for spice functions
+    fn as_any(&self) -> &dyn Any;
     fn clone_internal(&self) -> Box<dyn PartInternal>;
     fn debug(&self) -> Box<dyn Debug>;
 }
@@ -48,6 +97,63 @@ impl PartInternal for Box<dyn PartInternal> {
         PartInternal::get_output_size(&**self)
     }
 
+    fn get_input_pins(&self) -> Vec<Pin> {
+        PartInternal::get_input_pins(&**self)
+    }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        PartInternal::get_output_pins(&**self)
+    }
+
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        PartInternal::get_parameters(&**self)
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        PartInternal::evaluate(&**self, inputs)
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        PartInternal::spice_model(&**self)
+    }
+
+    fn spice_inline(
+        &self,
+        instance: &str,
+        inputs: &[String],
+        outputs: &[String],
+    ) -> Option<String> {
+        PartInternal::spice_inline(&**self, instance, inputs, outputs)
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        PartInternal::verilog_a(&**self, inputs, outputs)
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        PartInternal::verilog(&**self, inputs, outputs)
+    }
+
+    fn mna_element(&self) -> Option<Element> {
+        PartInternal::mna_element(&**self)
+    }
+
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Box<dyn PartInternal>> {
+        PartInternal::with_parameters(&**self, parameters)
+    }
+
+    fn output_ranges(&self, inputs: &[Interval]) -> Option<Vec<Interval>> {
+        PartInternal::output_ranges(&**self, inputs)
+    }
+
+    fn as_circuit(&self) -> Option<&Circuit> {
+        PartInternal::as_circuit(&**self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        PartInternal::as_any(&**self)
+    }
+
     fn clone_internal(&self) -> Box<dyn PartInternal> {
         PartInternal::clone_internal(&**self)
     }
@@ -73,6 +179,63 @@ where
         Part::get_output_size(self)
     }
 
+    fn get_input_pins(&self) -> Vec<Pin> {
+        Part::get_input_pins(self)
+    }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        Part::get_output_pins(self)
+    }
+
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        Part::get_parameters(self)
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        Part::evaluate(self, inputs)
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Part::spice_model(self)
+    }
+
+    fn spice_inline(
+        &self,
+        instance: &str,
+        inputs: &[String],
+        outputs: &[String],
+    ) -> Option<String> {
+        Part::spice_inline(self, instance, inputs, outputs)
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Part::verilog_a(self, inputs, outputs)
+    }
+
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Part::verilog(self, inputs, outputs)
+    }
+
+    fn mna_element(&self) -> Option<Element> {
+        Part::mna_element(self)
+    }
+
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Box<dyn PartInternal>> {
+        Part::with_parameters(self, parameters).map(|part| Box::new(part) as Box<dyn PartInternal>)
+    }
+
+    fn output_ranges(&self, inputs: &[Interval]) -> Option<Vec<Interval>> {
+        Part::output_ranges(self, inputs)
+    }
+
+    fn as_circuit(&self) -> Option<&Circuit> {
+        Part::as_circuit(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn clone_internal(&self) -> Box<dyn PartInternal> {
         let part: Box<dyn PartInternal> = Box::new(self.clone()) as Box<dyn PartInternal>;
         part
@@ -102,17 +265,44 @@ impl Part for Circuit {
             .unwrap_or_else(|| "Unnamed Circuit".to_string())
     }
 
+    // from the outside only the program inputs/outputs are visible
     fn get_input_size(&self) -> usize {
-        self.next_input_index
+        self.program_inputs.len()
     }
 
     fn get_output_size(&self) -> usize {
-        self.next_output_index
+        self.program_outputs.len()
+    }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        self.program_inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let name = input.name.clone().unwrap_or_else(|| format!("in{}", i));
+                Pin::new(name, PinType::Analog)
+            })
+            .collect()
+    }
+
+    // a nested circuit that can't be simulated has no behavior, whoever simulates the outer one says so
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        simulate(self, inputs).unwrap_or_default()
+    }
+
+    fn as_circuit(&self) -> Option<&Circuit> {
+        Some(self)
+    }
+}
+
+impl Default for Circuit {
+    fn default() -> Self {
+        Circuit::new()
     }
 }
 
 impl Circuit {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Circuit {
             parts: vec![],
             connections: vec![],
@@ -121,14 +311,153 @@ impl Circuit {
             next_input_index: 0,
             next_output_index: 0,
             name: None,
+            instance_names: vec![],
+        }
+    }
+
+    /// rebuilds a circuit from its raw pieces, used when loading a serialized circuit
+    /// parts without an instance name get the one ```add_part``` would have given them
+    pub(crate) fn from_parts(
+        name: Option<String>,
+        parts: Vec<(Box<dyn PartInternal>, Option<String>)>,
+        connections: Vec<(usize, usize)>,
+        program_inputs: Vec<CircuitInput>,
+        program_outputs: Vec<usize>,
+    ) -> Self {
+        let mut circuit = Circuit::new();
+        circuit.name = name;
+        for (part, instance_name) in parts {
+            let instance_name =
+                instance_name.unwrap_or_else(|| circuit.default_instance_name(&*part));
+            circuit.parts.push(part);
+            circuit.instance_names.push(instance_name);
+        }
+
+        // the index spaces are dense so the next indices are just the totals
+        // program outputs consume a value and program inputs produce one
+        let next_input_index = circuit
+            .parts
+            .iter()
+            .map(|part| part.get_input_size())
+            .sum::<usize>()
+            + program_outputs.len();
+        let next_output_index = circuit
+            .parts
+            .iter()
+            .map(|part| part.get_output_size())
+            .sum::<usize>()
+            + program_inputs.len();
+
+        Circuit {
+            connections,
+            program_inputs,
+            program_outputs,
+            next_input_index,
+            next_output_index,
+            ..circuit
         }
     }
 
-    fn set_name(&mut self, name: String) {
+    pub fn set_name(&mut self, name: String) {
         self.name = Some(name);
     }
 
-    fn add_part(&mut self, part: impl PartInternal + 'static) -> PartInfo {
+    pub fn get_circuit_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn get_parts(&self) -> &Vec<Box<dyn PartInternal>> {
+        &self.parts
+    }
+
+    /// swaps a part for one with the same pins, the connections stay where they are
+    pub(crate) fn replace_part(&mut self, index: usize, part: Box<dyn PartInternal>) {
+        debug_assert_eq!(part.get_input_size(), self.parts[index].get_input_size());
+        debug_assert_eq!(part.get_output_size(), self.parts[index].get_output_size());
+        self.parts[index] = part;
+    }
+
+    /// the name of every part, in the same order as ```get_parts```
+    pub fn get_instance_names(&self) -> &Vec<String> {
+        &self.instance_names
+    }
+
+    pub fn get_connections(&self) -> &Vec<(usize, usize)> {
+        &self.connections
+    }
+
+    pub fn get_program_inputs(&self) -> &Vec<CircuitInput> {
+        &self.program_inputs
+    }
+
+    pub fn get_program_outputs(&self) -> &Vec<usize> {
+        &self.program_outputs
+    }
+
+    /// recovers where the inputs and outputs of every part start
+    /// program inputs/outputs can be added between parts so we skip over their indices
+    pub fn get_part_offsets(&self) -> Vec<PartInfo> {
+        let mut program_owned_inputs = vec![false; self.next_input_index];
+        for &index in &self.program_outputs {
+            program_owned_inputs[index] = true;
+        }
+        let mut program_owned_outputs = vec![false; self.next_output_index];
+        for input in &self.program_inputs {
+            program_owned_outputs[input.index] = true;
+        }
+
+        let mut input_offset = 0;
+        let mut output_offset = 0;
+        let mut offsets = vec![];
+        for part in &self.parts {
+            // a part's indices are always next to each other, so only the start can be shifted
+            if part.get_input_size() > 0 {
+                while program_owned_inputs[input_offset] {
+                    input_offset += 1;
+                }
+            }
+            if part.get_output_size() > 0 {
+                while program_owned_outputs[output_offset] {
+                    output_offset += 1;
+                }
+            }
+            offsets.push(PartInfo {
+                input_offset,
+                output_offset,
+            });
+            input_offset += part.get_input_size();
+            output_offset += part.get_output_size();
+        }
+        offsets
+    }
+
+    /// one past the last index of the consuming index space
+    pub fn get_next_input_index(&self) -> usize {
+        self.next_input_index
+    }
+
+    /// one past the last index of the producing index space
+    pub fn get_next_output_index(&self) -> usize {
+        self.next_output_index
+    }
+
+    pub fn add_part(&mut self, part: impl PartInternal + 'static) -> PartInfo {
+        let instance_name = self.default_instance_name(&part);
+        self.push_part(Box::new(part), instance_name)
+    }
+
+    /// the part name and how many parts with that name came before it
+    fn default_instance_name(&self, part: &dyn PartInternal) -> String {
+        let name = part.get_name();
+        let count = self
+            .parts
+            .iter()
+            .filter(|other| other.get_name() == name)
+            .count();
+        format!("{}#{}", name, count)
+    }
+
+    fn push_part(&mut self, part: Box<dyn PartInternal>, instance_name: String) -> PartInfo {
         let part_info = PartInfo {
             input_offset: self.next_input_index,
             output_offset: self.next_output_index,
@@ -141,37 +470,208 @@ impl Circuit {
         self.next_input_index += input_size;
         self.next_output_index += output_size;
 
-        self.parts.push(Box::new(part));
+        self.parts.push(part);
+        self.instance_names.push(instance_name);
 
         part_info
     }
 
-    fn connect(&mut self, from: usize, to: usize) {
+    /// inlines every nested circuit so only primitives are left, for tools that don't understand hierarchy
+    /// the instances are renamed after the path to them, like ```main/add#0/Adder#0```
+    pub fn flatten(&self) -> Circuit {
+        let mut flat = self.inline_nested();
+        let top = Part::get_name(self);
+        for instance_name in &mut flat.instance_names {
+            *instance_name = format!("{}/{}", top, instance_name);
+        }
+        flat
+    }
+
+    /// the same circuit with every nested circuit (flattened first) replaced by its parts
+    fn inline_nested(&self) -> Circuit {
+        // where a value comes from in the flat circuit, either a flat producing index or
+        // whatever drives one of our consuming indices (a nested circuit passing its input straight through)
+        #[derive(Clone, Copy)]
+        enum Source {
+            Flat(usize),
+            DriverOf(usize),
+        }
+
+        let mut flat = Circuit::new();
+        flat.name = self.name.clone();
+
+        let mut drivers = vec![None; self.next_input_index];
+        for &(from, to) in &self.connections {
+            drivers[to] = Some(from);
+        }
+
+        // what every one of our producing indices turned into
+        let mut producers = vec![None; self.next_output_index];
+        // every flat consuming index and where its value should come from
+        let mut consumers = vec![];
+
+        for input in &self.program_inputs {
+            producers[input.index] = Some(Source::Flat(
+                flat.add_ranged_input(input.name.clone(), input.range),
+            ));
+        }
+
+        for ((part, instance_name), offsets) in self
+            .parts
+            .iter()
+            .zip(&self.instance_names)
+            .zip(self.get_part_offsets())
+        {
+            let Some(nested) = part.as_circuit() else {
+                let info = flat.push_part(part.clone(), instance_name.clone());
+                for i in 0..part.get_input_size() {
+                    consumers.push((
+                        info.input_offset + i,
+                        Some(Source::DriverOf(offsets.input_offset + i)),
+                    ));
+                }
+                for i in 0..part.get_output_size() {
+                    producers[offsets.output_offset + i] =
+                        Some(Source::Flat(info.output_offset + i));
+                }
+                continue;
+            };
+
+            // only primitives are left in here
+            let nested = nested.inline_nested();
+            let mut nested_producers = vec![None; nested.next_output_index];
+            for (i, input) in nested.program_inputs.iter().enumerate() {
+                nested_producers[input.index] = Some(Source::DriverOf(offsets.input_offset + i));
+            }
+            let mut nested_consumers = vec![];
+            for ((nested_part, nested_name), nested_offsets) in nested
+                .parts
+                .iter()
+                .zip(&nested.instance_names)
+                .zip(nested.get_part_offsets())
+            {
+                let info = flat.push_part(
+                    nested_part.clone(),
+                    format!("{}/{}", instance_name, nested_name),
+                );
+                for i in 0..nested_part.get_input_size() {
+                    nested_consumers.push((info.input_offset + i, nested_offsets.input_offset + i));
+                }
+                for i in 0..nested_part.get_output_size() {
+                    nested_producers[nested_offsets.output_offset + i] =
+                        Some(Source::Flat(info.output_offset + i));
+                }
+            }
+
+            let mut nested_drivers = vec![None; nested.next_input_index];
+            for &(from, to) in &nested.connections {
+                nested_drivers[to] = Some(from);
+            }
+            let nested_source =
+                |index: usize| nested_drivers[index].and_then(|driver| nested_producers[driver]);
+            for (flat_index, index) in nested_consumers {
+                consumers.push((flat_index, nested_source(index)));
+            }
+            for (i, &index) in nested.program_outputs.iter().enumerate() {
+                producers[offsets.output_offset + i] = nested_source(index);
+            }
+        }
+
+        for &index in &self.program_outputs {
+            consumers.push((flat.add_program_output(), Some(Source::DriverOf(index))));
+        }
+
+        for (flat_index, source) in consumers {
+            let mut source = source;
+            // a chain of pass throughs can't be longer than the number of consuming indices
+            for _ in 0..=self.next_input_index {
+                match source {
+                    Some(Source::DriverOf(index)) => {
+                        source = drivers[index].and_then(|driver| producers[driver]);
+                    }
+                    _ => break,
+                }
+            }
+            if let Some(Source::Flat(from)) = source {
+                flat.connect(from, flat_index);
+            }
+        }
+
+        flat
+    }
+
+    /// connects a producing index to a consuming index
+    pub fn connect(&mut self, from: usize, to: usize) {
         self.connections.push((from, to));
     }
 
     // add an input to the circuit
-    fn add_program_input(&mut self, name: Option<String>) -> usize {
-        let index = self.next_input_index;
-        self.program_inputs.push(CircuitInput { index, name });
-        self.next_input_index += 1;
+    // inside the circuit a program input drives other parts, so it lives with the part outputs
+    pub fn add_program_input(&mut self, name: Option<String>) -> usize {
+        self.add_ranged_input(name, None)
+    }
+
+    /// an input that is only ever given values in the range, range analysis starts from it
+    pub fn add_ranged_input(&mut self, name: Option<String>, range: Option<Interval>) -> usize {
+        let index = self.next_output_index;
+        self.program_inputs
+            .push(CircuitInput { index, name, range });
+        self.next_output_index += 1;
         index
     }
 
     // add an output to the circuit
-    fn add_program_output(&mut self) -> usize {
-        let index = self.next_output_index;
+    // and a program output is driven by a part, so it lives with the part inputs
+    pub fn add_program_output(&mut self) -> usize {
+        let index = self.next_input_index;
         self.program_outputs.push(index);
-        self.next_output_index += 1;
+        self.next_input_index += 1;
         index
     }
 }
 
-struct PartInfo {
-    input_offset: usize, // the first input will be at input_offset then the next will be at input_offset + 1 etc
-    output_offset: usize, // the same as input_offset but for outputs
+#[derive(Debug, Clone, Copy)]
+pub struct PartInfo {
+    pub input_offset: usize, // the first input will be at input_offset then the next will be at input_offset + 1 etc
+    pub output_offset: usize, // the same as input_offset but for outputs
+}
+
+/// what kind of signal a pin expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinType {
+    /// any value
+    #[default]
+    Analog,
+    /// 0 or 1, anything above 0.5 counts as high
+    Logic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    name: String,
+    pin_type: PinType,
+}
+
+impl Pin {
+    pub fn new(name: impl Into<String>, pin_type: PinType) -> Self {
+        Pin {
+            name: name.into(),
+            pin_type,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_pin_type(&self) -> PinType {
+        self.pin_type
+    }
 }
 
+/// anything that can be put in a ```Circuit```
+/// implement this for your own cells and put them in a ```PartRegistry``` so programs can call them
 pub trait Part: Debug + Clone
 where
     Self: 'static,
@@ -179,14 +679,79 @@ where
     fn get_name(&self) -> String;
     fn get_input_size(&self) -> usize;
     fn get_output_size(&self) -> usize;
+    /// one pin per input, the default is ```in0```, ```in1```... all analog
+    fn get_input_pins(&self) -> Vec<Pin> {
+        (0..self.get_input_size())
+            .map(|i| Pin::new(format!("in{}", i), PinType::Analog))
+            .collect()
+    }
+    /// one pin per output, the default is ```out0```, ```out1```... all analog
+    fn get_output_pins(&self) -> Vec<Pin> {
+        (0..self.get_output_size())
+            .map(|i| Pin::new(format!("out{}", i), PinType::Analog))
+            .collect()
+    }
+    /// named numeric parameters of the part (gain, resistance, thresholds...), empty for most parts
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        BTreeMap::new()
+    }
+    /// the ideal behaviour of the part, gets one value per input and returns one value per output
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64>;
+    /// the lines inside the part's ```.SUBCKT```, the ports are the pin names
+    /// parameters are passed to the subcircuit so they can be used as ```{name}```
+    fn spice_model(&self) -> Option<String> {
+        None
+    }
+    /// a single spice line written instead of instancing a subcircuit, for things spice already has
+    fn spice_inline(
+        &self,
+        _instance: &str,
+        _inputs: &[String],
+        _outputs: &[String],
+    ) -> Option<String> {
+        None
+    }
+    /// the contribution statements for the part inside an ```analog``` block, one per line
+    /// the nets are given by name so an output is driven with ```V(out) <+ ...;```
+    fn verilog_a(&self, _inputs: &[String], _outputs: &[String]) -> Option<String> {
+        None
+    }
+    /// the same for synthesizable verilog, ```assign``` statements on fixed width signed integers
+    fn verilog(&self, _inputs: &[String], _outputs: &[String]) -> Option<String> {
+        None
+    }
+    /// how the part is stamped into the DC solver, without one the outputs are ideal sources driven by ```evaluate```
+    fn mna_element(&self) -> Option<Element> {
+        None
+    }
+    /// a copy of the part with some of its ```get_parameters``` changed, the ones not in the map keep
+    /// their value. None for parts that can't be changed, Monte Carlo analysis leaves those alone
+    fn with_parameters(&self, _parameters: &BTreeMap<String, f64>) -> Option<Self> {
+        None
+    }
+    /// every value each output can take when the inputs stay within their ranges, for range analysis
+    /// without one ```evaluate``` is tried at every corner of the inputs which is fine for monotonic parts
+    fn output_ranges(&self, _inputs: &[Interval]) -> Option<Vec<Interval>> {
+        None
+    }
+    /// only circuits return something here, it lets us walk into nested circuits
+    fn as_circuit(&self) -> Option<&Circuit> {
+        None
+    }
     fn as_debug(&self) -> Box<dyn Debug> {
         Box::new(self.clone()) as Box<dyn Debug>
     }
 }
 
 #[derive(Debug, Clone)]
-struct Constant {
-    value: f64,
+pub struct Constant {
+    pub(crate) value: f64,
+}
+
+impl Constant {
+    pub fn new(value: f64) -> Self {
+        Constant { value }
+    }
 }
 
 impl Part for Constant {
@@ -201,11 +766,56 @@ impl Part for Constant {
     fn get_output_size(&self) -> usize {
         1
     }
+
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        BTreeMap::from([("value".to_string(), self.value)])
+    }
+
+    fn evaluate(&self, _inputs: &[f64]) -> Vec<f64> {
+        vec![self.value]
+    }
+
+    fn spice_inline(
+        &self,
+        instance: &str,
+        _inputs: &[String],
+        outputs: &[String],
+    ) -> Option<String> {
+        Some(format!("V{} {} 0 DC {}", instance, outputs[0], self.value))
+    }
+
+    fn verilog_a(&self, _inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!("V({}) <+ {};\n", outputs[0], self.value))
+    }
+
+    fn verilog(&self, _inputs: &[String], outputs: &[String]) -> Option<String> {
+        // verilog only has the integers, the translator refuses a Float constant
+        if self.value.fract() != 0.0 {
+            return None;
+        }
+        Some(format!("assign {} = {};\n", outputs[0], self.value as i64))
+    }
+
+    fn mna_element(&self) -> Option<Element> {
+        Some(Element::VoltageSource(self.value))
+    }
+
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Self> {
+        Some(Constant {
+            value: parameters.get("value").copied().unwrap_or(self.value),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
-struct Resistor {
-    resistance: f64, // ohms
+pub struct Resistor {
+    pub(crate) resistance: f64, // ohms
+}
+
+impl Resistor {
+    pub fn new(resistance: f64) -> Self {
+        Resistor { resistance }
+    }
 }
 
 impl Part for Resistor {
@@ -220,104 +830,700 @@ impl Part for Resistor {
     fn get_output_size(&self) -> usize {
         1
     }
-}
 
-#[derive(Clone)]
-struct VariableInfo {
-    index: usize,
-}
-
-#[derive(Clone)]
-struct ScopeInfo {
-    variables: HashMap<String, VariableInfo>,
-}
-
-impl ScopeInfo {
-    fn new() -> Self {
-        ScopeInfo {
-            variables: HashMap::new(),
-        }
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        BTreeMap::from([("resistance".to_string(), self.resistance)])
     }
 
-    fn add_variable(&mut self, name: String, index: usize) {
-        self.variables.insert(name, VariableInfo { index });
+    // nothing loads the resistor in the ideal model so it just passes the value through
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![inputs[0]]
     }
-}
 
-struct ScopeBody {
-    body: Vec<ASTNode>,
-}
-
-impl ScopeBody {
-    fn new(vec: Vec<ASTNode>) -> Self {
-        ScopeBody { body: vec }
+    fn spice_inline(
+        &self,
+        instance: &str,
+        inputs: &[String],
+        outputs: &[String],
+    ) -> Option<String> {
+        Some(format!(
+            "R{} {} {} {}",
+            instance, inputs[0], outputs[0], self.resistance
+        ))
     }
 
-    fn get_body(&self) -> &Vec<ASTNode> {
-        &self.body
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "I({}, {}) <+ V({}, {}) / {};\n",
+            inputs[0], outputs[0], inputs[0], outputs[0], self.resistance
+        ))
     }
 
-    /// returns a circuit that represents the body of the scope
-    /// if variables are used in the body that are not defined in the scope,
-    fn get_circuit(&self, exterior_function_defs: HashMap<String, Circuit>) -> Circuit {
-        let mut circuit = Circuit::new();
-
-        // we don't want the variables from the exterior scope to be used in the body (the indices wouldn't exist or would be wrong)
-        let mut translator = Translator::new();
-        translator.function_defs = exterior_function_defs.clone();
+    // there is nothing to resist in logic
+    fn verilog(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        Some(format!("assign {} = {};\n", outputs[0], inputs[0]))
+    }
 
-        for node in &self.body {
-            let _output_index = translator.translate_ast_internal(node.clone(), &mut circuit);
-        }
+    fn mna_element(&self) -> Option<Element> {
+        Some(Element::Resistor(self.resistance))
+    }
 
-        circuit
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Self> {
+        Some(Resistor {
+            resistance: parameters
+                .get("resistance")
+                .copied()
+                .unwrap_or(self.resistance),
+        })
     }
 }
 
+/// a resistor from its input to ground, it has no outputs and only matters to the DC solver
 #[derive(Debug, Clone)]
-pub enum NoVariableError {
-    NotDefinedInScope,
+pub struct Load {
+    pub(crate) resistance: f64, // ohms
 }
 
-pub struct Translator {
-    scope_defs: Vec<ScopeInfo>,
-    function_defs: HashMap<String, Circuit>,
+impl Load {
+    pub fn new(resistance: f64) -> Self {
+        Load { resistance }
+    }
 }
 
-impl Translator {
-    pub fn new() -> Self {
-        Translator {
-            scope_defs: vec![ScopeInfo {
-                variables: HashMap::new(),
-            }],
-            function_defs: HashMap::new(),
-        }
+impl Part for Load {
+    fn get_name(&self) -> String {
+        "Load".to_string()
     }
 
-    pub fn get_function_circuit(&self, name: String) -> &Circuit {
-        self.function_defs
-            .get(&name)
-            .expect(format!("function {} not defined", name).as_str())
+    fn get_input_size(&self) -> usize {
+        1
     }
 
-    pub fn add_function_circuit(&mut self, name: String, circuit: Circuit) {
-        self.function_defs.insert(name, circuit);
+    fn get_output_size(&self) -> usize {
+        0
     }
 
-    /// get the index of a variable in the current scope
-    pub fn get_variable_index(&mut self, ident: String) -> Result<usize, NoVariableError> {
-        let scope = self.scope_defs.last().unwrap();
-        let var_info = scope
-            .variables
-            .get(&ident)
-            .ok_or(NoVariableError::NotDefinedInScope)?;
-        Ok(var_info.index)
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        BTreeMap::from([("resistance".to_string(), self.resistance)])
     }
 
-    /// we copy the last scope whenever we enter a new scope
-    fn enter_scope(&mut self) {
-        self.scope_defs
-            .push(self.scope_defs.last().unwrap().clone());
+    fn evaluate(&self, _inputs: &[f64]) -> Vec<f64> {
+        vec![]
+    }
+
+    fn spice_inline(
+        &self,
+        instance: &str,
+        inputs: &[String],
+        _outputs: &[String],
+    ) -> Option<String> {
+        Some(format!("R{} {} 0 {}", instance, inputs[0], self.resistance))
+    }
+
+    fn verilog_a(&self, inputs: &[String], _outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "I({}) <+ V({}) / {};\n",
+            inputs[0], inputs[0], self.resistance
+        ))
+    }
+
+    fn verilog(&self, _inputs: &[String], _outputs: &[String]) -> Option<String> {
+        Some(String::new())
+    }
+
+    fn mna_element(&self) -> Option<Element> {
+        Some(Element::Load(self.resistance))
+    }
+
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Self> {
+        Some(Load {
+            resistance: parameters
+                .get("resistance")
+                .copied()
+                .unwrap_or(self.resistance),
+        })
+    }
+}
+
+/// a capacitor from its input to ground, it has no outputs and only matters to transient analysis
+#[derive(Debug, Clone)]
+pub struct Capacitor {
+    pub(crate) capacitance: f64, // farads
+}
+
+impl Capacitor {
+    pub fn new(capacitance: f64) -> Self {
+        Capacitor { capacitance }
+    }
+}
+
+impl Part for Capacitor {
+    fn get_name(&self) -> String {
+        "Capacitor".to_string()
+    }
+
+    fn get_input_size(&self) -> usize {
+        1
+    }
+
+    fn get_output_size(&self) -> usize {
+        0
+    }
+
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        BTreeMap::from([("capacitance".to_string(), self.capacitance)])
+    }
+
+    fn evaluate(&self, _inputs: &[f64]) -> Vec<f64> {
+        vec![]
+    }
+
+    fn spice_inline(
+        &self,
+        instance: &str,
+        inputs: &[String],
+        _outputs: &[String],
+    ) -> Option<String> {
+        Some(format!(
+            "C{} {} 0 {}",
+            instance, inputs[0], self.capacitance
+        ))
+    }
+
+    fn verilog_a(&self, inputs: &[String], _outputs: &[String]) -> Option<String> {
+        Some(format!(
+            "I({}) <+ {} * ddt(V({}));\n",
+            inputs[0], self.capacitance, inputs[0]
+        ))
+    }
+
+    fn verilog(&self, _inputs: &[String], _outputs: &[String]) -> Option<String> {
+        Some(String::new())
+    }
+
+    fn mna_element(&self) -> Option<Element> {
+        Some(Element::Capacitor(self.capacitance))
+    }
+
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Self> {
+        Some(Capacitor {
+            capacitance: parameters
+                .get("capacitance")
+                .copied()
+                .unwrap_or(self.capacitance),
+        })
+    }
+}
+
+/// an op-amp as a voltage controlled voltage source, the output is ```gain * (in0 - in1 + offset)```
+/// with a big enough gain and some feedback it behaves like an ideal one
+#[derive(Debug, Clone)]
+pub struct OpAmp {
+    pub(crate) gain: f64,
+    /// the input offset voltage, 0 for an ideal one
+    pub(crate) offset: f64,
+}
+
+impl OpAmp {
+    pub fn new(gain: f64) -> Self {
+        OpAmp { gain, offset: 0.0 }
+    }
+
+    pub fn with_offset(gain: f64, offset: f64) -> Self {
+        OpAmp { gain, offset }
+    }
+}
+
+impl Default for OpAmp {
+    fn default() -> Self {
+        OpAmp::new(1e6)
+    }
+}
+
+impl Part for OpAmp {
+    fn get_name(&self) -> String {
+        "OpAmp".to_string()
+    }
+
+    fn get_input_size(&self) -> usize {
+        2
+    }
+
+    fn get_output_size(&self) -> usize {
+        1
+    }
+
+    fn get_parameters(&self) -> BTreeMap<String, f64> {
+        BTreeMap::from([
+            ("gain".to_string(), self.gain),
+            ("offset".to_string(), self.offset),
+        ])
+    }
+
+    // without the rest of the network there is no feedback, so this is the open loop output
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        vec![self.gain * (inputs[0] - inputs[1] + self.offset)]
+    }
+
+    fn spice_inline(
+        &self,
+        instance: &str,
+        inputs: &[String],
+        outputs: &[String],
+    ) -> Option<String> {
+        if self.offset == 0.0 {
+            return Some(format!(
+                "E{} {} 0 {} {} {}",
+                instance, outputs[0], inputs[0], inputs[1], self.gain
+            ));
+        }
+        // the offset is a source in series with the positive input
+        Some(format!(
+            "V{}os os{} {} {}\nE{} {} 0 os{} {} {}",
+            instance,
+            instance,
+            inputs[0],
+            self.offset,
+            instance,
+            outputs[0],
+            instance,
+            inputs[1],
+            self.gain
+        ))
+    }
+
+    fn verilog_a(&self, inputs: &[String], outputs: &[String]) -> Option<String> {
+        if self.offset == 0.0 {
+            return Some(format!(
+                "V({}) <+ {} * (V({}) - V({}));\n",
+                outputs[0], self.gain, inputs[0], inputs[1]
+            ));
+        }
+        Some(format!(
+            "V({}) <+ {} * (V({}) - V({}) + {});\n",
+            outputs[0], self.gain, inputs[0], inputs[1], self.offset
+        ))
+    }
+
+    fn mna_element(&self) -> Option<Element> {
+        Some(Element::Vcvs {
+            gain: self.gain,
+            offset: self.offset,
+        })
+    }
+
+    fn with_parameters(&self, parameters: &BTreeMap<String, f64>) -> Option<Self> {
+        Some(OpAmp {
+            gain: parameters.get("gain").copied().unwrap_or(self.gain),
+            offset: parameters.get("offset").copied().unwrap_or(self.offset),
+        })
+    }
+}
+
+/// a function written as a ```spice { ... }``` block
+/// the ACL body is kept as the behaviour when there is one, otherwise the part can only be written out
+#[derive(Debug, Clone)]
+pub struct SpicePart {
+    name: String,
+    inputs: Vec<String>,
+    output_size: usize,
+    spice: String,
+    behavior: Option<Circuit>,
+}
+
+impl SpicePart {
+    pub fn new(
+        name: String,
+        inputs: Vec<String>,
+        output_size: usize,
+        spice: String,
+        behavior: Option<Circuit>,
+    ) -> Self {
+        SpicePart {
+            name,
+            inputs,
+            output_size,
+            spice,
+            behavior,
+        }
+    }
+
+    pub fn get_input_names(&self) -> &Vec<String> {
+        &self.inputs
+    }
+
+    pub fn get_spice(&self) -> &str {
+        &self.spice
+    }
+
+    pub fn get_behavior(&self) -> Option<&Circuit> {
+        self.behavior.as_ref()
+    }
+}
+
+impl Part for SpicePart {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get_input_size(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn get_output_size(&self) -> usize {
+        self.output_size
+    }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        self.inputs
+            .iter()
+            .map(|name| Pin::new(name.clone(), PinType::Analog))
+            .collect()
+    }
+
+    // nothing comes out without a behaviour, the simulator reports that instead of guessing
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        self.behavior
+            .as_ref()
+            .and_then(|behavior| simulate(behavior, inputs).ok())
+            .unwrap_or_default()
+    }
+
+    fn spice_model(&self) -> Option<String> {
+        Some(self.spice.clone())
+    }
+}
+
+/// a call to a function, every call shares the one definition instead of carrying its own copy
+#[derive(Clone)]
+pub struct CircuitInstance {
+    definition: Rc<Circuit>,
+}
+
+impl CircuitInstance {
+    pub fn new(definition: Rc<Circuit>) -> Self {
+        CircuitInstance { definition }
+    }
+
+    pub fn get_definition(&self) -> &Rc<Circuit> {
+        &self.definition
+    }
+}
+
+// the definition is printed once wherever it lives, not at every call
+impl Debug for CircuitInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitInstance")
+            .field("definition", &Part::get_name(&*self.definition))
+            .finish()
+    }
+}
+
+impl Part for CircuitInstance {
+    fn get_name(&self) -> String {
+        Part::get_name(&*self.definition)
+    }
+
+    fn get_input_size(&self) -> usize {
+        Part::get_input_size(&*self.definition)
+    }
+
+    fn get_output_size(&self) -> usize {
+        Part::get_output_size(&*self.definition)
+    }
+
+    fn get_input_pins(&self) -> Vec<Pin> {
+        Part::get_input_pins(&*self.definition)
+    }
+
+    fn get_output_pins(&self) -> Vec<Pin> {
+        Part::get_output_pins(&*self.definition)
+    }
+
+    fn evaluate(&self, inputs: &[f64]) -> Vec<f64> {
+        Part::evaluate(&*self.definition, inputs)
+    }
+
+    fn as_circuit(&self) -> Option<&Circuit> {
+        Some(&self.definition)
+    }
+}
+
+#[derive(Clone)]
+struct VariableInfo {
+    index: usize,
+    // a Bool is already a 0/1 level, so it can drive a gate as it is
+    boolean: bool,
+}
+
+#[derive(Clone)]
+struct ScopeInfo {
+    variables: HashMap<String, VariableInfo>,
+}
+
+impl ScopeInfo {
+    fn new() -> Self {
+        ScopeInfo {
+            variables: HashMap::new(),
+        }
+    }
+
+    fn add_variable(&mut self, name: String, index: usize, boolean: bool) {
+        self.variables.insert(name, VariableInfo { index, boolean });
+    }
+}
+
+/// one step of a function body
+/// blocks get flattened into these so a loop can be unrolled in front of whatever comes after it
+#[derive(Clone)]
+enum BodyItem {
+    Statement(ASTNode),
+    EnterScope,
+    ExitScope,
+}
+
+/// the statements of a block wrapped in its own scope
+fn scoped_block(body: &[ASTNode]) -> Vec<BodyItem> {
+    let mut items = vec![BodyItem::EnterScope];
+    items.extend(body.iter().cloned().map(BodyItem::Statement));
+    items.push(BodyItem::ExitScope);
+    items
+}
+
+/// how a block leaves the function
+#[derive(Clone, Copy)]
+enum Returned {
+    /// every path falls through to whatever comes after the block
+    Never,
+    /// every path returns the value at this index, nothing after the block is reached
+    Always(usize),
+    /// returns the value when the flag is high and falls through when it is low
+    When { flag: usize, value: usize },
+}
+
+impl Returned {
+    /// the output of a whole function, a function only some paths return from has no value on the others
+    fn into_output(self) -> Result<Option<usize>, TranslationError> {
+        match self {
+            Returned::Never => Ok(None),
+            Returned::Always(value) => Ok(Some(value)),
+            Returned::When { .. } => Err(TranslationError::MismatchedReturns),
+        }
+    }
+
+    fn value(self) -> Option<usize> {
+        match self {
+            Returned::Never => None,
+            Returned::Always(value) | Returned::When { value, .. } => Some(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NoVariableError {
+    NotDefinedInScope,
+}
+
+/// things in the program that can't be turned into a circuit
+#[derive(Debug, Clone)]
+pub enum TranslationError {
+    UndefinedFunction(String),
+    /// assigned to with ```=``` before a ```let```
+    UndefinedVariable(String),
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        got: usize,
+    },
+    /// nothing has the name of the entry point
+    NoEntryPoint(String),
+    EntryPointDefinedTwice(String),
+    /// nothing in the ```PartRegistry``` has this name
+    UndefinedPart(String),
+    /// the registered part doesn't have the pins it is used with, or the body of a spice function doesn't
+    /// have the ones it declares
+    WrongPinCount {
+        part: String,
+        inputs: usize,
+        outputs: usize,
+    },
+    /// some paths through a function return and others fall off the end, so those have no value
+    MismatchedReturns,
+    /// the value block of an if expression can't return from the function
+    ReturnInsideExpression,
+    Unsupported(String),
+}
+
+impl fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslationError::UndefinedFunction(name) => {
+                write!(f, "no function named {}", name)
+            }
+            TranslationError::UndefinedVariable(name) => {
+                write!(f, "{} is assigned to before it is defined with let", name)
+            }
+            TranslationError::WrongArgumentCount {
+                function,
+                expected,
+                got,
+            } => write!(
+                f,
+                "{} takes {} arguments but was called with {}",
+                function, expected, got
+            ),
+            TranslationError::NoEntryPoint(name) => {
+                write!(f, "no function named {} to start from", name)
+            }
+            TranslationError::EntryPointDefinedTwice(name) => {
+                write!(f, "the entry point {} is defined twice", name)
+            }
+            TranslationError::UndefinedPart(name) => write!(f, "no part named {}", name),
+            TranslationError::WrongPinCount {
+                part,
+                inputs,
+                outputs,
+            } => write!(
+                f,
+                "{} has {} inputs and {} outputs, which doesn't match how it is used",
+                part, inputs, outputs
+            ),
+            TranslationError::MismatchedReturns => {
+                write!(f, "some paths through a function return and others don't")
+            }
+            TranslationError::ReturnInsideExpression => {
+                write!(f, "the value block of an if expression can't return")
+            }
+            TranslationError::Unsupported(what) => write!(f, "{} isn't supported", what),
+        }
+    }
+}
+
+pub struct Translator {
+    // the function that becomes the circuit handed back, everything else ends up nested inside it
+    entry_point: String,
+    parts: PartRegistry,
+    scope_defs: Vec<ScopeInfo>,
+    // shared by every call to the function
+    function_defs: HashMap<String, Rc<Circuit>>,
+    // functions that are a single part instead of a circuit, ```extern fn``` and ```spice``` functions
+    part_functions: HashMap<String, Box<dyn PartInternal>>,
+    // identifiers that weren't defined anywhere become inputs of the function being translated
+    // they live outside of the scopes so every branch of an if shares them
+    implicit_inputs: HashMap<String, usize>,
+}
+
+impl Default for Translator {
+    fn default() -> Self {
+        Translator::new()
+    }
+}
+
+impl Translator {
+    pub fn new() -> Self {
+        Translator::with_entry_point("main".to_string())
+    }
+
+    pub fn with_entry_point(entry_point: String) -> Self {
+        Translator {
+            entry_point,
+            parts: PartRegistry::builtin(),
+            scope_defs: vec![ScopeInfo {
+                variables: HashMap::new(),
+            }],
+            function_defs: HashMap::new(),
+            part_functions: HashMap::new(),
+            implicit_inputs: HashMap::new(),
+        }
+    }
+
+    /// the parts operators are built from and ```extern fn``` declarations are bound to
+    pub fn set_part_registry(&mut self, parts: PartRegistry) {
+        self.parts = parts;
+    }
+
+    pub fn get_function_circuit(&self, name: String) -> Result<&Circuit, TranslationError> {
+        self.function_defs
+            .get(&name)
+            .map(|circuit| &**circuit)
+            .ok_or(TranslationError::UndefinedFunction(name))
+    }
+
+    /// binds an ```extern fn``` to the registered part with the same name
+    fn bind_extern_function(&mut self, node: &ExternFunction) -> Result<(), TranslationError> {
+        let part = self
+            .parts
+            .instance(node.get_name())
+            .ok_or_else(|| TranslationError::UndefinedPart(node.get_name().to_string()))?;
+        let returns = node.get_return_type().is_some();
+        if part.get_input_size() != node.get_args().len()
+            || (returns && part.get_output_size() == 0)
+        {
+            return Err(TranslationError::WrongPinCount {
+                part: node.get_name().to_string(),
+                inputs: part.get_input_size(),
+                outputs: part.get_output_size(),
+            });
+        }
+        self.part_functions
+            .insert(node.get_name().to_string(), part);
+        Ok(())
+    }
+
+    pub fn add_function_circuit(&mut self, name: String, circuit: Circuit) {
+        self.function_defs.insert(name, Rc::new(circuit));
+    }
+
+    /// get the index of a variable, looking from the innermost scope outwards
+    pub fn get_variable_index(&mut self, ident: String) -> Result<usize, NoVariableError> {
+        let var_info = self
+            .scope_defs
+            .iter()
+            .rev()
+            .find_map(|scope| scope.variables.get(&ident))
+            .ok_or(NoVariableError::NotDefinedInScope)?;
+        Ok(var_info.index)
+    }
+
+    /// points an existing variable at a new value in whichever scope it was defined
+    fn reassign_variable(
+        &mut self,
+        ident: &str,
+        index: usize,
+        boolean: bool,
+    ) -> Result<(), TranslationError> {
+        let var_info = self
+            .scope_defs
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.variables.get_mut(ident))
+            .ok_or_else(|| TranslationError::UndefinedVariable(ident.to_string()))?;
+        var_info.index = index;
+        var_info.boolean = boolean;
+        Ok(())
+    }
+
+    /// if the expression is known to give a ```Bool```, a call to a function returning one isn't yet
+    fn is_bool(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::Dyadic(dyadic) => {
+                is_comparison(dyadic.get_operator()) || is_logical(dyadic.get_operator())
+            }
+            Expression::Not(_) | Expression::Value(Value::Bool(_)) => true,
+            Expression::ParenExpression(inner) => self.is_bool(inner),
+            Expression::Identifier(ident) => self
+                .scope_defs
+                .iter()
+                .rev()
+                .find_map(|scope| scope.variables.get(ident))
+                .is_some_and(|var_info| var_info.boolean),
+            _ => false,
+        }
+    }
+
+    /// every scope starts empty, lookups fall through to the outer scopes
+    fn enter_scope(&mut self) {
+        self.scope_defs.push(ScopeInfo::new());
     }
 
     /// we drop the last scope whenever we exit a scope
@@ -330,122 +1536,494 @@ impl Translator {
         self.scope_defs.last_mut().expect("no scope to get")
     }
 
-    // TODO: make this use the ScopeBody struct
-    fn make_function_circuit(&mut self, node: FunctionDefinition) -> Circuit {
+    fn make_function_circuit(
+        &mut self,
+        node: FunctionDefinition,
+    ) -> Result<Circuit, TranslationError> {
         let mut circuit = Circuit::new();
+        self.implicit_inputs.clear();
 
         // add the inputs of the function to the circuit
         for input in node.get_args() {
-            let input_index = circuit.add_program_input(Some(input.0.clone()));
+            let input_index = circuit.add_ranged_input(Some(input.0.clone()), input.1.get_range());
             let name = input.0.clone();
 
-            // the type isn't used for now
-            self.get_current_scope().add_variable(name, input_index);
+            // the type checker already made sure the values fit, only the range is kept
+            let boolean = matches!(input.1, Type::Bool);
+            self.get_current_scope()
+                .add_variable(name, input_index, boolean);
         }
 
         // translate the body of the function
-        self.process_function_returns(node.clone(), &mut circuit);
+        self.process_function_returns(node.clone(), &mut circuit)?;
 
         circuit.set_name(node.get_name().to_string());
-        circuit
-    }
-
-    fn process_function_returns(&mut self, node: FunctionDefinition, circuit: &mut Circuit) {
-        for sub_node in node.get_body() {
-            match sub_node {
-                ASTNode::Return(_) => {
-                    // get the circuit for the expression
-                    let internal_output_index =
-                        self.translate_ast_internal(sub_node.clone(), circuit);
-
-                    // return statement means this is the output of the circuit
-                    // connect the output of the internal circuit to the output of the main circuit
-                    let new_output_index = circuit.add_program_output();
-                    circuit.connect(
-                        internal_output_index.expect("failed to get internal output index"),
-                        new_output_index,
-                    );
+        Ok(circuit)
+    }
+
+    fn process_function_returns(
+        &mut self,
+        node: FunctionDefinition,
+        circuit: &mut Circuit,
+    ) -> Result<(), TranslationError> {
+        let saved_scopes = self.scope_defs.clone();
+        let items = node
+            .get_body()
+            .iter()
+            .cloned()
+            .map(BodyItem::Statement)
+            .collect();
+        let return_index = self.translate_block(items, circuit)?.into_output()?;
+        // a return can leave scopes of loops or ifs behind
+        self.scope_defs = saved_scopes;
+
+        // the returned value is the output of the circuit
+        if let Some(return_index) = return_index {
+            let output_index = circuit.add_program_output();
+            circuit.connect(return_index, output_index);
+        }
+        Ok(())
+    }
+
+    /// translates the body and says how it returns, nothing after a return that is always taken is translated
+    fn translate_block(
+        &mut self,
+        mut items: VecDeque<BodyItem>,
+        circuit: &mut Circuit,
+    ) -> Result<Returned, TranslationError> {
+        let mut returned = Returned::Never;
+        while let Some(item) = items.pop_front() {
+            let statement_returned = match item {
+                BodyItem::EnterScope => {
+                    self.enter_scope();
+                    continue;
+                }
+                BodyItem::ExitScope => {
+                    self.exit_scope();
+                    continue;
+                }
+                BodyItem::Statement(ASTNode::Return(inner_expr)) => {
+                    let ASTNode::Expression(expr) = *inner_expr else {
+                        return Err(TranslationError::Unsupported(format!(
+                            "returning {:?}",
+                            inner_expr
+                        )));
+                    };
+                    Returned::Always(self.translate_expression(expr, circuit)?)
+                }
+                BodyItem::Statement(ASTNode::IfStatement(statement)) => {
+                    self.translate_if_statement(statement, circuit)?
+                }
+                BodyItem::Statement(ASTNode::ForLoop(for_loop)) => {
+                    // the range is known so the loop is unrolled, every iteration gets its own scope
+                    // where the loop variable is a constant
+                    if for_loop.get_range().len() > MAX_LOOP_ITERATIONS {
+                        return Err(TranslationError::Unsupported(format!(
+                            "a loop of {} iterations, more than {},",
+                            for_loop.get_range().len(),
+                            MAX_LOOP_ITERATIONS
+                        )));
+                    }
+                    for i in for_loop.get_range().rev() {
+                        let mut iteration = scoped_block(for_loop.get_body());
+                        iteration.insert(
+                            1,
+                            BodyItem::Statement(ASTNode::Assignment(Assignment::new(
+                                for_loop.get_variable().to_string(),
+                                Expression::Value(Value::Int(i)),
+                            ))),
+                        );
+                        for item in iteration.into_iter().rev() {
+                            items.push_front(item);
+                        }
+                    }
+                    continue;
+                }
+                BodyItem::Statement(node) => {
+                    let _output_index = self.translate_ast_internal(node, circuit)?;
+                    continue;
                 }
-                _ => {
-                    let _output_index = self.translate_ast_internal(sub_node.clone(), circuit);
+            };
+
+            // an earlier return that was taken wins over anything after it
+            returned = match returned {
+                Returned::Never => statement_returned,
+                Returned::When { flag, value } => {
+                    self.merge_returns(circuit, flag, Returned::Always(value), statement_returned)?
                 }
+                Returned::Always(_) => unreachable!("nothing is translated after a return"),
+            };
+            if let Returned::Always(_) = returned {
+                return Ok(returned);
             }
         }
+
+        Ok(returned)
     }
 
     fn translate_function_def(
         &mut self,
         node: FunctionDefinition,
         circuit: &mut Circuit,
-    ) -> Option<usize> {
-        if node.get_name() == "main" {
+    ) -> Result<Option<usize>, TranslationError> {
+        if node.get_name() == self.entry_point {
+            if node.get_spice().is_some() {
+                return Err(TranslationError::Unsupported(format!(
+                    "a spice block on the entry point {}",
+                    self.entry_point
+                )));
+            }
             // set the circuit to the main circuit
-            let main_circuit = self.make_function_circuit(node);
+            let function_name = node.get_name().to_string();
+            let main_circuit = self.make_function_circuit(node)?;
+            // functions after it can still call it, the entry point doesn't have to be main
+            self.add_function_circuit(function_name, main_circuit.clone());
             *circuit = main_circuit;
-            Some((circuit as &dyn PartInternal).get_output_size() - 1)
+            Ok(circuit.program_outputs.last().copied())
+        } else if let Some(spice) = node.get_spice() {
+            // the body, if there is one, is still translated so the part can be simulated
+            let output_size = node.get_return_type().map_or(0, |_| 1);
+            let behavior = if node.get_body().is_empty() {
+                None
+            } else {
+                let behavior = self.make_function_circuit(node.clone())?;
+                let (inputs, outputs) = (
+                    behavior.get_program_inputs().len(),
+                    behavior.get_program_outputs().len(),
+                );
+                if inputs != node.get_args().len() || outputs != output_size {
+                    return Err(TranslationError::WrongPinCount {
+                        part: node.get_name().to_string(),
+                        inputs,
+                        outputs,
+                    });
+                }
+                Some(behavior)
+            };
+            let inputs = node
+                .get_args()
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect();
+            let part = SpicePart::new(
+                node.get_name().to_string(),
+                inputs,
+                output_size,
+                spice.to_string(),
+                behavior,
+            );
+            self.part_functions
+                .insert(node.get_name().to_string(), Box::new(part));
+            Ok(None)
         } else {
             let function_name = node.get_name().to_string();
-            let function_circuit = self.make_function_circuit(node);
-            println!("name: {:?}", Part::get_name(&function_circuit));
+            let function_circuit = self.make_function_circuit(node)?;
+            log::debug!(
+                "translated function {:?}",
+                Part::get_name(&function_circuit)
+            );
             self.add_function_circuit(function_name, function_circuit);
-            None
-        }
-    }
-
-    // We have to note that if statements are pointless without
-    // case 1:
-    // - let value = if(smth) {smth} else {smth}
-    // which i will ignore for now
-    // - a return statement in the if statement
-    // which is problematic because return statements are only parsed in the outermost body of a function right now
-    // this should form a circuit that looks like this for case 1 (which we aren't supporting for now):
-    // condition_circuit   -            - body_circuit -
-    //                       \        /                 \
-    //                         Gate -                    |
-    //                       /        \                  |
-    // if statement inputs -            ------------------ rest of the function
-    // in case 2 the circuit would look like this
-    // condition_circuit   -             ----- body_circuit -----
-    //                       \         /                          \
-    //                         - Gate -                              - Function Out
-    //                       /         \                          /
-    // if statement inputs -             - rest of the function -
-    fn translate_if_statement(&mut self, node: IfStatement, circuit: &mut Circuit) {
-        // two parts: the condition and the body
-        let condition_circuit =
-            self.translate_ast_internal(ASTNode::Expression(node.get_condition().clone()), circuit);
-        let body_circuit = ScopeBody::new(node.get_body().clone());
-        body_circuit.get_circuit(self.function_defs.clone());
-        // connect the condition to the body
-        // TODO: connect the condition properly
-        // also, we need to make sure the body's inputs are connected properly
-        // and also are routed properly based on the condition
-    }
-
-    fn translate_program(&mut self, nodes: Vec<ASTNode>, circuit: &mut Circuit) -> usize {
-        let mut output_index = None;
-        for node in nodes {
-            match node {
-                ASTNode::FunctionDefinition(func_def) => {
-                    if func_def.get_name() == "main" {
-                        if output_index.is_some() {
-                            panic!("main function already defined");
-                        }
+            Ok(None)
+        }
+    }
 
-                        output_index = self.translate_function_def(func_def, circuit);
-                    } else {
-                        self.translate_function_def(func_def, circuit);
+    // both branches are always built, then every variable either of them changed goes through a multiplexer
+    // condition_circuit ----------------
+    //                                   \
+    //            - body -- variable -- Gate -
+    //           /                              \
+    // inputs --                                  + - variable after the if
+    //           \                              /
+    //            - else -- variable -- Gate -
+    // returns are joined the same way, along with a flag that says whether one was taken
+    fn translate_if_statement(
+        &mut self,
+        node: IfStatement,
+        circuit: &mut Circuit,
+    ) -> Result<Returned, TranslationError> {
+        let condition_index = self.translate_condition(node.get_condition().clone(), circuit)?;
+        let saved_scopes = self.scope_defs.clone();
+
+        let body_returned =
+            self.translate_block(VecDeque::from(scoped_block(node.get_body())), circuit)?;
+        // a return leaves the scopes of the branch behind
+        self.scope_defs.truncate(saved_scopes.len());
+        let body_scopes = std::mem::replace(&mut self.scope_defs, saved_scopes.clone());
+
+        let else_returned =
+            self.translate_block(VecDeque::from(scoped_block(node.get_else_body())), circuit)?;
+        self.scope_defs.truncate(saved_scopes.len());
+        let else_scopes = std::mem::replace(&mut self.scope_defs, saved_scopes);
+
+        // a branch that always returns never reaches the code after the if, so the other one decides
+        match (body_returned, else_returned) {
+            (Returned::Always(_), Returned::Always(_)) => {}
+            (Returned::Always(_), _) => self.scope_defs = else_scopes,
+            (_, Returned::Always(_)) => self.scope_defs = body_scopes,
+            _ => self.merge_scopes(circuit, condition_index, &body_scopes, &else_scopes)?,
+        }
+
+        self.merge_returns(circuit, condition_index, body_returned, else_returned)
+    }
+
+    /// points every variable the branches disagree on at a multiplexer between the two values
+    fn merge_scopes(
+        &mut self,
+        circuit: &mut Circuit,
+        condition_index: usize,
+        body_scopes: &[ScopeInfo],
+        else_scopes: &[ScopeInfo],
+    ) -> Result<(), TranslationError> {
+        for depth in 0..self.scope_defs.len() {
+            // sorted so the parts are always added in the same order
+            let mut names: Vec<String> = self.scope_defs[depth].variables.keys().cloned().collect();
+            names.sort();
+            for name in names {
+                let body_info = &body_scopes[depth].variables[&name];
+                let else_info = &else_scopes[depth].variables[&name];
+                if body_info.index == else_info.index {
+                    continue;
+                }
+                let boolean = body_info.boolean && else_info.boolean;
+                let index = self.add_multiplexer(
+                    circuit,
+                    condition_index,
+                    body_info.index,
+                    else_info.index,
+                )?;
+                self.scope_defs[depth].add_variable(name, index, boolean);
+            }
+        }
+        Ok(())
+    }
+
+    /// how two blocks return together when the condition picks which one is taken
+    fn merge_returns(
+        &mut self,
+        circuit: &mut Circuit,
+        condition_index: usize,
+        body: Returned,
+        other: Returned,
+    ) -> Result<Returned, TranslationError> {
+        match (body, other) {
+            (Returned::Never, Returned::Never) => Ok(Returned::Never),
+            (Returned::Always(body_value), Returned::Always(other_value)) => Ok(Returned::Always(
+                self.add_multiplexer(circuit, condition_index, body_value, other_value)?,
+            )),
+            _ => {
+                // some path falls through, so whether a return was taken is only known in the circuit
+                let body_flag = self.return_flag(circuit, body);
+                let other_flag = self.return_flag(circuit, other);
+                let flag = self.add_multiplexer(circuit, condition_index, body_flag, other_flag)?;
+                // the value only matters when the flag is high, a branch that can't return doesn't need one
+                let value = match (body.value(), other.value()) {
+                    (Some(body_value), Some(other_value)) => {
+                        self.add_multiplexer(circuit, condition_index, body_value, other_value)?
                     }
+                    (Some(value), None) | (None, Some(value)) => value,
+                    (None, None) => unreachable!("both branches would fall through"),
+                };
+                Ok(Returned::When { flag, value })
+            }
+        }
+    }
+
+    /// a 0/1 level that is high when the block returned
+    fn return_flag(&mut self, circuit: &mut Circuit, returned: Returned) -> usize {
+        match returned {
+            Returned::Never => self.add_constant(circuit, 0.0),
+            Returned::Always(_) => self.add_constant(circuit, 1.0),
+            Returned::When { flag, .. } => flag,
+        }
+    }
+
+    /// translates the value block of an if expression in its own scope
+    fn translate_value_block(
+        &mut self,
+        block: &ValueBlock,
+        circuit: &mut Circuit,
+    ) -> Result<usize, TranslationError> {
+        let saved_scopes = self.scope_defs.clone();
+        self.enter_scope();
+        let items = block
+            .get_statements()
+            .iter()
+            .cloned()
+            .map(BodyItem::Statement)
+            .collect();
+        if !matches!(self.translate_block(items, circuit)?, Returned::Never) {
+            return Err(TranslationError::ReturnInsideExpression);
+        }
+        let value_index = self.translate_expression(block.get_value().clone(), circuit)?;
+        self.scope_defs = saved_scopes;
+        Ok(value_index)
+    }
+
+    /// IfGates route the value to their second output when the control is high and to the first when it is low
+    /// the unused output sits at 0 so adding the right outputs of two gates picks between the values
+    fn add_multiplexer(
+        &mut self,
+        circuit: &mut Circuit,
+        control_index: usize,
+        true_index: usize,
+        false_index: usize,
+    ) -> Result<usize, TranslationError> {
+        let true_gate = circuit.add_part(self.get_part("IfGate", 2, 2)?);
+        circuit.connect(control_index, true_gate.input_offset);
+        circuit.connect(true_index, true_gate.input_offset + 1);
+
+        let false_gate = circuit.add_part(self.get_part("IfGate", 2, 2)?);
+        circuit.connect(control_index, false_gate.input_offset);
+        circuit.connect(false_index, false_gate.input_offset + 1);
+
+        self.add_binary_part(
+            circuit,
+            "Adder",
+            true_gate.output_offset + 1,
+            false_gate.output_offset,
+        )
+    }
+
+    /// a copy of the registered part, it has to have the pins the caller is going to connect
+    fn get_part(
+        &self,
+        name: &str,
+        input_size: usize,
+        output_size: usize,
+    ) -> Result<Box<dyn PartInternal>, TranslationError> {
+        let part = self
+            .parts
+            .instance(name)
+            .ok_or_else(|| TranslationError::UndefinedPart(name.to_string()))?;
+        if part.get_input_size() != input_size || part.get_output_size() != output_size {
+            return Err(TranslationError::WrongPinCount {
+                part: name.to_string(),
+                inputs: part.get_input_size(),
+                outputs: part.get_output_size(),
+            });
+        }
+        Ok(part)
+    }
+
+    /// adds a part with two inputs and one output and connects it up
+    fn add_binary_part(
+        &mut self,
+        circuit: &mut Circuit,
+        name: &str,
+        left_index: usize,
+        right_index: usize,
+    ) -> Result<usize, TranslationError> {
+        let part_info = circuit.add_part(self.get_part(name, 2, 1)?);
+        circuit.connect(left_index, part_info.input_offset);
+        circuit.connect(right_index, part_info.input_offset + 1);
+        Ok(part_info.output_offset)
+    }
+
+    fn add_constant(&mut self, circuit: &mut Circuit, value: f64) -> usize {
+        circuit.add_part(Constant { value }).output_offset
+    }
+
+    /// flips a 0/1 level
+    fn add_not(&mut self, circuit: &mut Circuit, index: usize) -> Result<usize, TranslationError> {
+        let part_info = circuit.add_part(self.get_part("Not", 1, 1)?);
+        circuit.connect(index, part_info.input_offset);
+        Ok(part_info.output_offset)
+    }
+
+    /// conditions have to be 0 or 1 to drive a gate, anything that isn't already a ```Bool``` is compared
+    /// against 0, that includes variables and arguments declared or assigned as one
+    fn translate_condition(
+        &mut self,
+        condition: Expression,
+        circuit: &mut Circuit,
+    ) -> Result<usize, TranslationError> {
+        if self.is_bool(&condition) {
+            return self.translate_expression(condition, circuit);
+        }
+        let value_index = self.translate_expression(condition, circuit)?;
+        let zero = self.add_constant(circuit, 0.0);
+        self.translate_comparison(&Operator::NotEqual, value_index, zero, circuit)
+    }
+
+    /// everything is built out of the comparator, which outputs 1 when left > right
+    fn translate_comparison(
+        &mut self,
+        operator: &Operator,
+        left_index: usize,
+        right_index: usize,
+        circuit: &mut Circuit,
+    ) -> Result<usize, TranslationError> {
+        match operator {
+            Operator::GreaterThan => {
+                self.add_binary_part(circuit, "Comparator", left_index, right_index)
+            }
+            Operator::LessThan => {
+                self.add_binary_part(circuit, "Comparator", right_index, left_index)
+            }
+            Operator::GreaterThanOrEqual => {
+                let less = self.add_binary_part(circuit, "Comparator", right_index, left_index)?;
+                self.add_not(circuit, less)
+            }
+            Operator::LessThanOrEqual => {
+                let greater =
+                    self.add_binary_part(circuit, "Comparator", left_index, right_index)?;
+                self.add_not(circuit, greater)
+            }
+            Operator::NotEqual | Operator::Equal => {
+                let greater =
+                    self.add_binary_part(circuit, "Comparator", left_index, right_index)?;
+                let less = self.add_binary_part(circuit, "Comparator", right_index, left_index)?;
+                // at most one of them can be 1
+                let different = self.add_binary_part(circuit, "Adder", greater, less)?;
+                if let Operator::Equal = operator {
+                    self.add_not(circuit, different)
+                } else {
+                    Ok(different)
                 }
-                _ => (),
+            }
+            _ => panic!("{:?} is not a comparison", operator),
+        }
+    }
+
+    /// the output of the entry point, None when it never returns anything
+    fn translate_program(
+        &mut self,
+        nodes: Vec<ASTNode>,
+        circuit: &mut Circuit,
+    ) -> Result<Option<usize>, TranslationError> {
+        // externs can be declared anywhere in the file, so they are bound before any function needs them
+        for node in &nodes {
+            if let ASTNode::ExternFunction(extern_function) = node {
+                self.bind_extern_function(extern_function)?;
             }
         }
 
-        if output_index.is_none() {
-            panic!("main function not defined or doesn't return anything");
+        let mut entry_point_defined = false;
+        let mut output_index = None;
+        for node in nodes {
+            if let ASTNode::FunctionDefinition(func_def) = node {
+                // every function gets its own scope so variables don't leak between them
+                self.enter_scope();
+                if func_def.get_name() == self.entry_point {
+                    if entry_point_defined {
+                        return Err(TranslationError::EntryPointDefinedTwice(
+                            self.entry_point.clone(),
+                        ));
+                    }
+                    entry_point_defined = true;
+
+                    output_index = self.translate_function_def(func_def, circuit)?;
+                } else {
+                    self.translate_function_def(func_def, circuit)?;
+                }
+                self.exit_scope();
+            }
         }
 
-        output_index.unwrap()
+        if !entry_point_defined {
+            return Err(TranslationError::NoEntryPoint(self.entry_point.clone()));
+        }
+        Ok(output_index)
     }
 
     /// Outputs the index of the output of the circuit
@@ -453,120 +2031,218 @@ impl Translator {
         &mut self,
         node: ASTNode,
         circuit: &mut Circuit,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, TranslationError> {
         match node {
-            ASTNode::Program(nodes) => Some(self.translate_program(nodes, circuit)),
+            ASTNode::Program(nodes) => self.translate_program(nodes, circuit),
             ASTNode::FunctionDefinition(func_def) => {
                 self.enter_scope();
                 let output_index = self.translate_function_def(func_def, circuit);
                 self.exit_scope();
                 output_index
             }
-            ASTNode::IfStatement(statement) => {
-                self.enter_scope();
-                self.translate_if_statement(statement, circuit);
-                self.exit_scope();
-                None
+            ASTNode::ExternFunction(extern_function) => {
+                self.bind_extern_function(&extern_function)?;
+                Ok(None)
+            }
+            ASTNode::IfStatement(_) | ASTNode::ForLoop(_) => {
+                // these can return, so they only make sense as part of a block
+                self.translate_block(VecDeque::from([BodyItem::Statement(node)]), circuit)?
+                    .into_output()
             }
             ASTNode::Return(ref inner_expr) => {
                 // get the circuit for the expression
                 let internal_output_index =
-                    self.translate_ast_internal(*inner_expr.clone(), circuit);
+                    self.translate_ast_internal(*inner_expr.clone(), circuit)?;
 
                 // return statement means this is the output of the circuit
                 // connect the output of the internal circuit to the output of the main circuit
+                let Some(internal_output_index) = internal_output_index else {
+                    return Ok(None);
+                };
                 let new_output_index = circuit.add_program_output();
-                circuit.connect(internal_output_index?, new_output_index);
-                Some(new_output_index)
+                circuit.connect(internal_output_index, new_output_index);
+                Ok(Some(new_output_index))
             }
-            ASTNode::Expression(expr) => Some(self.translate_expression(expr, circuit)),
-
-            _ => panic!("{:?} couldn't be handled", node),
+            ASTNode::Expression(expr) => Ok(Some(self.translate_expression(expr, circuit)?)),
+            ASTNode::Assignment(assignment) => {
+                // the variable just points at whatever produces the value
+                let boolean = self.is_bool(assignment.get_value());
+                let value_index =
+                    self.translate_expression(assignment.get_value().clone(), circuit)?;
+                self.get_current_scope().add_variable(
+                    assignment.get_name().to_string(),
+                    value_index,
+                    boolean,
+                );
+                Ok(None)
+            }
+            ASTNode::Reassignment(assignment) => {
+                let boolean = self.is_bool(assignment.get_value());
+                let value_index =
+                    self.translate_expression(assignment.get_value().clone(), circuit)?;
+                self.reassign_variable(assignment.get_name(), value_index, boolean)?;
+                Ok(None)
+            }
+            // tests don't become hardware
+            ASTNode::Test(_) | ASTNode::Assert(_) => Ok(None),
         }
     }
 
-    pub fn translate_ast(&mut self, node: ASTNode) -> Circuit {
+    pub fn translate_ast(&mut self, node: ASTNode) -> Result<Circuit, TranslationError> {
         let mut circuit = Circuit::new();
-        self.translate_ast_internal(node, &mut circuit);
-        circuit
+        self.translate_ast_internal(node, &mut circuit)?;
+        Ok(circuit)
     }
 
-    fn translate_expression(&mut self, expr: Expression, circuit: &mut Circuit) -> usize {
+    /// translates an expression, it always produces a value so its index is never missing
+    fn translate_operand(
+        &mut self,
+        node: ASTNode,
+        circuit: &mut Circuit,
+    ) -> Result<usize, TranslationError> {
+        Ok(self
+            .translate_ast_internal(node, circuit)?
+            .expect("failed to get internal output index"))
+    }
+
+    fn translate_expression(
+        &mut self,
+        expr: Expression,
+        circuit: &mut Circuit,
+    ) -> Result<usize, TranslationError> {
         match expr {
             Expression::Dyadic(dyadic) => {
                 let left_node = ASTNode::Expression(dyadic.get_left().clone());
                 let right_node = ASTNode::Expression(dyadic.get_right().clone());
-                let left_circuit_output_index = self.translate_ast_internal(left_node, circuit);
-                let right_circuit_output_index = self.translate_ast_internal(right_node, circuit);
-
-                let operator_circuit = self.get_operator_circuit(dyadic.get_operator());
-                let operator_info = circuit.add_part(operator_circuit);
+                let left_circuit_output_index = self.translate_operand(left_node, circuit)?;
+                let right_circuit_output_index = self.translate_operand(right_node, circuit)?;
+
+                if is_comparison(dyadic.get_operator()) {
+                    return self.translate_comparison(
+                        dyadic.get_operator(),
+                        left_circuit_output_index,
+                        right_circuit_output_index,
+                        circuit,
+                    );
+                }
 
                 // connect the inputs of the operator to the outputs of the left and right circuits
-                circuit.connect(
-                    left_circuit_output_index.expect("failed to get internal output index"),
-                    operator_info.input_offset,
-                );
-                circuit.connect(
-                    right_circuit_output_index.expect("failed to get internal output index"),
-                    operator_info.input_offset + 1,
-                );
-
-                operator_info.output_offset // assuming the operator has only one output
+                let operator_name = get_operator_part_name(dyadic.get_operator())?;
+                self.add_binary_part(
+                    circuit,
+                    operator_name,
+                    left_circuit_output_index,
+                    right_circuit_output_index,
+                )
             }
             Expression::Identifier(ident) => {
-                let var_index = self.get_variable_index(ident.clone());
                 // if the variable is not in the current scope, it must be a function argument
                 // so we add a new input to the circuit
-                if let Err(_) = var_index {
-                    let input_index = circuit.add_program_input(Some(ident.clone()));
-                    self.get_current_scope().add_variable(ident, input_index);
-                    input_index
-                } else {
-                    var_index.unwrap()
+                if let Ok(var_index) = self.get_variable_index(ident.clone()) {
+                    return Ok(var_index);
+                }
+                if let Some(input_index) = self.implicit_inputs.get(&ident) {
+                    return Ok(*input_index);
                 }
+                let input_index = circuit.add_program_input(Some(ident.clone()));
+                self.implicit_inputs.insert(ident, input_index);
+                Ok(input_index)
+            }
+            Expression::Value(Value::Int(value)) => Ok(self.add_constant(circuit, value as f64)),
+            Expression::Value(Value::Float(value)) => Ok(self.add_constant(circuit, value)),
+            Expression::Value(Value::Bool(value)) => {
+                Ok(self.add_constant(circuit, value as i32 as f64))
+            }
+            Expression::ParenExpression(inner) => self.translate_expression(*inner, circuit),
+            Expression::Not(inner) => {
+                let inner_index = self.translate_expression(*inner, circuit)?;
+                self.add_not(circuit, inner_index)
+            }
+            Expression::IfExpression(if_expression) => {
+                let condition_index =
+                    self.translate_condition(if_expression.get_condition().clone(), circuit)?;
+                let body_index = self.translate_value_block(if_expression.get_body(), circuit)?;
+                let else_index =
+                    self.translate_value_block(if_expression.get_else_body(), circuit)?;
+                self.add_multiplexer(circuit, condition_index, body_index, else_index)
             }
             Expression::FunctionCall(call) => {
-                // This will take a lot of thought. Some sort of structure where it can guarentee the function isn't being used twice at the same time
-                // And if it is instatiate a new version
-                //panic!("Function calls are not yet implemented.")
                 let mut arg_indices = vec![];
                 for arg in call.get_args() {
-                    let arg_index = self.translate_ast_internal(arg.clone(), circuit);
-                    arg_indices.push(arg_index);
+                    arg_indices.push(self.translate_operand(arg.clone(), circuit)?);
                 }
 
-                // get the function definition
+                // get the function definition, it is either ACL code or an extern part
                 let function_name = call.get_name();
-                let function_circuit = self.get_function_circuit(function_name.to_string());
-                let mut function_circuit_clone = function_circuit.clone();
-                function_circuit_clone.set_name(function_name.to_string());
+                let function_part: Box<dyn PartInternal> =
+                    match self.function_defs.get(function_name) {
+                        Some(definition) => Box::new(CircuitInstance::new(Rc::clone(definition))),
+                        None => {
+                            self.part_functions
+                                .get(function_name)
+                                .cloned()
+                                .ok_or_else(|| {
+                                    TranslationError::UndefinedFunction(function_name.to_string())
+                                })?
+                        }
+                    };
+                let expected = function_part.get_input_size();
+                if expected != arg_indices.len() {
+                    return Err(TranslationError::WrongArgumentCount {
+                        function: function_name.to_string(),
+                        expected,
+                        got: arg_indices.len(),
+                    });
+                }
+                if function_part.get_output_size() == 0 {
+                    return Err(TranslationError::WrongPinCount {
+                        part: function_name.to_string(),
+                        inputs: function_part.get_input_size(),
+                        outputs: 0,
+                    });
+                }
 
                 // add the function to the circuit
-                let function_info = circuit.add_part(function_circuit.clone());
+                let function_info = circuit.add_part(function_part);
 
                 // connect the inputs of the function to the outputs of the arguments
                 for (i, arg_index) in arg_indices.iter().enumerate() {
-                    circuit.connect(
-                        arg_index.expect("failed to get arg index"),
-                        function_info.input_offset + i,
-                    );
+                    circuit.connect(*arg_index, function_info.input_offset + i);
                 }
 
                 // connect the output of the function to the output of the circuit
-                function_info.output_offset
+                Ok(function_info.output_offset)
             }
-            _ => panic!("{:#?} not yet implemented", expr),
+            _ => Err(TranslationError::Unsupported(format!("{:?}", expr))),
         }
     }
+}
 
-    fn get_operator_circuit(&self, operator: &Operator) -> Box<dyn PartInternal> {
-        match operator {
-            Operator::Plus => Box::new(Adder {}),
-            Operator::Minus => Box::new(Subtractor {}),
-            Operator::Multiply => Box::new(Multiplier {}),
-            Operator::Divide => Box::new(Divider {}),
-            _ => panic!("{:?} not yet implemented", operator),
-        }
+/// which registered part each arithmetic and logical operator is built from
+fn get_operator_part_name(operator: &Operator) -> Result<&'static str, TranslationError> {
+    match operator {
+        Operator::Plus => Ok("Adder"),
+        Operator::Minus => Ok("Subtractor"),
+        Operator::Multiply => Ok("Multiplier"),
+        Operator::Divide => Ok("Divider"),
+        Operator::And => Ok("And"),
+        Operator::Or => Ok("Or"),
+        _ => Err(TranslationError::Unsupported(format!("{:?}", operator))),
     }
 }
+
+fn is_comparison(operator: &Operator) -> bool {
+    matches!(
+        operator,
+        Operator::Equal
+            | Operator::NotEqual
+            | Operator::LessThan
+            | Operator::LessThanOrEqual
+            | Operator::GreaterThan
+            | Operator::GreaterThanOrEqual
+    )
+}
+
+fn is_logical(operator: &Operator) -> bool {
+    matches!(operator, Operator::And | Operator::Or)
+}
@@ -0,0 +1,593 @@
+//! checks the ranges of ```Int<low..high>``` values before anything is turned into a circuit
+//!
+//! every expression gets the interval of values it can take, the same interval arithmetic as
+//! ```range_analysis``` but on the program so the errors can name functions and arguments. arguments
+//! have to fit the range of the parameter they are passed to, returned values the declared return
+//! type, and every value in between has to fit in an ```Int``` of the verilog width
+//!
+//! a plain ```Int``` can be anything, so it never fits where a range is expected and nothing computed
+//! from one is checked for overflow
+//!
+//! an ```Int``` can be used where a ```Float``` is expected but not the other way around, arithmetic with
+//! a ```Float``` on either side is a ```Float``` and isn't rounded or limited to the verilog width. ```/```
+//! divides exactly like the divider does, so it is always a ```Float```
+//!
+//! comparisons and ```&&```, ```||```, ```!``` give a ```Bool```, which is what conditions and asserts take.
+//! a ```Bool``` is a logic level in the circuit so it can be used as an ```Int``` that is 0 or 1
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    range_analysis::Interval, ASTNode, Expression, FunctionCall, Operator, Type, Value, ValueBlock,
+    MAX_LOOP_ITERATIONS,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError {
+    /// ```Int<low..high>``` with low above high, nothing fits
+    EmptyRange { function: String, declared: Type },
+    /// a call can pass a value outside the range of the parameter
+    ArgumentOutOfRange {
+        function: String,
+        call: String,
+        argument: String,
+        range: Interval,
+        declared: Type,
+    },
+    /// the function can return a value outside its return type
+    ReturnOutOfRange {
+        function: String,
+        range: Interval,
+        declared: Type,
+    },
+    /// a value along the way doesn't fit in the ```int_width``` bits the verilog uses
+    Overflow { function: String, range: Interval },
+    /// a ```String``` where a number is needed, a ```Float``` where an ```Int``` is needed...
+    Mismatch {
+        function: String,
+        expected: Type,
+        found: Type,
+    },
+    /// a loop runs for more than ```MAX_LOOP_ITERATIONS```, too many copies of its body to build
+    TooManyIterations { function: String, iterations: usize },
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeError::EmptyRange { function, declared } => {
+                write!(f, "{} in {} has no values", declared, function)
+            }
+            TypeError::ArgumentOutOfRange {
+                function,
+                call,
+                argument,
+                range,
+                declared,
+            } => write!(
+                f,
+                "{} can pass {} to argument {} of {}, outside of {}",
+                function, range, argument, call, declared
+            ),
+            TypeError::ReturnOutOfRange {
+                function,
+                range,
+                declared,
+            } => write!(
+                f,
+                "{} can return {}, outside of its return type {}",
+                function, range, declared
+            ),
+            TypeError::Overflow { function, range } => write!(
+                f,
+                "a value in {} can be {}, more than an Int of the verilog width holds",
+                function, range
+            ),
+            TypeError::Mismatch {
+                function,
+                expected,
+                found,
+            } => write!(f, "{} expected {} but found {}", function, expected, found),
+            TypeError::TooManyIterations {
+                function,
+                iterations,
+            } => write!(
+                f,
+                "a loop in {} runs {} times, at most {} are unrolled",
+                function, iterations, MAX_LOOP_ITERATIONS
+            ),
+        }
+    }
+}
+
+/// what an expression evaluates to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Checked {
+    Int(Interval),
+    Float(Interval),
+    Bool,
+    String,
+}
+
+impl Checked {
+    fn of_type(value_type: Type) -> Checked {
+        match value_type {
+            Type::String => Checked::String,
+            Type::Float => Checked::Float(Interval::UNBOUNDED),
+            Type::Bool => Checked::Bool,
+            _ => Checked::Int(value_type.get_range().unwrap_or(Interval::UNBOUNDED)),
+        }
+    }
+
+    fn union(&self, other: &Checked) -> Checked {
+        match (self, other, self.as_number(), other.as_number()) {
+            (Checked::Bool, Checked::Bool, _, _) => Checked::Bool,
+            (_, _, Some((a, false)), Some((b, false))) => Checked::Int(a.union(&b)),
+            (_, _, Some((a, _)), Some((b, _))) => Checked::Float(a.union(&b)),
+            // the branches disagree, the mismatch is found wherever the value is used
+            _ => Checked::String,
+        }
+    }
+
+    /// the range of a number and if it is a ```Float```, a ```Bool``` is an ```Int``` that is 0 or 1
+    fn as_number(&self) -> Option<(Interval, bool)> {
+        match self {
+            Checked::Int(range) => Some((*range, false)),
+            Checked::Float(range) => Some((*range, true)),
+            Checked::Bool => Some((Interval::new(0.0, 1.0), false)),
+            Checked::String => None,
+        }
+    }
+
+    /// an ```Int``` given where a ```Float``` is declared becomes one, a ```Bool``` given where a number
+    /// is declared becomes 0 or 1
+    fn promoted_to(self, declared: Type) -> Checked {
+        match (self, declared) {
+            (Checked::Int(range), Type::Float) => Checked::Float(range),
+            (Checked::Bool, Type::Float) => Checked::Float(Interval::new(0.0, 1.0)),
+            (Checked::Bool, Type::Int | Type::RangedInt { .. }) => {
+                Checked::Int(Interval::new(0.0, 1.0))
+            }
+            (checked, _) => checked,
+        }
+    }
+
+    fn get_type(&self) -> Type {
+        match self {
+            Checked::Int(_) => Type::Int,
+            Checked::Float(_) => Type::Float,
+            Checked::Bool => Type::Bool,
+            Checked::String => Type::String,
+        }
+    }
+}
+
+struct Signature<'a> {
+    args: &'a Vec<(String, Type)>,
+    return_type: Option<Type>,
+    /// None for extern functions, their parts are checked by whoever wrote them
+    body: Option<&'a Vec<ASTNode>>,
+}
+
+/// checks every function and test of the program, ```int_width``` is the verilog ```Int``` width
+pub fn check(program: &ASTNode, int_width: u32) -> Result<(), TypeError> {
+    let ASTNode::Program(nodes) = program else {
+        return Ok(());
+    };
+    let mut functions = HashMap::new();
+    for node in nodes {
+        match node {
+            ASTNode::FunctionDefinition(function) => {
+                empty_ranges(
+                    function.get_name(),
+                    function.get_args(),
+                    function.get_return_type(),
+                )?;
+                functions.insert(
+                    function.get_name(),
+                    Signature {
+                        args: function.get_args(),
+                        return_type: function.get_return_type(),
+                        // a spice body is a netlist, there is nothing to check in it
+                        body: Some(function.get_body()).filter(|_| function.get_spice().is_none()),
+                    },
+                );
+            }
+            ASTNode::ExternFunction(function) => {
+                empty_ranges(
+                    function.get_name(),
+                    function.get_args(),
+                    function.get_return_type(),
+                )?;
+                functions.insert(
+                    function.get_name(),
+                    Signature {
+                        args: function.get_args(),
+                        return_type: function.get_return_type(),
+                        body: None,
+                    },
+                );
+            }
+            _ => (),
+        }
+    }
+
+    let limit = 2f64.powi(int_width.saturating_sub(1).min(1023) as i32);
+    let checker = Checker {
+        functions: &functions,
+        limit: Interval::new(-limit, limit - 1.0),
+    };
+    for node in nodes {
+        match node {
+            ASTNode::FunctionDefinition(function) => {
+                let arguments = function
+                    .get_args()
+                    .iter()
+                    .map(|(_, arg)| Checked::of_type(*arg))
+                    .collect::<Vec<_>>();
+                checker.function(function.get_name(), &arguments, &mut vec![])?;
+            }
+            ASTNode::Test(test) => {
+                let mut scope = Scope::new(format!("test {}", test.get_name()));
+                checker.block(test.get_body(), &mut scope, &mut vec![])?;
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn empty_ranges(
+    function: &str,
+    args: &[(String, Type)],
+    return_type: Option<Type>,
+) -> Result<(), TypeError> {
+    for declared in args.iter().map(|(_, arg)| *arg).chain(return_type) {
+        if let Type::RangedInt { low, high } = declared {
+            if low > high {
+                return Err(TypeError::EmptyRange {
+                    function: function.to_string(),
+                    declared,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Checker<'a> {
+    functions: &'a HashMap<&'a str, Signature<'a>>,
+    /// what an ```Int``` can hold
+    limit: Interval,
+}
+
+/// the variables of the function being checked and what it has returned so far
+struct Scope {
+    function: String,
+    variables: HashMap<String, Checked>,
+    returned: Option<Checked>,
+}
+
+impl Scope {
+    fn new(function: String) -> Self {
+        Scope {
+            function,
+            variables: HashMap::new(),
+            returned: None,
+        }
+    }
+
+    fn branch(&self) -> Scope {
+        Scope {
+            function: self.function.clone(),
+            variables: self.variables.clone(),
+            returned: self.returned,
+        }
+    }
+
+    /// after an if, a variable can have the value from either branch
+    fn merge(&mut self, body: Scope, else_body: Scope) {
+        for (name, value) in body.variables {
+            let merged = match else_body.variables.get(&name) {
+                Some(other) => value.union(other),
+                None => value,
+            };
+            self.variables.insert(name, merged);
+        }
+        for (name, value) in else_body.variables {
+            self.variables.entry(name).or_insert(value);
+        }
+        self.returned = match (body.returned, else_body.returned) {
+            (Some(a), Some(b)) => Some(a.union(&b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+impl Checker<'_> {
+    /// the values the function returns when called with the arguments, ```calls``` are the functions
+    /// being checked further up so a function calling itself doesn't go on forever
+    fn function(
+        &self,
+        name: &str,
+        arguments: &[Checked],
+        calls: &mut Vec<String>,
+    ) -> Result<Checked, TypeError> {
+        let signature = &self.functions[name];
+        let declared = signature
+            .return_type
+            .map_or(Checked::Int(Interval::UNBOUNDED), Checked::of_type);
+        let Some(body) = signature.body else {
+            return Ok(declared);
+        };
+        if calls.iter().any(|call| call == name) {
+            return Ok(declared);
+        }
+
+        let mut scope = Scope::new(name.to_string());
+        for ((arg, declared), value) in signature.args.iter().zip(arguments) {
+            scope
+                .variables
+                .insert(arg.clone(), value.promoted_to(*declared));
+        }
+        calls.push(name.to_string());
+        self.block(body, &mut scope, calls)?;
+        calls.pop();
+
+        let returned = scope.returned.unwrap_or(Checked::Int(Interval::UNBOUNDED));
+        let promoted = signature
+            .return_type
+            .map_or(returned, |declared| returned.promoted_to(declared));
+        match (signature.return_type, promoted) {
+            (Some(declared @ Type::RangedInt { .. }), Checked::Int(range)) => {
+                if !range.is_within(&declared.get_range().unwrap()) {
+                    return Err(TypeError::ReturnOutOfRange {
+                        function: name.to_string(),
+                        range,
+                        declared,
+                    });
+                }
+                // what this call returns can be narrower than what the function promises
+                Ok(Checked::Int(range))
+            }
+            (Some(declared), found) => self.expect(&scope, declared, found).map(|()| found),
+            (None, found) => Ok(found),
+        }
+    }
+
+    fn block(
+        &self,
+        nodes: &[ASTNode],
+        scope: &mut Scope,
+        calls: &mut Vec<String>,
+    ) -> Result<(), TypeError> {
+        for node in nodes {
+            self.statement(node, scope, calls)?;
+        }
+        Ok(())
+    }
+
+    fn statement(
+        &self,
+        node: &ASTNode,
+        scope: &mut Scope,
+        calls: &mut Vec<String>,
+    ) -> Result<(), TypeError> {
+        match node {
+            ASTNode::Expression(expression) => {
+                self.expression(expression, scope, calls)?;
+            }
+            ASTNode::Return(inner) => {
+                let ASTNode::Expression(expression) = &**inner else {
+                    return Ok(());
+                };
+                let value = self.expression(expression, scope, calls)?;
+                scope.returned = Some(match scope.returned {
+                    Some(returned) => returned.union(&value),
+                    None => value,
+                });
+            }
+            ASTNode::Assignment(assignment) | ASTNode::Reassignment(assignment) => {
+                let value = self.expression(assignment.get_value(), scope, calls)?;
+                scope
+                    .variables
+                    .insert(assignment.get_name().to_string(), value);
+            }
+            ASTNode::Assert(assertion) => {
+                self.bool(assertion.get_condition(), scope, calls)?;
+            }
+            ASTNode::IfStatement(statement) => {
+                self.bool(statement.get_condition(), scope, calls)?;
+                let mut body = scope.branch();
+                self.block(statement.get_body(), &mut body, calls)?;
+                let mut else_body = scope.branch();
+                self.block(statement.get_else_body(), &mut else_body, calls)?;
+                scope.merge(body, else_body);
+            }
+            ASTNode::ForLoop(for_loop) => {
+                // the loop is unrolled in the circuit so it is here too
+                if for_loop.get_range().len() > MAX_LOOP_ITERATIONS {
+                    return Err(TypeError::TooManyIterations {
+                        function: scope.function.clone(),
+                        iterations: for_loop.get_range().len(),
+                    });
+                }
+                for i in for_loop.get_range() {
+                    scope.variables.insert(
+                        for_loop.get_variable().to_string(),
+                        Checked::Int(Interval::point(i as f64)),
+                    );
+                    self.block(for_loop.get_body(), scope, calls)?;
+                }
+            }
+            ASTNode::Program(_)
+            | ASTNode::FunctionDefinition(_)
+            | ASTNode::ExternFunction(_)
+            | ASTNode::Test(_) => (),
+        }
+        Ok(())
+    }
+
+    fn expression(
+        &self,
+        expression: &Expression,
+        scope: &mut Scope,
+        calls: &mut Vec<String>,
+    ) -> Result<Checked, TypeError> {
+        match expression {
+            Expression::Value(Value::Int(value)) => {
+                Ok(Checked::Int(Interval::point(*value as f64)))
+            }
+            Expression::Value(Value::Float(value)) => Ok(Checked::Float(Interval::point(*value))),
+            Expression::Value(Value::Bool(_)) => Ok(Checked::Bool),
+            Expression::Value(Value::String(_)) => Ok(Checked::String),
+            // anything not in scope becomes an input of the circuit, it can be anything
+            Expression::Identifier(name) => Ok(scope
+                .variables
+                .get(name)
+                .copied()
+                .unwrap_or(Checked::Int(Interval::UNBOUNDED))),
+            Expression::ParenExpression(inner) => self.expression(inner, scope, calls),
+            Expression::Not(inner) => {
+                self.bool(inner, scope, calls)?;
+                Ok(Checked::Bool)
+            }
+            Expression::Dyadic(dyadic) if is_logical(dyadic.get_operator()) => {
+                self.bool(dyadic.get_left(), scope, calls)?;
+                self.bool(dyadic.get_right(), scope, calls)?;
+                Ok(Checked::Bool)
+            }
+            Expression::Dyadic(dyadic) => {
+                let (left, left_float) = self.number(dyadic.get_left(), scope, calls)?;
+                let (right, right_float) = self.number(dyadic.get_right(), scope, calls)?;
+                let float =
+                    left_float || right_float || matches!(dyadic.get_operator(), Operator::Divide);
+                let range = match dyadic.get_operator() {
+                    Operator::Plus => left.add(&right),
+                    Operator::Minus => left.sub(&right),
+                    Operator::Multiply => left.mul(&right),
+                    Operator::Divide => left.div(&right),
+                    // comparisons are always a Bool
+                    Operator::Equal
+                    | Operator::NotEqual
+                    | Operator::LessThan
+                    | Operator::LessThanOrEqual
+                    | Operator::GreaterThan
+                    | Operator::GreaterThanOrEqual => return Ok(Checked::Bool),
+                    Operator::And | Operator::Or => unreachable!("checked above"),
+                };
+                if float {
+                    return Ok(Checked::Float(range));
+                }
+                if range.is_bounded() && !range.is_within(&self.limit) {
+                    return Err(TypeError::Overflow {
+                        function: scope.function.clone(),
+                        range,
+                    });
+                }
+                Ok(Checked::Int(range))
+            }
+            Expression::FunctionCall(call) => self.call(call, scope, calls),
+            Expression::IfExpression(if_expression) => {
+                self.bool(if_expression.get_condition(), scope, calls)?;
+                let body = self.value_block(if_expression.get_body(), scope, calls)?;
+                let else_body = self.value_block(if_expression.get_else_body(), scope, calls)?;
+                Ok(body.union(&else_body))
+            }
+        }
+    }
+
+    fn value_block(
+        &self,
+        block: &ValueBlock,
+        scope: &Scope,
+        calls: &mut Vec<String>,
+    ) -> Result<Checked, TypeError> {
+        let mut inner = scope.branch();
+        self.block(block.get_statements(), &mut inner, calls)?;
+        self.expression(block.get_value(), &mut inner, calls)
+    }
+
+    fn call(
+        &self,
+        call: &FunctionCall,
+        scope: &mut Scope,
+        calls: &mut Vec<String>,
+    ) -> Result<Checked, TypeError> {
+        let mut arguments = vec![];
+        for arg in call.get_args() {
+            arguments.push(match arg {
+                ASTNode::Expression(expression) => self.expression(expression, scope, calls)?,
+                _ => Checked::Int(Interval::UNBOUNDED),
+            });
+        }
+        // a part straight from the registry, nothing is declared about it
+        let Some(signature) = self.functions.get(call.get_name()) else {
+            return Ok(Checked::Int(Interval::UNBOUNDED));
+        };
+        for ((argument, declared), value) in signature.args.iter().zip(&arguments) {
+            if let (Some(allowed), Some((range, false))) = (declared.get_range(), value.as_number())
+            {
+                if !range.is_within(&allowed) {
+                    return Err(TypeError::ArgumentOutOfRange {
+                        function: scope.function.clone(),
+                        call: call.get_name().to_string(),
+                        argument: argument.clone(),
+                        range,
+                        declared: *declared,
+                    });
+                }
+            }
+            self.expect(scope, *declared, *value)?;
+        }
+        self.function(call.get_name(), &arguments, calls)
+    }
+
+    /// an expression that has to be a ```Bool```, like a condition
+    fn bool(
+        &self,
+        expression: &Expression,
+        scope: &mut Scope,
+        calls: &mut Vec<String>,
+    ) -> Result<(), TypeError> {
+        let found = self.expression(expression, scope, calls)?;
+        self.expect(scope, Type::Bool, found)
+    }
+
+    /// an expression that has to be a number, and if it is a ```Float```
+    fn number(
+        &self,
+        expression: &Expression,
+        scope: &mut Scope,
+        calls: &mut Vec<String>,
+    ) -> Result<(Interval, bool), TypeError> {
+        let found = self.expression(expression, scope, calls)?;
+        found.as_number().ok_or_else(|| TypeError::Mismatch {
+            function: scope.function.clone(),
+            expected: Type::Float,
+            found: found.get_type(),
+        })
+    }
+
+    fn expect(&self, scope: &Scope, expected: Type, found: Checked) -> Result<(), TypeError> {
+        let found = found.get_type();
+        let fits = match expected {
+            Type::String => found == Type::String,
+            Type::Float => found != Type::String,
+            Type::Bool => found == Type::Bool,
+            Type::Int | Type::RangedInt { .. } => found == Type::Int || found == Type::Bool,
+        };
+        if !fits {
+            return Err(TypeError::Mismatch {
+                function: scope.function.clone(),
+                expected,
+                found,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn is_logical(operator: &Operator) -> bool {
+    matches!(operator, Operator::And | Operator::Or)
+}
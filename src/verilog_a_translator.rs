@@ -0,0 +1,156 @@
+//! translates a ```Circuit``` to Verilog-A behavioural models
+//!
+//! every circuit becomes a ```module``` with ```electrical``` ports named like the spice subcircuits, function calls
+//! are module instances and every other part is a contribution statement in the ```analog``` block
+//! a part without a Verilog-A model stops the translation instead of leaving its net undriven
+
+use std::collections::BTreeMap;
+
+use crate::{
+    spice_translator::{input_port_names, output_port_names, sanitize, subcircuit_name},
+    translator::Circuit,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerilogAError {
+    /// the name of a part that has no Verilog-A model
+    NoModel(String),
+}
+
+pub fn circuit_to_verilog_a(circuit: &Circuit) -> Result<String, VerilogAError> {
+    let mut verilog_a = format!(
+        "// {} generated by ACL\n`include \"disciplines.vams\"\n",
+        subcircuit_name(circuit)
+    );
+
+    // the functions are defined before anything uses them, same as the netlist
+    let mut functions = BTreeMap::new();
+    collect_functions(circuit, &mut functions);
+    for function in functions.values() {
+        verilog_a.push('\n');
+        verilog_a.push_str(&module(function)?);
+    }
+    verilog_a.push('\n');
+    verilog_a.push_str(&module(circuit)?);
+    Ok(verilog_a)
+}
+
+fn collect_functions(circuit: &Circuit, functions: &mut BTreeMap<String, Circuit>) {
+    for nested in circuit
+        .get_parts()
+        .iter()
+        .filter_map(|part| part.as_circuit())
+    {
+        let name = subcircuit_name(nested);
+        if functions.contains_key(&name) {
+            continue;
+        }
+        collect_functions(nested, functions);
+        functions.insert(name, nested.clone());
+    }
+}
+
+fn module(circuit: &Circuit) -> Result<String, VerilogAError> {
+    let name = subcircuit_name(circuit);
+    let input_names = input_port_names(circuit);
+    let output_names = output_port_names(circuit);
+
+    // nets are named after whatever produces them, like in the netlist
+    let mut net_names = (0..circuit.get_next_output_index())
+        .map(|index| format!("n{}", index))
+        .collect::<Vec<_>>();
+    for (input, name) in circuit.get_program_inputs().iter().zip(&input_names) {
+        net_names[input.index] = name.clone();
+    }
+    let mut drivers = vec![None; circuit.get_next_input_index()];
+    for &(from, to) in circuit.get_connections() {
+        drivers[to] = Some(from);
+    }
+    let consumer_net = |index: usize| match drivers[index] {
+        Some(driver) => net_names[driver].clone(),
+        None => format!("unconnected{}", index),
+    };
+
+    let mut internal_nets = vec![];
+    let mut instances = String::new();
+    let mut analog = String::new();
+    let part_offsets = circuit.get_part_offsets();
+    for ((part, instance_name), offsets) in circuit
+        .get_parts()
+        .iter()
+        .zip(circuit.get_instance_names())
+        .zip(part_offsets)
+    {
+        let inputs = (0..part.get_input_size())
+            .map(|input| consumer_net(offsets.input_offset + input))
+            .collect::<Vec<_>>();
+        let outputs = (0..part.get_output_size())
+            .map(|output| net_names[offsets.output_offset + output].clone())
+            .collect::<Vec<_>>();
+        for net in inputs.iter().filter(|net| net.starts_with("unconnected")) {
+            declare(&mut internal_nets, net);
+        }
+        for net in &outputs {
+            declare(&mut internal_nets, net);
+        }
+
+        match (part.as_circuit(), part.verilog_a(&inputs, &outputs)) {
+            (Some(nested), _) => {
+                let mut ports = inputs;
+                ports.extend(outputs);
+                instances.push_str(&format!(
+                    "    {} {} ({});\n",
+                    subcircuit_name(nested),
+                    sanitize(instance_name),
+                    ports.join(", ")
+                ));
+            }
+            (None, Some(statements)) => {
+                for line in statements.lines() {
+                    analog.push_str(&format!("        {}\n", line));
+                }
+            }
+            (None, None) => return Err(VerilogAError::NoModel(part.get_name())),
+        }
+    }
+
+    // the outputs are driven from their nets so a port is never tied straight to another port
+    for (output_name, &index) in output_names.iter().zip(circuit.get_program_outputs()) {
+        let net = consumer_net(index);
+        if net.starts_with("unconnected") {
+            declare(&mut internal_nets, &net);
+        }
+        analog.push_str(&format!("        V({}) <+ V({});\n", output_name, net));
+    }
+
+    let mut ports = input_names.clone();
+    ports.extend(output_names.iter().cloned());
+    let mut verilog_a = format!("module {}({});\n", name, ports.join(", "));
+    if !input_names.is_empty() {
+        verilog_a.push_str(&format!("    input {};\n", input_names.join(", ")));
+    }
+    if !output_names.is_empty() {
+        verilog_a.push_str(&format!("    output {};\n", output_names.join(", ")));
+    }
+    if !ports.is_empty() {
+        verilog_a.push_str(&format!("    electrical {};\n", ports.join(", ")));
+    }
+    if !internal_nets.is_empty() {
+        verilog_a.push_str(&format!("    electrical {};\n", internal_nets.join(", ")));
+    }
+    if !instances.is_empty() {
+        verilog_a.push('\n');
+        verilog_a.push_str(&instances);
+    }
+    verilog_a.push_str("\n    analog begin\n");
+    verilog_a.push_str(&analog);
+    verilog_a.push_str("    end\nendmodule\n");
+    Ok(verilog_a)
+}
+
+/// every net is declared once, in the order it first shows up
+fn declare(nets: &mut Vec<String>, net: &str) {
+    if !nets.iter().any(|other| other == net) {
+        nets.push(net.to_string());
+    }
+}
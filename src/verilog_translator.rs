@@ -0,0 +1,213 @@
+//! translates a ```Circuit``` to synthesizable verilog
+//!
+//! every value is a signed integer of the same width, every circuit becomes a ```module``` with a ```WIDTH```
+//! parameter, function calls are module instances and every other part is a handful of ```assign``` statements
+//! logic values are 0 or 1 like in the simulator, anything that isn't 0 counts as high
+//! when every input has a declared range the width shrinks to what the values in between need
+//! a ```Float``` constant or quotient has no integer to become, so it stops the translation, and so does any
+//! other part without a verilog model instead of leaving its net undriven
+
+use std::collections::BTreeMap;
+
+use crate::{
+    range_analysis::{self, Interval},
+    spice_translator::{input_port_names, output_port_names, sanitize, subcircuit_name},
+    sub_circuits::Divider,
+    translator::{Circuit, Constant},
+};
+
+/// the width ```Int``` values get when nothing else says otherwise
+pub const DEFAULT_WIDTH: u32 = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerilogError {
+    /// a constant that isn't a whole number, in the module and instance it is in
+    FractionalConstant {
+        module: String,
+        part: String,
+        value: f64,
+    },
+    /// a divider, ```/``` is exact and always gives a ```Float```, truncating it would compute something else
+    FractionalQuotient { module: String, part: String },
+    /// the name of a part that has no verilog model
+    NoModel(String),
+}
+
+pub fn circuit_to_verilog(circuit: &Circuit, width: u32) -> Result<String, VerilogError> {
+    let mut verilog = format!("// {} generated by ACL\n", subcircuit_name(circuit));
+
+    let mut functions = BTreeMap::new();
+    collect_functions(circuit, &mut functions);
+    for function in functions.values() {
+        verilog.push('\n');
+        verilog.push_str(&module(function, width)?);
+    }
+    verilog.push('\n');
+    verilog.push_str(&module(circuit, width)?);
+    Ok(verilog)
+}
+
+/// the bits every net needs, None when an input has no range or a value in between isn't bounded
+fn declared_width(circuit: &Circuit) -> Option<u32> {
+    let inputs = circuit
+        .get_program_inputs()
+        .iter()
+        .map(|input| input.get_range())
+        .collect::<Option<Vec<_>>>()?;
+    let report = range_analysis::analyze(circuit, &inputs, Interval::UNBOUNDED).ok()?;
+    report
+        .get_nets()
+        .iter()
+        .map(|(_, range)| range.is_bounded().then(|| signed_bits(range)))
+        .try_fold(1, |width, bits| Some(width.max(bits?)))
+}
+
+/// the smallest two's complement width holding every whole number the range touches
+fn signed_bits(range: &Interval) -> u32 {
+    let (low, high) = (range.low.floor(), range.high.ceil());
+    (1..64)
+        .find(|&bits| {
+            let limit = 2f64.powi(bits as i32 - 1);
+            -limit <= low && high < limit
+        })
+        .unwrap_or(64)
+}
+
+fn collect_functions(circuit: &Circuit, functions: &mut BTreeMap<String, Circuit>) {
+    for nested in circuit
+        .get_parts()
+        .iter()
+        .filter_map(|part| part.as_circuit())
+    {
+        let name = subcircuit_name(nested);
+        if functions.contains_key(&name) {
+            continue;
+        }
+        collect_functions(nested, functions);
+        functions.insert(name, nested.clone());
+    }
+}
+
+fn module(circuit: &Circuit, width: u32) -> Result<String, VerilogError> {
+    // an instance gets the width of the module around it, this is only the one it has on its own
+    let width = declared_width(circuit).map_or(width, |declared| declared.min(width));
+    let name = subcircuit_name(circuit);
+    let input_names = input_port_names(circuit);
+    let output_names = output_port_names(circuit);
+
+    // nets are named after whatever produces them, like in the netlist
+    let mut net_names = (0..circuit.get_next_output_index())
+        .map(|index| format!("n{}", index))
+        .collect::<Vec<_>>();
+    for (input, name) in circuit.get_program_inputs().iter().zip(&input_names) {
+        net_names[input.index] = name.clone();
+    }
+    let mut drivers = vec![None; circuit.get_next_input_index()];
+    for &(from, to) in circuit.get_connections() {
+        drivers[to] = Some(from);
+    }
+    let consumer_net = |index: usize| match drivers[index] {
+        Some(driver) => net_names[driver].clone(),
+        None => format!("unconnected{}", index),
+    };
+
+    let mut wires = vec![];
+    let mut instances = String::new();
+    let mut assigns = String::new();
+    let part_offsets = circuit.get_part_offsets();
+    for ((part, instance_name), offsets) in circuit
+        .get_parts()
+        .iter()
+        .zip(circuit.get_instance_names())
+        .zip(part_offsets)
+    {
+        let inputs = (0..part.get_input_size())
+            .map(|input| consumer_net(offsets.input_offset + input))
+            .collect::<Vec<_>>();
+        let outputs = (0..part.get_output_size())
+            .map(|output| net_names[offsets.output_offset + output].clone())
+            .collect::<Vec<_>>();
+        for net in inputs.iter().filter(|net| net.starts_with("unconnected")) {
+            declare(&mut wires, net);
+        }
+        for net in &outputs {
+            declare(&mut wires, net);
+        }
+
+        match (part.as_circuit(), part.verilog(&inputs, &outputs)) {
+            (Some(nested), _) => {
+                let mut ports = inputs;
+                ports.extend(outputs);
+                instances.push_str(&format!(
+                    "    {} #(.WIDTH(WIDTH)) {} ({});\n",
+                    subcircuit_name(nested),
+                    sanitize(instance_name),
+                    ports.join(", ")
+                ));
+            }
+            (None, Some(statements)) => {
+                for line in statements.lines() {
+                    assigns.push_str(&format!("    {}\n", line));
+                }
+            }
+            (None, None) => {
+                if let Some(constant) = part.as_any().downcast_ref::<Constant>() {
+                    return Err(VerilogError::FractionalConstant {
+                        module: name,
+                        part: instance_name.clone(),
+                        value: constant.value,
+                    });
+                }
+                if part.as_any().is::<Divider>() {
+                    return Err(VerilogError::FractionalQuotient {
+                        module: name,
+                        part: instance_name.clone(),
+                    });
+                }
+                return Err(VerilogError::NoModel(part.get_name()));
+            }
+        }
+    }
+
+    for (output_name, &index) in output_names.iter().zip(circuit.get_program_outputs()) {
+        let net = consumer_net(index);
+        if net.starts_with("unconnected") {
+            declare(&mut wires, &net);
+        }
+        assigns.push_str(&format!("    assign {} = {};\n", output_name, net));
+    }
+
+    let ports = input_names
+        .iter()
+        .map(|name| format!("    input signed [WIDTH-1:0] {}", name))
+        .chain(
+            output_names
+                .iter()
+                .map(|name| format!("    output signed [WIDTH-1:0] {}", name)),
+        )
+        .collect::<Vec<_>>();
+    let mut verilog = format!(
+        "module {} #(parameter WIDTH = {}) (\n{}\n);\n",
+        name,
+        width,
+        ports.join(",\n")
+    );
+    for wire in &wires {
+        verilog.push_str(&format!("    wire signed [WIDTH-1:0] {};\n", wire));
+    }
+    if !instances.is_empty() {
+        verilog.push('\n');
+        verilog.push_str(&instances);
+    }
+    verilog.push('\n');
+    verilog.push_str(&assigns);
+    verilog.push_str("endmodule\n");
+    Ok(verilog)
+}
+
+/// every net is declared once, in the order it first shows up
+fn declare(nets: &mut Vec<String>, net: &str) {
+    if !nets.iter().any(|other| other == net) {
+        nets.push(net.to_string());
+    }
+}